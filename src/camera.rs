@@ -8,11 +8,46 @@ pub struct Ray {
     a: Vector,
     b: Vector,
     time: f64,
+    // In nm, for rays carrying light of a single wavelength
+    wavelength: Option<f64>,
+    // Shadow rays pass through media, which attenuate them by their transmittance instead
+    shadow: bool,
 }
 
 impl Ray {
     pub fn new(a: Vector, b: Vector, time: f64) -> Ray {
-        Ray { a, b, time }
+        Ray {
+            a,
+            b,
+            time,
+            wavelength: None,
+            shadow: false,
+        }
+    }
+
+    /// A ray continuing the path of this one from a new origin, e.g. after scattering
+    pub fn continued(&self, origin: Vector, direction: Vector) -> Ray {
+        Ray {
+            a: origin,
+            b: direction,
+            ..*self
+        }
+    }
+
+    pub fn with_wavelength(self, wavelength: f64) -> Ray {
+        Ray {
+            wavelength: Some(wavelength),
+            ..self
+        }
+    }
+
+    /// This ray used to check how much light from a light reaches its origin, so that media are
+    /// accounted for by their transmittance instead of being hit
+    pub fn shadow(self) -> Ray {
+        Ray {
+            shadow: true,
+            ..self
+        }
     }
 
     pub fn origin(&self) -> &Vector {
@@ -31,12 +66,16 @@ impl Ray {
         self.time
     }
 
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+
+    pub fn is_shadow(&self) -> bool {
+        self.shadow
+    }
+
     pub fn offset(&self, offset: Vector) -> Ray {
-        Ray {
-            a: self.origin() - offset,
-            b: *self.direction(),
-            time: self.time(),
-        }
+        self.continued(self.origin() - offset, *self.direction())
     }
 }
 
@@ -149,23 +188,27 @@ impl Camera {
     }
 
     pub fn rays(&self, row: u32, col: u32, config: &Config) -> Vec<Ray> {
-        let height = config.height();
-        let width = config.width();
-
         (0..config.num_of_rays())
-            .map(|_| {
-                let row_fuzz: f64 = uniform();
-                let col_fuzz: f64 = uniform();
+            .map(|_| self.random_ray(row, col, config))
+            .collect()
+    }
 
-                let v = f64::from(row) + row_fuzz;
-                let h = f64::from(col) + col_fuzz;
+    /// Ray through a random point of a pixel
+    pub fn random_ray(&self, row: u32, col: u32, config: &Config) -> Ray {
+        let row_fuzz: f64 = uniform();
+        let col_fuzz: f64 = uniform();
 
-                self.ray(h / f64::from(width), v / f64::from(height))
-            })
-            .collect()
+        let v = f64::from(row) + row_fuzz;
+        let h = f64::from(col) + col_fuzz;
+
+        self.ray(
+            h / f64::from(config.width()),
+            v / f64::from(config.height()),
+        )
     }
 
-    fn ray(&self, h: f64, v: f64) -> Ray {
+    /// Ray through a point of the image, given as fractions of its width and height
+    pub fn ray(&self, h: f64, v: f64) -> Ray {
         let rd = self.lens_radius * random_point_in_unit_disk();
         let lens_offset = self.u * rd.x() + self.v * rd.y();
         let time = self.time_start + uniform::<f64>() * (self.time_end - self.time_start);
@@ -175,7 +218,52 @@ impl Camera {
                 - self.origin
                 - lens_offset,
             time,
+            wavelength: None,
+            shadow: false,
+        }
+    }
+
+    /// Pixel, as row and column, of the rays from a point on the lens that pass through a point
+    pub fn pixel_towards(
+        &self,
+        lens_point: &Vector,
+        point: &Vector,
+        config: &Config,
+    ) -> Option<(u32, u32)> {
+        let direction = point - lens_point;
+        let forward = -Vector::dot(&direction, &self.w);
+        if forward <= 0.0 {
+            return None;
+        }
+
+        // Where the ray crosses the plane in focus, which holds the image
+        let focus_distance = -Vector::dot(&(self.lower_left_corner - self.origin), &self.w);
+        let in_focus = lens_point + direction * (focus_distance / forward) - self.lower_left_corner;
+        let h = Vector::dot(&in_focus, &self.horizontal) / self.horizontal.len_squared();
+        let v = Vector::dot(&in_focus, &self.vertical) / self.vertical.len_squared();
+        if !(0.0..1.0).contains(&h) || !(0.0..1.0).contains(&v) {
+            return None;
         }
+
+        let row = (v * f64::from(config.height())) as u32;
+        let col = (h * f64::from(config.width())) as u32;
+        if row >= config.height() || col >= config.width() {
+            return None;
+        }
+        Some((row, col))
+    }
+
+    /// Density with respect to solid angle of the directions of rays over the whole image
+    pub fn direction_pdf(&self, direction: &Vector) -> f64 {
+        let cosine = -Vector::dot(&direction.unit_vector(), &self.w);
+        if cosine <= 0.0 {
+            return 0.0;
+        }
+
+        // Area of the image at unit distance from the lens
+        let focus_distance = -Vector::dot(&(self.lower_left_corner - self.origin), &self.w);
+        let area = self.horizontal.len() * self.vertical.len() / focus_distance.powi(2);
+        1.0 / (area * cosine.powi(3))
     }
 
     pub fn time_start(&self) -> f64 {
@@ -197,3 +285,66 @@ fn random_point_in_unit_disk() -> Vector {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::tests::{build_config, white};
+    use crate::renderer::Integrator;
+    use crate::world::geometry::rectangle::XzRect;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn config() -> Config {
+        let floor = XzRect::build((-5.0, 5.0), (-5.0, 5.0), 0.0, white());
+        build_config(vec![floor], vec![], Integrator::Bidirectional)
+    }
+
+    #[test]
+    fn test_pixel_towards_finds_pixel_of_ray() {
+        let config = config();
+        let camera = config.camera();
+        for row in 0..config.height() {
+            for col in 0..config.width() {
+                let ray = camera.random_ray(row, col, &config);
+                let point = ray.point(3.0);
+                assert_eq!(
+                    camera.pixel_towards(ray.origin(), &point, &config),
+                    Some((row, col))
+                );
+            }
+        }
+
+        let behind = camera.origin + camera.w;
+        assert_eq!(camera.pixel_towards(&camera.origin, &behind, &config), None);
+    }
+
+    #[test]
+    fn test_direction_pdf_over_image() {
+        // Looking straight down with a 90 degree field of view, so the image is 10 by 10 at the
+        // focus distance of 5
+        let config = config();
+        let camera = config.camera();
+        assert_approx_eq!(camera.direction_pdf(&Vector::new(0.0, -1.0, 0.0)), 0.25);
+        assert_approx_eq!(
+            camera.direction_pdf(&Vector::new(1.0, -1.0, 1.0)),
+            3.0_f64.powf(1.5) / 4.0
+        );
+        assert_approx_eq!(camera.direction_pdf(&Vector::new(0.0, 1.0, 0.0)), 0.0);
+
+        // Integrating over the solid angle of the image, one small patch of it at a time
+        let steps = 200;
+        let patch_area = 100.0 / f64::from(steps * steps);
+        let mut total = 0.0;
+        for i in 0..steps {
+            for j in 0..steps {
+                let h = (f64::from(i) + 0.5) / f64::from(steps);
+                let v = (f64::from(j) + 0.5) / f64::from(steps);
+                let direction = *camera.ray(h, v).direction();
+                let distance = direction.len();
+                let solid_angle = patch_area * (5.0 / distance) / (distance * distance);
+                total += camera.direction_pdf(&direction) * solid_angle;
+            }
+        }
+        assert_approx_eq!(total, 1.0, 1e-3);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::io::SUPPORTED_IMAGE_EXT;
+use crate::renderer::Integrator;
 use crate::scenes::Scene;
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use std::str::FromStr;
@@ -30,6 +31,14 @@ impl ImagePath {
     }
 }
 
+/// Path depths given on the command line, which override the ones set by the scene
+pub struct PathDepthArgs {
+    pub diffuse: Option<u64>,
+    pub specular: Option<u64>,
+    pub volume: Option<u64>,
+    pub roulette: Option<u64>,
+}
+
 pub enum CliCommand {
     RENDER {
         width: u32,
@@ -37,6 +46,10 @@ pub enum CliCommand {
         num_of_rays: u64,
         num_of_threads: usize,
         asset_paths: Vec<ImagePath>,
+        spectral: bool,
+        integrator: Integrator,
+        large_step_probability: f64,
+        path_depths: PathDepthArgs,
     },
     GENERATE {
         scene: Scene,
@@ -133,8 +146,85 @@ pub fn get_cli_config() -> Result<CliConfig, anyhow::Error> {
                         .required(false)
                         .multiple(true)
                         .help(
-                            "the paths to image assets needed by the selected scene. The \
-                             filename must be unique amongst all loaded assets",
+                            "the paths to image and density grid (.grid) assets needed by the \
+                             selected scene. The filename must be unique amongst all loaded \
+                             assets",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("spectral")
+                        .long("spectral")
+                        .takes_value(false)
+                        .required(false)
+                        .help(
+                            "render with light of a single random wavelength per ray instead \
+                             of RGB colours",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("integrator")
+                        .short("i")
+                        .long("integrator")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("path")
+                        .possible_values(&Integrator::variants())
+                        .case_insensitive(true)
+                        .help(
+                            "the algorithm used to find the light arriving along each ray. \
+                             Bidirectional is slower per ray but better at caustics and \
+                             small or enclosed lights. PhotonMapping renders caustics cleanly, \
+                             taking one pass over the image per ray. Metropolis explores the \
+                             paths around those that carry light, for scenes lit through small \
+                             openings",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("large_step_probability")
+                        .long("large-step-probability")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0.3")
+                        .help(
+                            "the probability of the Metropolis integrator proposing a whole new \
+                             path instead of a small change to the current one",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("max_diffuse_depth")
+                        .long("max-diffuse-depth")
+                        .takes_value(true)
+                        .required(false)
+                        .help("the maximum number of diffuse bounces, overrides the scene"),
+                )
+                .arg(
+                    Arg::with_name("max_specular_depth")
+                        .long("max-specular-depth")
+                        .takes_value(true)
+                        .required(false)
+                        .help(
+                            "the maximum number of specular bounces, e.g. through glass, \
+                             overrides the scene",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("max_volume_depth")
+                        .long("max-volume-depth")
+                        .takes_value(true)
+                        .required(false)
+                        .help(
+                            "the maximum number of scattering events inside media, overrides \
+                             the scene",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("roulette_depth")
+                        .long("roulette-depth")
+                        .takes_value(true)
+                        .required(false)
+                        .help(
+                            "the number of bounces after which paths carrying little light \
+                             are randomly terminated, overrides the scene",
                         ),
                 ),
             SubCommand::with_name("generate")
@@ -166,6 +256,15 @@ pub fn get_cli_config() -> Result<CliConfig, anyhow::Error> {
             .unwrap_or_default()
             .map(|path| ImagePath(String::from(path)))
             .collect();
+        let spectral = subcommand.is_present("spectral");
+        let integrator = parse::<Integrator>(subcommand, "integrator")?;
+        let large_step_probability = parse_probability(subcommand, "large_step_probability")?;
+        let path_depths = PathDepthArgs {
+            diffuse: parse_optional::<u64>(subcommand, "max_diffuse_depth")?,
+            specular: parse_optional::<u64>(subcommand, "max_specular_depth")?,
+            volume: parse_optional::<u64>(subcommand, "max_volume_depth")?,
+            roulette: parse_optional::<u64>(subcommand, "roulette_depth")?,
+        };
 
         validate_output_path(&output_path)?;
 
@@ -176,6 +275,10 @@ pub fn get_cli_config() -> Result<CliConfig, anyhow::Error> {
                 num_of_rays,
                 num_of_threads,
                 asset_paths,
+                spectral,
+                integrator,
+                large_step_probability,
+                path_depths,
             },
             config_path: ConfigPath(config_path),
         });
@@ -226,3 +329,25 @@ fn parse<T: FromStr>(matches: &ArgMatches, arg: &str) -> Result<T, CliParsingErr
         }),
     }
 }
+
+fn parse_probability(matches: &ArgMatches, arg: &str) -> Result<f64, CliParsingError> {
+    let probability = parse::<f64>(matches, arg)?;
+    if !(0.0..=1.0).contains(&probability) {
+        return Err(CliParsingError::InvalidValue {
+            arg: String::from(arg),
+            value: probability.to_string(),
+        });
+    }
+    Ok(probability)
+}
+
+fn parse_optional<T: FromStr>(
+    matches: &ArgMatches,
+    arg: &str,
+) -> Result<Option<T>, CliParsingError> {
+    if matches.is_present(arg) {
+        Ok(Some(parse::<T>(matches, arg)?))
+    } else {
+        Ok(None)
+    }
+}
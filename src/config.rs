@@ -1,8 +1,13 @@
 use crate::camera::{Camera, CameraSave};
+use crate::cli::PathDepthArgs;
 use crate::data::assets::Assets;
-use crate::world::background::Background;
+use crate::renderer::Integrator;
+use crate::world::background::{Background, EnvironmentSampler};
+use crate::world::fog::FoggyGeometry;
 use crate::world::geometry::bounding_volume_hierarchy::BoundingVolumeHierarchyNode;
+use crate::world::geometry::light_tree::LightTree;
 use crate::world::geometry::{Geometry, Hittable};
+use crate::world::lights::Light;
 use crate::world::WorldSave;
 
 pub struct Config {
@@ -10,9 +15,17 @@ pub struct Config {
     height: u32,
     camera: Camera,
     background: Background,
-    bvh: Geometry,
+    environment_sampler: Option<EnvironmentSampler>,
+    // Scene geometry in a bounding volume hierarchy, seen through the fog if there is any
+    bvh: FoggyGeometry,
     attractors: Vec<Geometry>,
+    light_tree: Option<LightTree>,
+    lights: Vec<Light>,
     num_of_rays: u64,
+    spectral: bool,
+    integrator: Integrator,
+    large_step_probability: f64,
+    path_depths: PathDepths,
     assets: Assets,
 }
 
@@ -21,6 +34,41 @@ pub struct ConfigSave {
     aspect: f64,
     camera: CameraSave,
     world: WorldSave,
+    #[serde(default)]
+    path_depths: PathDepths,
+}
+
+/// Maximum number of bounces of each kind along a path. Past the roulette depth paths are
+/// randomly terminated based on how much light they can still carry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathDepths {
+    pub diffuse: u64,
+    pub specular: u64,
+    pub volume: u64,
+    pub roulette: u64,
+}
+
+impl Default for PathDepths {
+    fn default() -> PathDepths {
+        PathDepths {
+            diffuse: 50,
+            specular: 50,
+            volume: 50,
+            roulette: 5,
+        }
+    }
+}
+
+impl PathDepths {
+    fn with_overrides(self, args: &PathDepthArgs) -> PathDepths {
+        PathDepths {
+            diffuse: args.diffuse.unwrap_or(self.diffuse),
+            specular: args.specular.unwrap_or(self.specular),
+            volume: args.volume.unwrap_or(self.volume),
+            roulette: args.roulette.unwrap_or(self.roulette),
+        }
+    }
 }
 
 impl Config {
@@ -40,11 +88,19 @@ impl Config {
         self.num_of_rays
     }
 
+    pub fn spectral(&self) -> bool {
+        self.spectral
+    }
+
     pub fn background(&self) -> &Background {
         &self.background
     }
 
-    pub fn bvh(&self) -> &Geometry {
+    pub fn environment_sampler(&self) -> Option<&EnvironmentSampler> {
+        self.environment_sampler.as_ref()
+    }
+
+    pub fn bvh(&self) -> &FoggyGeometry {
         &self.bvh
     }
 
@@ -52,6 +108,26 @@ impl Config {
         &self.attractors
     }
 
+    pub fn light_tree(&self) -> Option<&LightTree> {
+        self.light_tree.as_ref()
+    }
+
+    pub fn lights(&self) -> &Vec<Light> {
+        &self.lights
+    }
+
+    pub fn integrator(&self) -> Integrator {
+        self.integrator
+    }
+
+    pub fn large_step_probability(&self) -> f64 {
+        self.large_step_probability
+    }
+
+    pub fn path_depths(&self) -> &PathDepths {
+        &self.path_depths
+    }
+
     pub fn assets(&self) -> &Assets {
         &self.assets
     }
@@ -63,33 +139,61 @@ impl ConfigSave {
             aspect,
             camera,
             world,
+            path_depths: PathDepths::default(),
         }
     }
 
-    pub fn into_config(mut self, width: u32, num_of_rays: u64, assets: Assets) -> Config {
+    #[allow(clippy::too_many_arguments)]
+    pub fn into_config(
+        mut self,
+        width: u32,
+        num_of_rays: u64,
+        spectral: bool,
+        integrator: Integrator,
+        large_step_probability: f64,
+        path_depth_args: &PathDepthArgs,
+        assets: Assets,
+    ) -> Config {
         let camera = self.camera.into_camera();
 
         let time_start = camera.time_start();
         let time_end = camera.time_end();
 
-        let geometries = self.world.drain_geometries();
+        let mut geometries = self.world.drain_geometries();
+        for geometry in geometries.iter_mut() {
+            geometry.load_assets(&assets);
+        }
 
-        let attractors = geometries
+        // Emitting attractors are sampled as lights, the rest only help scattering, e.g. glass
+        let (lights, attractors): (Vec<Geometry>, Vec<Geometry>) = geometries
             .iter()
             .filter(|g| g.is_attractor())
             .cloned()
-            .collect();
+            .partition(|g| g.power(&assets) > 0.0);
+
+        let light_tree = LightTree::build(lights, time_start, time_end, &assets);
+        let bvh = FoggyGeometry::new(
+            BoundingVolumeHierarchyNode::build(geometries, time_start, time_end),
+            self.world.fog().cloned(),
+        );
 
-        let bvh = BoundingVolumeHierarchyNode::build(geometries, time_start, time_end);
+        let environment_sampler = self.world.background().sampler(&assets);
 
         Config {
             width,
             height: (f64::from(width) / self.aspect) as u32,
             camera,
             background: self.world.background().clone(),
+            environment_sampler,
             bvh,
             attractors,
+            light_tree,
+            lights: self.world.lights().clone(),
             num_of_rays,
+            spectral,
+            integrator,
+            large_step_probability,
+            path_depths: self.path_depths.with_overrides(path_depth_args),
             assets,
         }
     }
@@ -157,6 +261,7 @@ mod tests {
                     Material::Metal {
                         albedo: Colour::new(0.8, 0.6, 0.2),
                         fuzz: 0.1,
+                        thin_film: None,
                     },
                 ),
                 Sphere::build(
@@ -164,9 +269,15 @@ mod tests {
                     -0.45,
                     Material::Dielectric {
                         refractive_index: 1.5,
+                        thin_film: None,
+                        dispersion: None,
                     },
                 ),
             ],
+            vec![Light::Point {
+                position: Vector::new(0.0, 2.0, 0.0),
+                intensity: Colour::new(4.0, 4.0, 4.0),
+            }],
         );
 
         let serialised = serde_yaml::to_string(&world).unwrap();
@@ -176,6 +287,7 @@ mod tests {
             world.drain_geometries().len(),
             deserialised.drain_geometries().len()
         );
+        assert_eq!(world.lights(), deserialised.lights());
     }
 
     #[test]
@@ -200,14 +312,72 @@ mod tests {
                     },
                 },
             )],
+            vec![],
         );
         let saved_config = ConfigSave {
             aspect: 1.5,
             camera,
             world,
+            path_depths: PathDepths {
+                diffuse: 4,
+                ..PathDepths::default()
+            },
         };
 
         let serialised = serde_yaml::to_string(&saved_config).unwrap();
-        serde_yaml::from_str::<ConfigSave>(&serialised).unwrap();
+        let deserialised = serde_yaml::from_str::<ConfigSave>(&serialised).unwrap();
+
+        assert_eq!(saved_config.path_depths, deserialised.path_depths);
+    }
+
+    #[test]
+    fn test_path_depths_default_when_missing() {
+        let path_depths: PathDepths = serde_yaml::from_str("specular: 200").unwrap();
+
+        assert_eq!(path_depths.specular, 200);
+        assert_eq!(path_depths.diffuse, PathDepths::default().diffuse);
+
+        let args = PathDepthArgs {
+            diffuse: Some(2),
+            specular: None,
+            volume: None,
+            roulette: Some(0),
+        };
+        let overridden = path_depths.with_overrides(&args);
+        assert_eq!(overridden.diffuse, 2);
+        assert_eq!(overridden.specular, 200);
+        assert_eq!(overridden.roulette, 0);
+    }
+
+    #[test]
+    fn test_deserialise_world_with_old_background() {
+        let yaml = "
+background:
+  top:
+    r: 0.5
+    g: 0.7
+    b: 1.0
+  bottom:
+    r: 1.0
+    g: 1.0
+    b: 1.0
+geometries: []
+";
+        let world: WorldSave = serde_yaml::from_str(yaml).unwrap();
+        let assets = Assets::new(&[]).unwrap();
+
+        match world.background() {
+            Background::Gradient { top, bottom } => {
+                assert_eq!(*top, Colour::new(0.5, 0.7, 1.0));
+                assert_eq!(*bottom, Colour::new(1.0, 1.0, 1.0));
+            }
+            background => panic!("expected a gradient, got {:?}", background),
+        }
+        assert_eq!(
+            world
+                .background()
+                .value(&Vector::new(0.0, 1.0, 0.0), &assets),
+            Colour::new(0.5, 0.7, 1.0)
+        );
     }
 }
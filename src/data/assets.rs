@@ -1,7 +1,9 @@
 use crate::cli::ImagePath;
+use crate::data::density_grid::DensityGrid;
 use crate::data::image::Image;
-use crate::io::load_image;
+use crate::io::{load_density_grid, load_image, DENSITY_GRID_EXT};
 use std::collections::HashMap;
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -12,25 +14,37 @@ pub enum AssetValidationError {
 
 pub struct Assets {
     assets: HashMap<String, Image>,
+    // Shared with the media built from them
+    density_grids: HashMap<String, Arc<DensityGrid>>,
 }
 
 impl Assets {
     pub fn new(asset_paths: &[ImagePath]) -> Result<Assets, anyhow::Error> {
         let mut assets: HashMap<String, Image> = HashMap::new();
+        let mut density_grids: HashMap<String, Arc<DensityGrid>> = HashMap::new();
         for asset_path in asset_paths {
-            assets.insert(
-                String::from(asset_path.file_name()),
-                load_image(asset_path)?,
-            );
+            let asset_name = String::from(asset_path.file_name());
+            if asset_path.path().ends_with(DENSITY_GRID_EXT) {
+                density_grids.insert(asset_name, Arc::new(load_density_grid(asset_path)?));
+            } else {
+                assets.insert(asset_name, load_image(asset_path)?);
+            }
         }
 
-        Ok(Assets { assets })
+        Ok(Assets {
+            assets,
+            density_grids,
+        })
     }
 
     pub fn get_asset(&self, asset_name: &str) -> &Image {
         &self.assets[asset_name]
     }
 
+    pub fn get_density_grid(&self, asset_name: &str) -> Arc<DensityGrid> {
+        Arc::clone(&self.density_grids[asset_name])
+    }
+
     pub fn validate(&self, asset_name: &str) -> Result<(), AssetValidationError> {
         if self.assets.get(asset_name).is_some() {
             Ok(())
@@ -40,4 +54,14 @@ impl Assets {
             })
         }
     }
+
+    pub fn validate_density_grid(&self, asset_name: &str) -> Result<(), AssetValidationError> {
+        if self.density_grids.contains_key(asset_name) {
+            Ok(())
+        } else {
+            Err(AssetValidationError::MissingAsset {
+                asset_name: String::from(asset_name),
+            })
+        }
+    }
 }
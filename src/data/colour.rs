@@ -1,6 +1,6 @@
 use image::Rgb;
 use std::cmp::min;
-use std::iter::Sum;
+use std::iter::{Product, Sum};
 use std::ops;
 
 const RGB_MULT: f64 = 255.99;
@@ -47,6 +47,11 @@ impl Colour {
         }
     }
 
+    pub fn luminance(&self) -> f64 {
+        // Relative luminance using the Rec. 709 primaries
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
     pub fn dot(lhs: &Colour, rhs: &Colour) -> f64 {
         lhs.r * rhs.r + lhs.g * rhs.g + lhs.b * rhs.b
     }
@@ -101,6 +106,20 @@ impl Sum<Colour> for Colour {
     }
 }
 
+impl Product<Colour> for Colour {
+    fn product<I: Iterator<Item = Colour>>(iter: I) -> Colour {
+        let mut product = Colour {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+        };
+        for colour in iter {
+            product = product * colour;
+        }
+        product
+    }
+}
+
 fn add_colours(lhs: &Colour, rhs: &Colour) -> Colour {
     Colour {
         r: lhs.r + rhs.r,
@@ -314,6 +333,13 @@ mod tests {
         assert_approx_eq!(colour.len(), expected_result);
     }
 
+    #[test]
+    fn test_colour_luminance() {
+        assert_approx_eq!(Colour::new(1.0, 1.0, 1.0).luminance(), 1.0);
+        assert_approx_eq!(Colour::new(0.5, 0.5, 0.5).luminance(), 0.5);
+        assert_approx_eq!(Colour::new(0.0, 1.0, 0.0).luminance(), 0.7152);
+    }
+
     #[test]
     fn test_colour_unit_vector() {
         let colour = Colour::new(1.0, 2.0, 3.0);
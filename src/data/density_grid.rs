@@ -0,0 +1,122 @@
+use crate::data::vector::Vector;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DensityGridError {
+    #[error("density grid must start with its size as three positive whole numbers")]
+    InvalidSize,
+    #[error("invalid density <{0}>, densities must be non-negative numbers")]
+    InvalidValue(String),
+    #[error("density grid has {found} values but its size needs {expected}")]
+    WrongNumberOfValues { expected: usize, found: usize },
+}
+
+/// Densities at the voxels of a box, e.g. a simulated smoke plume. Grids are read from text
+/// starting with the number of voxels along x, y and z, followed by the density of every voxel
+/// with x changing fastest and z slowest, all separated by whitespace
+#[derive(Debug)]
+pub struct DensityGrid {
+    size: [usize; 3],
+    values: Vec<f64>,
+    max: f64,
+}
+
+impl DensityGrid {
+    pub fn parse(text: &str) -> Result<DensityGrid, DensityGridError> {
+        let mut tokens = text.split_whitespace();
+
+        let mut size = [0; 3];
+        for axis_size in size.iter_mut() {
+            *axis_size = tokens
+                .next()
+                .and_then(|token| token.parse::<usize>().ok())
+                .filter(|axis_size| *axis_size > 0)
+                .ok_or(DensityGridError::InvalidSize)?;
+        }
+
+        let values = tokens
+            .map(|token| match token.parse::<f64>() {
+                Ok(value) if value >= 0.0 && value.is_finite() => Ok(value),
+                _ => Err(DensityGridError::InvalidValue(String::from(token))),
+            })
+            .collect::<Result<Vec<f64>, DensityGridError>>()?;
+
+        let expected = size[0] * size[1] * size[2];
+        if values.len() != expected {
+            return Err(DensityGridError::WrongNumberOfValues {
+                expected,
+                found: values.len(),
+            });
+        }
+
+        let max = values.iter().cloned().fold(0.0, f64::max);
+        Ok(DensityGrid { size, values, max })
+    }
+
+    /// Density at a point given as its share of the way across the grid along each axis,
+    /// interpolated between the centres of the nearest voxels
+    pub fn value(&self, coords: &Vector) -> f64 {
+        let (x0, x1, tx) = self.neighbours(coords.x(), 0);
+        let (y0, y1, ty) = self.neighbours(coords.y(), 1);
+        let (z0, z1, tz) = self.neighbours(coords.z(), 2);
+
+        let lerp = |a: f64, b: f64, t: f64| a + t * (b - a);
+        let along_x = |y, z| lerp(self.voxel(x0, y, z), self.voxel(x1, y, z), tx);
+        let along_y = |z| lerp(along_x(y0, z), along_x(y1, z), ty);
+        lerp(along_y(z0), along_y(z1), tz)
+    }
+
+    /// Highest density anywhere in the grid
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    fn neighbours(&self, coord: f64, axis: usize) -> (usize, usize, f64) {
+        let last = self.size[axis] - 1;
+        let position = (coord * self.size[axis] as f64 - 0.5)
+            .max(0.0)
+            .min(last as f64);
+        let lower = position.floor() as usize;
+        (lower, usize::min(lower + 1, last), position - lower as f64)
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        self.values[x + self.size[0] * (y + self.size[1] * z)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_density_grid_interpolates_between_voxels() {
+        let grid = DensityGrid::parse("2 1 1\n0.0 4.0\n").unwrap();
+        assert_approx_eq!(grid.max(), 4.0);
+        // Voxel centres sit at a quarter and three quarters of the way across
+        assert_approx_eq!(grid.value(&Vector::new(0.25, 0.5, 0.5)), 0.0);
+        assert_approx_eq!(grid.value(&Vector::new(0.5, 0.5, 0.5)), 2.0);
+        assert_approx_eq!(grid.value(&Vector::new(0.75, 0.5, 0.5)), 4.0);
+        assert_approx_eq!(grid.value(&Vector::new(1.0, 0.0, 1.0)), 4.0);
+    }
+
+    #[test]
+    fn test_density_grid_rejects_bad_files() {
+        assert!(matches!(
+            DensityGrid::parse("2 0 1 1.0"),
+            Err(DensityGridError::InvalidSize)
+        ));
+        assert!(matches!(
+            DensityGrid::parse("1 1 1 -1.0"),
+            Err(DensityGridError::InvalidValue(_))
+        ));
+        assert!(matches!(
+            DensityGrid::parse("2 2 1 1.0 2.0"),
+            Err(DensityGridError::WrongNumberOfValues {
+                expected: 4,
+                found: 2
+            })
+        ));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::data::colour::Colour;
-use image::{DynamicImage, ImageBuffer, RgbImage};
+use image::{DynamicImage, ImageBuffer, Rgb, RgbImage, RgbaImage};
 
 pub struct Pixel {
     row: u32,
@@ -12,6 +12,7 @@ pub struct Image {
     width: u32,
     height: u32,
     pixels: Vec<Vec<Colour>>,
+    alphas: Vec<Vec<f64>>,
 }
 
 impl Pixel {
@@ -33,6 +34,10 @@ impl Image {
         &self.pixels[row as usize][col as usize]
     }
 
+    pub fn get_alpha(&self, row: u32, col: u32) -> f64 {
+        self.alphas[row as usize][col as usize]
+    }
+
     pub fn into_rgb_image(self) -> RgbImage {
         let mut image: RgbImage = ImageBuffer::new(self.width, self.height);
 
@@ -66,6 +71,7 @@ impl From<&Vec<Pixel>> for Image {
             width,
             height,
             pixels: pixel_matrix,
+            alphas: vec![vec![1.0; width as usize]; height as usize],
         }
     }
 }
@@ -90,9 +96,34 @@ impl From<&RgbImage> for Image {
     }
 }
 
+impl From<&RgbaImage> for Image {
+    fn from(image: &RgbaImage) -> Self {
+        let (width, height) = image.dimensions();
+
+        let mut pixels: Vec<Pixel> = vec![];
+        let mut alphas: Vec<Vec<f64>> = vec![vec![1.0; width as usize]; height as usize];
+        for x in 0..width {
+            for y in 0..height {
+                let rgba = image.get_pixel(x, y);
+                let colour = Colour::from(&Rgb([rgba[0], rgba[1], rgba[2]]));
+
+                // Translate from the coordinate system expected by the image crate
+                let pixel = Pixel::new(height - y - 1, x, colour);
+                alphas[pixel.row as usize][pixel.col as usize] = f64::from(rgba[3]) / 255.0;
+                pixels.push(pixel);
+            }
+        }
+
+        Image {
+            alphas,
+            ..Image::from(&pixels)
+        }
+    }
+}
+
 impl From<&DynamicImage> for Image {
     fn from(image: &DynamicImage) -> Self {
-        let image = image.to_rgb();
+        let image = image.to_rgba();
         Image::from(&image)
     }
 }
@@ -148,4 +179,26 @@ mod tests {
         assert_colour_eq(image.get_pixel(1, 0), &Colour::new(0.0, 1.0, 0.0));
         assert_colour_eq(image.get_pixel(1, 1), &Colour::new(1.0, 0.0, 0.0));
     }
+
+    #[test]
+    fn test_image_from_rgba_image_keeps_alpha() {
+        let mut rgba_image: RgbaImage = ImageBuffer::new(2, 1);
+        rgba_image.put_pixel(0, 0, image::Rgba([255, 0, 0, 255]));
+        rgba_image.put_pixel(1, 0, image::Rgba([0, 255, 0, 0]));
+
+        let image = Image::from(&rgba_image);
+
+        assert_colour_eq(image.get_pixel(0, 0), &Colour::new(1.0, 0.0, 0.0));
+        assert_colour_eq(image.get_pixel(0, 1), &Colour::new(0.0, 1.0, 0.0));
+        assert_approx_eq!(image.get_alpha(0, 0), 1.0);
+        assert_approx_eq!(image.get_alpha(0, 1), 0.0);
+    }
+
+    #[test]
+    fn test_image_from_pixels_is_opaque() {
+        let pixels = vec![Pixel::new(0, 0, Colour::new(0.0, 0.0, 0.0))];
+        let image = Image::from(&pixels);
+
+        assert_approx_eq!(image.get_alpha(0, 0), 1.0);
+    }
 }
@@ -0,0 +1,138 @@
+use crate::data::vector::Vector;
+
+/// Points with a value each, split in half along the widest axis at every level so that the
+/// points near a position can be found without checking all of them. The tree is stored in a
+/// single list where every node sits between the nodes on either side of it
+pub struct KdTree<T> {
+    items: Vec<(Vector, T)>,
+    axes: Vec<usize>,
+}
+
+impl<T> KdTree<T> {
+    pub fn build(mut items: Vec<(Vector, T)>) -> KdTree<T> {
+        let mut axes = vec![0; items.len()];
+        split(&mut items, &mut axes);
+        KdTree { items, axes }
+    }
+
+    /// Calls the visitor with every point within the radius of a position, and its value
+    pub fn within<F>(&self, position: &Vector, radius: f64, mut visitor: F)
+    where
+        F: FnMut(&Vector, &T),
+    {
+        self.visit(0, self.items.len(), position, radius, &mut visitor);
+    }
+
+    fn visit<F>(&self, start: usize, end: usize, position: &Vector, radius: f64, visitor: &mut F)
+    where
+        F: FnMut(&Vector, &T),
+    {
+        if start >= end {
+            return;
+        }
+
+        let middle = start + (end - start) / 2;
+        let (point, value) = &self.items[middle];
+        if (point - position).len_squared() <= radius * radius {
+            visitor(point, value);
+        }
+
+        let axis = self.axes[middle];
+        let offset = coordinate(position, axis) - coordinate(point, axis);
+        if offset <= radius {
+            self.visit(start, middle, position, radius, visitor);
+        }
+        if offset >= -radius {
+            self.visit(middle + 1, end, position, radius, visitor);
+        }
+    }
+}
+
+fn split<T>(items: &mut [(Vector, T)], axes: &mut [usize]) {
+    if items.is_empty() {
+        return;
+    }
+
+    let axis = widest_axis(items);
+    let middle = items.len() / 2;
+    items.select_nth_unstable_by(middle, |(a, _), (b, _)| {
+        coordinate(a, axis)
+            .partial_cmp(&coordinate(b, axis))
+            .unwrap()
+    });
+    axes[middle] = axis;
+
+    let (lower_items, upper_items) = items.split_at_mut(middle);
+    let (lower_axes, upper_axes) = axes.split_at_mut(middle);
+    split(lower_items, lower_axes);
+    split(&mut upper_items[1..], &mut upper_axes[1..]);
+}
+
+fn widest_axis<T>(items: &[(Vector, T)]) -> usize {
+    let first = items[0].0;
+    let (min, max) = items.iter().fold((first, first), |(min, max), (point, _)| {
+        (min.min(point), max.max(point))
+    });
+    let extent = max - min;
+
+    if extent.x() >= extent.y() && extent.x() >= extent.z() {
+        0
+    } else if extent.y() >= extent.z() {
+        1
+    } else {
+        2
+    }
+}
+
+fn coordinate(point: &Vector, axis: usize) -> f64 {
+    match axis {
+        0 => point.x(),
+        1 => point.y(),
+        _ => point.z(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::uniform_between;
+
+    #[test]
+    fn test_kd_tree_within_matches_every_point_in_radius() {
+        let points: Vec<Vector> = (0..500)
+            .map(|_| {
+                Vector::new(
+                    uniform_between(-10.0, 10.0),
+                    uniform_between(-10.0, 10.0),
+                    uniform_between(-1.0, 1.0),
+                )
+            })
+            .collect();
+        let tree = KdTree::build(
+            points
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(i, p)| (p, i))
+                .collect(),
+        );
+
+        let position = Vector::new(1.0, -2.0, 0.5);
+        let radius = 3.0;
+        let mut found = vec![];
+        tree.within(&position, radius, |_, index| found.push(*index));
+        found.sort();
+
+        let expected: Vec<usize> = (0..points.len())
+            .filter(|i| (points[*i] - position).len() <= radius)
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_kd_tree_empty() {
+        let tree: KdTree<()> = KdTree::build(vec![]);
+        tree.within(&Vector::new(0.0, 0.0, 0.0), 1.0, |_, _| panic!("No points"));
+    }
+}
@@ -1,4 +1,7 @@
 pub mod assets;
 pub mod colour;
+pub mod density_grid;
 pub mod image;
+pub mod kd_tree;
+pub mod spectrum;
 pub mod vector;
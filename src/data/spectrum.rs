@@ -0,0 +1,382 @@
+//! Conversions between single wavelengths of light and colours, for effects that depend on the
+//! wavelength such as dispersion and for spectral rendering
+
+use crate::data::colour::Colour;
+use crate::float;
+use crate::pdf::uniform_between;
+use thiserror::Error;
+
+// Visible range in nm
+pub const WAVELENGTH_MIN: f64 = 380.0;
+pub const WAVELENGTH_MAX: f64 = 780.0;
+
+// Average of the linear sRGB colour of every wavelength over the visible range, used to
+// normalise wavelength weights so that a flat spectrum is white
+const AVERAGE_WAVELENGTH_COLOUR: [f64; 3] = [0.320_907, 0.253_872, 0.242_624];
+
+// Number of wavelengths used when converting a spectrum to a colour
+const SPECTRUM_TO_RGB_STEPS: usize = 40;
+
+// Spectra used to upsample RGB colours, from "An RGB to Spectrum Conversion for Reflectances"
+// by Smits. Each has 10 bins of equal width between 380 nm and 720 nm
+const SMITS_WAVELENGTH_MAX: f64 = 720.0;
+const SMITS_WHITE: [f64; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [f64; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [f64; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [f64; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f64; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f64; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [f64; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+// CIE standard illuminant D65 from 380 nm to 780 nm in steps of 10 nm, relative to 560 nm
+const D65_STEP: f64 = 10.0;
+const D65: [f64; 41] = [
+    49.9755, 54.6482, 82.7549, 91.4860, 93.4318, 86.6823, 104.8650, 117.0080, 117.8120, 114.8610,
+    115.9230, 108.8110, 109.3540, 107.8020, 104.7900, 107.6890, 104.4050, 104.0460, 100.0000,
+    96.3342, 95.7880, 88.6856, 90.0062, 89.5991, 87.6987, 83.2886, 83.6992, 80.0268, 80.2146,
+    82.2778, 78.2842, 69.7213, 71.6091, 74.3490, 61.6040, 69.8856, 75.0870, 63.5927, 46.4182,
+    66.8054, 63.3828,
+];
+
+// Physical constants in SI units
+const PLANCK: f64 = 6.626_070_15e-34;
+const SPEED_OF_LIGHT: f64 = 2.997_924_58e8;
+const BOLTZMANN: f64 = 1.380_649e-23;
+const WIEN_DISPLACEMENT: f64 = 2.897_772_1e-3;
+
+#[derive(Debug, Error)]
+pub enum SpectrumError {
+    #[error("blackbody temperature <{0}> must be positive")]
+    InvalidTemperature(f64),
+    #[error("tabulated spectrum must have at least one value")]
+    EmptyTable,
+    #[error("tabulated spectrum step <{0}> must be positive")]
+    InvalidStep(f64),
+}
+
+/// Emission spectra, with values relative to their peak or to 560 nm for standard illuminants
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Spectrum {
+    // In K
+    Blackbody {
+        temperature: f64,
+    },
+    // Daylight
+    D65,
+    // Incandescent tungsten light
+    A,
+    // Measured data, e.g. the F series of fluorescent illuminants. Values are linearly
+    // interpolated and zero outside the table
+    Tabulated {
+        start: f64,
+        step: f64,
+        values: Vec<f64>,
+    },
+}
+
+impl Spectrum {
+    pub fn value(&self, wavelength: f64) -> f64 {
+        match self {
+            Spectrum::Blackbody { temperature } => {
+                let peak = WIEN_DISPLACEMENT / temperature * 1e9;
+                planck(wavelength, *temperature) / planck(peak, *temperature)
+            }
+            Spectrum::D65 => interpolate(&D65, WAVELENGTH_MIN, D65_STEP, wavelength) / 100.0,
+            Spectrum::A => {
+                let c2 = 1.435e7;
+                f64::powi(560.0 / wavelength, 5) * f64::exp_m1(c2 / (2848.0 * 560.0))
+                    / f64::exp_m1(c2 / (2848.0 * wavelength))
+            }
+            Spectrum::Tabulated {
+                start,
+                step,
+                values,
+            } => interpolate(values, *start, *step, wavelength),
+        }
+    }
+
+    pub fn to_rgb(&self) -> Colour {
+        let range = WAVELENGTH_MAX - WAVELENGTH_MIN;
+        let total: Colour = (0..SPECTRUM_TO_RGB_STEPS)
+            .map(|step| {
+                let t = (step as f64 + 0.5) / SPECTRUM_TO_RGB_STEPS as f64;
+                let wavelength = WAVELENGTH_MIN + t * range;
+                self.value(wavelength) * wavelength_weight(wavelength)
+            })
+            .sum();
+        total / SPECTRUM_TO_RGB_STEPS as f64
+    }
+
+    pub fn validate(&self) -> Result<(), SpectrumError> {
+        match self {
+            Spectrum::Blackbody { temperature } if *temperature <= 0.0 => {
+                Err(SpectrumError::InvalidTemperature(*temperature))
+            }
+            Spectrum::Tabulated { values, .. } if values.is_empty() => {
+                Err(SpectrumError::EmptyTable)
+            }
+            Spectrum::Tabulated { step, .. } if *step <= 0.0 => {
+                Err(SpectrumError::InvalidStep(*step))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn sample_wavelength() -> f64 {
+    uniform_between(WAVELENGTH_MIN, WAVELENGTH_MAX)
+}
+
+/// CIE 1931 colour matching functions, using the multi-lobe fit by Wyman, Sloan and Shirley
+pub fn xyz(wavelength: f64) -> (f64, f64, f64) {
+    let x = 1.056 * lobe(wavelength, 599.8, 37.9, 31.0)
+        + 0.362 * lobe(wavelength, 442.0, 16.0, 26.7)
+        - 0.065 * lobe(wavelength, 501.1, 20.4, 26.2);
+    let y =
+        0.821 * lobe(wavelength, 568.8, 46.9, 40.5) + 0.286 * lobe(wavelength, 530.9, 16.3, 31.1);
+    let z =
+        1.217 * lobe(wavelength, 437.0, 11.8, 36.0) + 0.681 * lobe(wavelength, 459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+/// Linear sRGB with a D65 white point
+pub fn xyz_to_rgb(x: f64, y: f64, z: f64) -> Colour {
+    Colour::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    )
+}
+
+/// Weight to apply to light of a single wavelength picked uniformly over the visible range,
+/// so that the weights of many such wavelengths average to white. Spectral colours are outside
+/// the sRGB gamut, so some channels are negative
+pub fn wavelength_weight(wavelength: f64) -> Colour {
+    let (x, y, z) = xyz(wavelength);
+    let colour = xyz_to_rgb(x, y, z);
+
+    Colour::new(
+        colour.r() / AVERAGE_WAVELENGTH_COLOUR[0],
+        colour.g() / AVERAGE_WAVELENGTH_COLOUR[1],
+        colour.b() / AVERAGE_WAVELENGTH_COLOUR[2],
+    )
+}
+
+/// Value at the given wavelength of a spectrum with the given colour, using Smits' method. White
+/// gives a flat spectrum
+pub fn rgb_to_spectrum(colour: &Colour, wavelength: f64) -> f64 {
+    let bin = (wavelength - WAVELENGTH_MIN) / (SMITS_WAVELENGTH_MAX - WAVELENGTH_MIN) * 10.0;
+    let bin = float::min(9.0, float::max(0.0, bin)) as usize;
+
+    let (r, g, b) = (colour.r(), colour.g(), colour.b());
+    if r <= g && r <= b {
+        let value = r * SMITS_WHITE[bin];
+        if g <= b {
+            value + (g - r) * SMITS_CYAN[bin] + (b - g) * SMITS_BLUE[bin]
+        } else {
+            value + (b - r) * SMITS_CYAN[bin] + (g - b) * SMITS_GREEN[bin]
+        }
+    } else if g <= r && g <= b {
+        let value = g * SMITS_WHITE[bin];
+        if r <= b {
+            value + (r - g) * SMITS_MAGENTA[bin] + (b - r) * SMITS_BLUE[bin]
+        } else {
+            value + (b - g) * SMITS_MAGENTA[bin] + (r - b) * SMITS_RED[bin]
+        }
+    } else {
+        let value = b * SMITS_WHITE[bin];
+        if r <= g {
+            value + (r - b) * SMITS_YELLOW[bin] + (g - r) * SMITS_GREEN[bin]
+        } else {
+            value + (g - b) * SMITS_YELLOW[bin] + (r - g) * SMITS_RED[bin]
+        }
+    }
+}
+
+/// Rays carrying a single wavelength see every colour as the value of its spectrum at that
+/// wavelength, which is stored in all three channels
+pub fn at_wavelength(colour: &Colour, wavelength: Option<f64>) -> Colour {
+    match wavelength {
+        Some(wavelength) => {
+            let value = rgb_to_spectrum(colour, wavelength);
+            Colour::new(value, value, value)
+        }
+        None => *colour,
+    }
+}
+
+/// Spectral radiance of a blackbody in W / (sr m³)
+pub fn planck(wavelength: f64, temperature: f64) -> f64 {
+    let metres = wavelength * 1e-9;
+    2.0 * PLANCK * SPEED_OF_LIGHT * SPEED_OF_LIGHT
+        / (f64::powi(metres, 5)
+            * f64::exp_m1(PLANCK * SPEED_OF_LIGHT / (metres * BOLTZMANN * temperature)))
+}
+
+fn interpolate(values: &[f64], start: f64, step: f64, wavelength: f64) -> f64 {
+    let t = (wavelength - start) / step;
+    if t < 0.0 || t > (values.len() - 1) as f64 {
+        return 0.0;
+    }
+
+    let index = t as usize;
+    if index + 1 == values.len() {
+        return values[index];
+    }
+    let fraction = t - index as f64;
+    values[index] * (1.0 - fraction) + values[index + 1] * fraction
+}
+
+fn lobe(wavelength: f64, mean: f64, width_below: f64, width_above: f64) -> f64 {
+    let width = if wavelength < mean {
+        width_below
+    } else {
+        width_above
+    };
+    let t = (wavelength - mean) / width;
+    f64::exp(-0.5 * t * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_wavelength_weights_average_to_white() {
+        let steps = 4000;
+        let total: Colour = (0..steps)
+            .map(|step| {
+                let t = (step as f64 + 0.5) / steps as f64;
+                wavelength_weight(WAVELENGTH_MIN + t * (WAVELENGTH_MAX - WAVELENGTH_MIN))
+            })
+            .sum();
+        let average = total / steps as f64;
+
+        assert_approx_eq!(average.r(), 1.0, 1e-3);
+        assert_approx_eq!(average.g(), 1.0, 1e-3);
+        assert_approx_eq!(average.b(), 1.0, 1e-3);
+    }
+
+    #[test]
+    fn test_wavelength_weight_hue() {
+        let red = wavelength_weight(650.0);
+        let green = wavelength_weight(530.0);
+        let blue = wavelength_weight(450.0);
+
+        assert!(red.r() > red.g() && red.r() > red.b());
+        assert!(green.g() > green.r() && green.g() > green.b());
+        assert!(blue.b() > blue.r() && blue.b() > blue.g());
+    }
+
+    fn spectrum_of_colour_to_rgb(colour: &Colour) -> Colour {
+        let steps = 4000;
+        let total: Colour = (0..steps)
+            .map(|step| {
+                let t = (step as f64 + 0.5) / steps as f64;
+                let wavelength = WAVELENGTH_MIN + t * (WAVELENGTH_MAX - WAVELENGTH_MIN);
+                rgb_to_spectrum(colour, wavelength) * wavelength_weight(wavelength)
+            })
+            .sum();
+        total / steps as f64
+    }
+
+    #[test]
+    fn test_rgb_to_spectrum_roundtrip() {
+        for colour in [
+            Colour::new(1.0, 1.0, 1.0),
+            Colour::new(0.65, 0.05, 0.05),
+            Colour::new(0.12, 0.45, 0.15),
+            Colour::new(0.8, 0.6, 0.2),
+            Colour::new(0.1, 0.2, 0.5),
+        ]
+        .iter()
+        {
+            let roundtrip = spectrum_of_colour_to_rgb(colour);
+
+            assert_approx_eq!(roundtrip.r(), colour.r(), 0.02);
+            assert_approx_eq!(roundtrip.g(), colour.g(), 0.02);
+            assert_approx_eq!(roundtrip.b(), colour.b(), 0.02);
+        }
+    }
+
+    #[test]
+    fn test_white_is_a_flat_spectrum() {
+        let white = Colour::new(2.0, 2.0, 2.0);
+
+        for &wavelength in [400.0, 550.0, 700.0].iter() {
+            assert_approx_eq!(rgb_to_spectrum(&white, wavelength), 2.0, 1e-2);
+        }
+    }
+
+    #[test]
+    fn test_blackbody_is_one_at_peak() {
+        let spectrum = Spectrum::Blackbody {
+            temperature: 5000.0,
+        };
+
+        assert_approx_eq!(spectrum.value(579.55), 1.0, 1e-6);
+        assert!(spectrum.value(450.0) < 1.0);
+        assert!(spectrum.value(700.0) < 1.0);
+    }
+
+    #[test]
+    fn test_standard_illuminants_at_560_nm() {
+        assert_approx_eq!(Spectrum::D65.value(560.0), 1.0);
+        assert_approx_eq!(Spectrum::A.value(560.0), 1.0);
+    }
+
+    #[test]
+    fn test_illuminant_colours() {
+        let incandescent = Spectrum::A.to_rgb();
+        let hot = Spectrum::Blackbody {
+            temperature: 10000.0,
+        }
+        .to_rgb();
+
+        assert!(incandescent.r() > incandescent.g() && incandescent.g() > incandescent.b());
+        assert!(hot.b() > hot.g() && hot.g() > hot.r());
+    }
+
+    #[test]
+    fn test_tabulated_spectrum_interpolates() {
+        let spectrum = Spectrum::Tabulated {
+            start: 400.0,
+            step: 100.0,
+            values: vec![0.0, 1.0, 0.5],
+        };
+
+        assert_approx_eq!(spectrum.value(450.0), 0.5);
+        assert_approx_eq!(spectrum.value(600.0), 0.5);
+        assert_approx_eq!(spectrum.value(650.0), 0.0);
+        assert_approx_eq!(spectrum.value(390.0), 0.0);
+    }
+
+    #[test]
+    fn test_tabulated_spectrum_is_validated() {
+        let tabulated = |step: f64, values: Vec<f64>| Spectrum::Tabulated {
+            start: 400.0,
+            step,
+            values,
+        };
+
+        assert!(tabulated(10.0, vec![1.0, 2.0]).validate().is_ok());
+        assert!(tabulated(10.0, vec![]).validate().is_err());
+        assert!(tabulated(0.0, vec![1.0, 2.0]).validate().is_err());
+        assert!(tabulated(-10.0, vec![1.0, 2.0]).validate().is_err());
+    }
+}
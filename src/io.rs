@@ -1,19 +1,56 @@
 use crate::cli::{ConfigPath, ImagePath, OutputPath};
 use crate::config::ConfigSave;
-use crate::data::image::Image;
+use crate::data::colour::Colour;
+use crate::data::density_grid::DensityGrid;
+use crate::data::image::{Image, Pixel};
+use image::hdr::HDRDecoder;
 use std;
+use std::fs::File;
+use std::io::BufReader;
 
-pub const SUPPORTED_IMAGE_EXT: [&str; 4] = [".ppm", ".jpeg", ".jpg", ".png"];
+pub const SUPPORTED_IMAGE_EXT: [&str; 5] = [".ppm", ".jpeg", ".jpg", ".png", ".hdr"];
+pub const DENSITY_GRID_EXT: &str = ".grid";
 
 pub fn write_image(image: Image, output_path: &OutputPath) -> std::io::Result<()> {
     image.into_rgb_image().save(output_path.path())
 }
 
 pub fn load_image(image_path: &ImagePath) -> Result<Image, anyhow::Error> {
+    if image_path.path().ends_with(".hdr") {
+        return load_hdr_image(image_path);
+    }
     let image = image::open(image_path.path())?;
     Ok(Image::from(&image))
 }
 
+pub fn load_density_grid(grid_path: &ImagePath) -> Result<DensityGrid, anyhow::Error> {
+    let read = std::fs::read_to_string(grid_path.path())?;
+    Ok(DensityGrid::parse(&read)?)
+}
+
+fn load_hdr_image(image_path: &ImagePath) -> Result<Image, anyhow::Error> {
+    // Keep the full range of HDR images instead of clamping them to 8 bit colours
+    let decoder = HDRDecoder::new(BufReader::new(File::open(image_path.path())?))?;
+    let metadata = decoder.metadata();
+    let (width, height) = (metadata.width, metadata.height);
+
+    let pixels: Vec<Pixel> = decoder
+        .read_image_hdr()?
+        .iter()
+        .enumerate()
+        .map(|(index, rgb)| {
+            let x = index as u32 % width;
+            let y = index as u32 / width;
+            let colour = Colour::new(f64::from(rgb[0]), f64::from(rgb[1]), f64::from(rgb[2]));
+
+            // Translate from the coordinate system expected by the image crate
+            Pixel::new(height - y - 1, x, colour)
+        })
+        .collect();
+
+    Ok(Image::from(&pixels))
+}
+
 pub fn save_config(config_path: &ConfigPath, config_save: ConfigSave) -> Result<(), anyhow::Error> {
     let serialised = serde_yaml::to_string(&config_save)?;
     std::fs::write(config_path.path(), serialised)?;
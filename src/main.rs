@@ -17,11 +17,11 @@ mod renderer;
 mod scenes;
 mod world;
 
-use crate::cli::{get_cli_config, CliCommand, ConfigPath, ImagePath, OutputPath};
+use crate::cli::{get_cli_config, CliCommand, ConfigPath, ImagePath, OutputPath, PathDepthArgs};
 use crate::config::Config;
 use crate::data::assets::Assets;
 use crate::io::{load_config, save_config};
-use crate::renderer::render;
+use crate::renderer::{render, Integrator};
 use crate::scenes::{build_scene_config, Scene};
 use console::style;
 use indicatif::{FormattedDuration, ProgressBar, ProgressStyle};
@@ -47,6 +47,10 @@ fn run() -> Result<(), anyhow::Error> {
             num_of_rays,
             num_of_threads,
             asset_paths,
+            spectral,
+            integrator,
+            large_step_probability,
+            path_depths,
         } => {
             run_render(
                 &cli_config.config_path(),
@@ -55,6 +59,10 @@ fn run() -> Result<(), anyhow::Error> {
                 *num_of_rays,
                 *num_of_threads,
                 asset_paths,
+                *spectral,
+                *integrator,
+                *large_step_probability,
+                path_depths,
             )?;
         }
         CliCommand::GENERATE { scene } => {
@@ -65,6 +73,7 @@ fn run() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_render(
     config_path: &ConfigPath,
     width: u32,
@@ -72,6 +81,10 @@ fn run_render(
     num_of_rays: u64,
     num_of_threads: usize,
     asset_paths: &[ImagePath],
+    spectral: bool,
+    integrator: Integrator,
+    large_step_probability: f64,
+    path_depths: &PathDepthArgs,
 ) -> Result<(), anyhow::Error> {
     rayon::ThreadPoolBuilder::new()
         .num_threads(num_of_threads)
@@ -91,7 +104,15 @@ fn run_render(
     config_save.validate(&assets)?;
 
     step_logger.log("Creating config (constructing BVH)");
-    let config = config_save.into_config(width, num_of_rays, assets);
+    let config = config_save.into_config(
+        width,
+        num_of_rays,
+        spectral,
+        integrator,
+        large_step_probability,
+        path_depths,
+        assets,
+    );
 
     step_logger.log("Rendering");
     let progress_bar = progress_bar(&config);
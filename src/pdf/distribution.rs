@@ -0,0 +1,178 @@
+//! Piecewise constant distributions for importance sampling tabulated functions, e.g. the
+//! brightness of the pixels of an environment map. Samples are in [0, 1) along each dimension
+
+use crate::float;
+
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    function: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(function: Vec<f64>) -> Distribution1D {
+        let n = function.len() as f64;
+
+        let mut cdf = vec![0.0; function.len() + 1];
+        for i in 1..cdf.len() {
+            cdf[i] = cdf[i - 1] + function[i - 1] / n;
+        }
+        let integral = cdf[function.len()];
+
+        // A function that is zero everywhere is sampled uniformly
+        for (i, value) in cdf.iter_mut().enumerate() {
+            *value = if integral > 0.0 {
+                *value / integral
+            } else {
+                i as f64 / n
+            };
+        }
+
+        Distribution1D {
+            function,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    pub fn len(&self) -> usize {
+        self.function.len()
+    }
+
+    /// Returns the sample and the index of the piece it falls in
+    pub fn sample(&self, random: f64) -> (f64, usize) {
+        // Last piece whose cdf is not above the random number, skipping empty pieces
+        let index = match self
+            .cdf
+            .binary_search_by(|value| value.partial_cmp(&random).unwrap())
+        {
+            Ok(index) => index,
+            Err(index) => index - 1,
+        };
+        let index = usize::min(index, self.len() - 1);
+        let index = (index..self.len())
+            .find(|&i| self.cdf[i + 1] > self.cdf[i])
+            .unwrap_or(index);
+
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (random - self.cdf[index]) / width
+        } else {
+            0.0
+        };
+        let offset = float::min(float::max(offset, 0.0), 1.0 - f64::EPSILON);
+
+        ((index as f64 + offset) / self.len() as f64, index)
+    }
+
+    pub fn pdf(&self, index: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.function[index] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    // One distribution along u for every row along v
+    conditionals: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Takes the function values as rows along v, each with values along u
+    pub fn new(function: Vec<Vec<f64>>) -> Distribution2D {
+        let conditionals: Vec<Distribution1D> =
+            function.into_iter().map(Distribution1D::new).collect();
+        let marginal = Distribution1D::new(
+            conditionals
+                .iter()
+                .map(|conditional| conditional.integral())
+                .collect(),
+        );
+
+        Distribution2D {
+            conditionals,
+            marginal,
+        }
+    }
+
+    /// Returns (u, v) and the pdf with respect to area in the unit square
+    pub fn sample(&self, random_u: f64, random_v: f64) -> ((f64, f64), f64) {
+        let (v, row) = self.marginal.sample(random_v);
+        let (u, _) = self.conditionals[row].sample(random_u);
+
+        ((u, v), self.pdf(u, v))
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = usize::min(
+            (v * self.marginal.len() as f64) as usize,
+            self.marginal.len() - 1,
+        );
+        let conditional = &self.conditionals[row];
+        let col = usize::min(
+            (u * conditional.len() as f64) as usize,
+            conditional.len() - 1,
+        );
+
+        conditional.pdf(col) * self.marginal.pdf(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_distribution_1d_skips_empty_pieces() {
+        let distribution = Distribution1D::new(vec![0.0, 1.0, 0.0, 3.0]);
+
+        for &random in [0.0, 0.1, 0.25, 0.5, 0.9, 0.999].iter() {
+            let (x, index) = distribution.sample(random);
+            assert!(index == 1 || index == 3);
+            assert!(x >= index as f64 / 4.0 && x < (index + 1) as f64 / 4.0);
+        }
+
+        assert_approx_eq!(distribution.pdf(1), 1.0);
+        assert_approx_eq!(distribution.pdf(3), 3.0);
+    }
+
+    #[test]
+    fn test_distribution_1d_of_zeros_is_uniform() {
+        let distribution = Distribution1D::new(vec![0.0, 0.0]);
+
+        let (x, index) = distribution.sample(0.75);
+        assert_approx_eq!(x, 0.75);
+        assert_eq!(index, 1);
+        assert_approx_eq!(distribution.pdf(0), 1.0);
+    }
+
+    #[test]
+    fn test_distribution_2d_pdf_integrates_to_one() {
+        let distribution = Distribution2D::new(vec![vec![1.0, 2.0, 0.0], vec![4.0, 0.5, 1.0]]);
+
+        let steps = 60;
+        let mut total = 0.0;
+        for i in 0..steps {
+            for j in 0..steps {
+                let u = (i as f64 + 0.5) / steps as f64;
+                let v = (j as f64 + 0.5) / steps as f64;
+                total += distribution.pdf(u, v) / (steps * steps) as f64;
+            }
+        }
+        assert_approx_eq!(total, 1.0);
+
+        let ((u, v), pdf) = distribution.sample(0.3, 0.8);
+        assert!(v >= 0.5);
+        assert_approx_eq!(pdf, distribution.pdf(u, v));
+    }
+}
@@ -0,0 +1,12 @@
+//! PDF that samples towards the lights of a scene, picked by the light tree
+
+use crate::data::vector::Vector;
+use crate::world::geometry::light_tree::LightTree;
+
+pub fn value(light_tree: &LightTree, origin: &Vector, direction: &Vector) -> f64 {
+    light_tree.value(origin, direction)
+}
+
+pub fn generate(light_tree: &LightTree, origin: &Vector) -> Vector {
+    light_tree.generate(origin)
+}
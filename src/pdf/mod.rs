@@ -12,14 +12,20 @@
 
 use crate::data::vector::Vector;
 use crate::onb::Onb;
+use crate::world::background::EnvironmentSampler;
+use crate::world::geometry::light_tree::LightTree;
 use crate::world::geometry::Geometry;
+use crate::world::materials::phase::PhaseFunction;
 use rand::distributions::uniform::SampleUniform;
 use rand::distributions::Standard;
 use rand::prelude::*;
 
 mod cosine;
+pub mod distribution;
 mod geometry;
+mod lights;
 mod mixture;
+pub mod primary_samples;
 
 #[derive(Debug)]
 pub enum Pdf<'a> {
@@ -28,6 +34,16 @@ pub enum Pdf<'a> {
         geometries: &'a Vec<Geometry>,
         origin: Vector,
     },
+    Lights {
+        light_tree: &'a LightTree,
+        origin: Vector,
+    },
+    Environment(&'a EnvironmentSampler),
+    // Scattering inside a medium of light travelling in the incoming direction
+    Phase {
+        phase: PhaseFunction,
+        incoming: Vector,
+    },
     Mixture(Box<Pdf<'a>>, Box<Pdf<'a>>),
 }
 
@@ -42,6 +58,9 @@ impl Pdf<'_> {
             Pdf::Geometry { geometries, origin } => {
                 geometry::value(&geometries, &origin, direction)
             }
+            Pdf::Lights { light_tree, origin } => lights::value(light_tree, origin, direction),
+            Pdf::Environment(sampler) => sampler.value(direction),
+            Pdf::Phase { phase, incoming } => phase.value(incoming, direction),
             Pdf::Mixture(pdf_a, pdf_b) => mixture::value(&pdf_a, &pdf_b, direction),
         }
     }
@@ -50,17 +69,34 @@ impl Pdf<'_> {
         match self {
             Pdf::Cosine(onb) => cosine::generate(&onb),
             Pdf::Geometry { geometries, origin } => geometry::generate(&geometries, &origin),
+            Pdf::Lights { light_tree, origin } => lights::generate(light_tree, origin),
+            Pdf::Environment(sampler) => sampler.generate(),
+            Pdf::Phase { phase, incoming } => phase.generate(incoming),
             Pdf::Mixture(pdf_a, pdf_b) => mixture::generate(&pdf_a, &pdf_b),
         }
     }
 }
 
+/// Weight of a sample taken with one strategy when it could also have been taken with another,
+/// given the pdf of the direction under each of them
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let pdf_squared = pdf * pdf;
+    let total = pdf_squared + other_pdf * other_pdf;
+    if total > 0.0 {
+        pdf_squared / total
+    } else {
+        0.0
+    }
+}
+
 pub fn uniform<T>() -> T
 where
     Standard: Distribution<T>,
 {
-    let mut rng = rand::thread_rng();
-    rng.gen::<T>()
+    match primary_samples::running() {
+        Some(mut rng) => rng.gen::<T>(),
+        None => rand::thread_rng().gen::<T>(),
+    }
 }
 
 pub fn uniform_between<T>(low: T, high: T) -> T
@@ -68,8 +104,10 @@ where
     Standard: Distribution<T>,
     T: SampleUniform,
 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range::<T, T, T>(low, high)
+    match primary_samples::running() {
+        Some(mut rng) => rng.gen_range::<T, T, T>(low, high),
+        None => rand::thread_rng().gen_range::<T, T, T>(low, high),
+    }
 }
 
 pub fn random_point_in_unit_sphere() -> Vector {
@@ -82,3 +120,26 @@ pub fn random_point_in_unit_sphere() -> Vector {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_power_heuristic_weights() {
+        assert_approx_eq!(power_heuristic(2.0, 2.0), 0.5);
+        assert_approx_eq!(power_heuristic(3.0, 1.0), 0.9);
+        assert_approx_eq!(power_heuristic(1.0, 3.0), 0.1);
+        assert_approx_eq!(power_heuristic(1.0, 0.0), 1.0);
+        assert_approx_eq!(power_heuristic(0.0, 0.0), 0.0);
+
+        // Weights of the two strategies for the same sample add up to one
+        for &(pdf, other_pdf) in [(0.3, 1.7), (5.0, 0.01), (0.25, 0.25)].iter() {
+            assert_approx_eq!(
+                power_heuristic(pdf, other_pdf) + power_heuristic(other_pdf, pdf),
+                1.0
+            );
+        }
+    }
+}
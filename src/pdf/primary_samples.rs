@@ -0,0 +1,221 @@
+//! Random numbers that can be replayed and mutated, so that a Markov chain can explore the space
+//! of the random numbers consumed by a renderer, following Kelemen et al., "A Simple and Robust
+//! Mutation Strategy for the Metropolis Light Transport Algorithm" (2002)
+//!
+//! While samples are running on a thread every random number drawn there through this module is
+//! taken from them, in the order they are drawn. Samples are only mutated when they are first
+//! drawn in an iteration, so a renderer can use as many of them as it likes
+
+use rand::distributions::StandardNormal;
+use rand::rngs::SmallRng;
+use rand::{Error, Rng, RngCore, SeedableRng};
+use std::cell::RefCell;
+
+// Standard deviation of the small steps taken by every sample
+const MUTATION_SIZE: f64 = 0.01;
+
+thread_local! {
+    static RUNNING: RefCell<Option<PrimarySamples>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PrimarySample {
+    value: f64,
+    // Iteration in which the value was last changed
+    modified: u64,
+    // State before the current iteration, restored when a proposal is rejected
+    backup_value: f64,
+    backup_modified: u64,
+}
+
+/// Current state of a Markov chain over random numbers, which proposes the next state either as
+/// a small step away from it or, with the large step probability, as a fresh set of numbers
+#[derive(Debug, Clone)]
+pub struct PrimarySamples {
+    rng: SmallRng,
+    samples: Vec<PrimarySample>,
+    index: usize,
+    iteration: u64,
+    large_step: bool,
+    last_large_step: u64,
+    large_step_probability: f64,
+}
+
+impl PrimarySamples {
+    /// Samples whose first run draws fresh random numbers. Samples built with the same seed draw
+    /// the same numbers
+    pub fn new(seed: u64, large_step_probability: f64) -> PrimarySamples {
+        PrimarySamples {
+            rng: SmallRng::seed_from_u64(seed),
+            samples: vec![],
+            index: 0,
+            iteration: 0,
+            large_step: true,
+            last_large_step: 0,
+            large_step_probability,
+        }
+    }
+
+    /// Proposes the next state of the chain, which is drawn lazily during the next run
+    pub fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.gen::<f64>() < self.large_step_probability;
+        self.index = 0;
+    }
+
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    pub fn reject(&mut self) {
+        let iteration = self.iteration;
+        for sample in self.samples.iter_mut() {
+            if sample.modified == iteration {
+                sample.value = sample.backup_value;
+                sample.modified = sample.backup_modified;
+            }
+        }
+        self.iteration -= 1;
+    }
+
+    /// Runs a function with every random number drawn by this thread taken from these samples
+    pub fn run<T, F>(self, function: F) -> (T, PrimarySamples)
+    where
+        F: FnOnce() -> T,
+    {
+        RUNNING.with(|running| *running.borrow_mut() = Some(self));
+        let result = function();
+        let samples = RUNNING.with(|running| running.borrow_mut().take().unwrap());
+        (result, samples)
+    }
+
+    fn next(&mut self) -> f64 {
+        if self.index >= self.samples.len() {
+            // Numbers never drawn before start out uniformly random, as any step from them is
+            let value = self.rng.gen();
+            self.samples.push(PrimarySample {
+                value,
+                modified: self.last_large_step,
+                backup_value: value,
+                backup_modified: self.last_large_step,
+            });
+        }
+        let index = self.index;
+        self.index += 1;
+
+        // Samples left untouched since the last accepted large step take part in it late
+        let mut sample = self.samples[index];
+        if sample.modified < self.last_large_step {
+            sample.value = self.rng.gen();
+            sample.modified = self.last_large_step;
+        }
+        sample.backup_value = sample.value;
+        sample.backup_modified = sample.modified;
+
+        if self.large_step {
+            sample.value = self.rng.gen();
+        } else {
+            // Every small step missed since the last change is taken at once
+            let num_of_steps = (self.iteration - sample.modified) as f64;
+            let step: f64 = self.rng.sample(StandardNormal);
+            let value = sample.value + step * MUTATION_SIZE * num_of_steps.sqrt();
+            sample.value = value - value.floor();
+            if sample.value >= 1.0 {
+                sample.value = 0.0;
+            }
+        }
+        sample.modified = self.iteration;
+
+        self.samples[index] = sample;
+        sample.value
+    }
+}
+
+/// Generator for random numbers drawn while samples are running on this thread
+pub fn running() -> Option<PrimarySampleRng> {
+    RUNNING.with(|running| running.borrow().as_ref().map(|_| PrimarySampleRng))
+}
+
+/// Turns the running samples into the bits the `rand` distributions are built from, so that a
+/// float drawn from them is the sample itself
+pub struct PrimarySampleRng;
+
+impl PrimarySampleRng {
+    fn next_sample(&self) -> f64 {
+        RUNNING.with(|running| running.borrow_mut().as_mut().unwrap().next())
+    }
+}
+
+impl RngCore for PrimarySampleRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_sample() * f64::from(u32::MAX)) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        // Floats take the top 53 bits
+        let max = (1_u64 << 53) - 1;
+        let bits = (self.next_sample() * (1_u64 << 53) as f64) as u64;
+        u64::min(bits, max) << 11
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::{uniform, uniform_between};
+    use assert_approx_eq::assert_approx_eq;
+
+    fn draw() -> Vec<f64> {
+        vec![uniform(), uniform(), uniform_between(2.0, 4.0)]
+    }
+
+    #[test]
+    fn test_primary_samples_replay_with_same_seed() {
+        let (first, _) = PrimarySamples::new(7, 0.3).run(draw);
+        let (second, _) = PrimarySamples::new(7, 0.3).run(draw);
+        assert_eq!(first, second);
+        assert!(first[2] >= 2.0 && first[2] < 4.0);
+    }
+
+    #[test]
+    fn test_primary_samples_small_step_and_reject() {
+        let (initial, mut samples) = PrimarySamples::new(3, 0.0).run(draw);
+
+        samples.start_iteration();
+        let (proposed, mut samples) = samples.run(draw);
+        assert_ne!(initial, proposed);
+        for (a, b) in initial.iter().zip(proposed.iter()).take(2) {
+            // Small steps wrap around the unit interval
+            let distance = f64::min((a - b).abs(), 1.0 - (a - b).abs());
+            assert!(distance < 0.1);
+        }
+
+        samples.reject();
+        assert_eq!(samples.iteration, 0);
+        for (sample, value) in samples.samples.iter().zip(initial.iter()).take(2) {
+            assert_approx_eq!(sample.value, *value);
+        }
+    }
+
+    #[test]
+    fn test_no_primary_samples_running() {
+        assert!(running().is_none());
+        let (is_running, _) = PrimarySamples::new(1, 0.3).run(|| running().is_some());
+        assert!(is_running);
+        assert!(running().is_none());
+    }
+}
@@ -0,0 +1,849 @@
+//! Bidirectional path tracing, following Veach, "Robust Monte Carlo Methods for Light Transport
+//! Simulation" (1997). Every camera ray is extended into a camera subpath and paired with a
+//! subpath traced from a point on a light, then every vertex of one is connected to every vertex
+//! of the other. Each complete path could have been built by several of these strategies, so
+//! contributions are weighted against all of them with the power heuristic. This finds light the
+//! path tracer struggles with, e.g. caustics seen on diffuse surfaces and small enclosed lights
+//!
+//! Light subpath vertices connected to the camera itself land on whichever pixel they are seen
+//! through, so they are splatted onto the image rather than added to the pixel of the camera ray.
+//! Only lights with geometry take part in the bidirectional strategies, the environment and
+//! lights without geometry are sampled from the camera subpath as in the path tracer. Specular
+//! bounces can not be connected to, while scattering inside media can, through its phase function
+
+use crate::camera::Ray;
+use crate::config::Config;
+use crate::data::colour::Colour;
+use crate::data::spectrum::wavelength_weight;
+use crate::data::vector::Vector;
+use crate::onb::Onb;
+use crate::pdf::{power_heuristic, uniform, Pdf};
+use crate::renderer::{self, background, direct_lighting, Bounce, PathState, Splats};
+use crate::world::geometry::light_tree::LightTree;
+use crate::world::geometry::{HitResult, Hittable};
+use crate::world::materials::ScatterResult;
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Distance along the normal from which a point sampled on a light is hit to find its material
+const SURFACE_OFFSET: f64 = 0.0001;
+
+pub struct Vertex {
+    pub point: Vector,
+    // Facing the side the subpath arrived from, meaningless inside media
+    normal: Vector,
+    // Missing for the camera
+    hit: Option<HitResult>,
+    // Attenuation and pdf of diffuse scattering, as only diffuse vertices can be connected to
+    scatter: Option<(Colour, Pdf<'static>)>,
+    // Light carried by the subpath up to this vertex, divided by the density of sampling it
+    pub throughput: Colour,
+    delta: bool,
+    // Densities with respect to area of sampling this vertex from the previous vertex of its own
+    // subpath, and from the next one when tracing the path in the other direction
+    pdf_forward: f64,
+    pdf_reverse: f64,
+}
+
+/// Copy of the densities of a vertex, modified for each connection when weighing it
+#[derive(Clone, Copy)]
+struct Densities {
+    forward: f64,
+    reverse: f64,
+    delta: bool,
+}
+
+/// Camera ray that left the scene without hitting anything
+struct Escape {
+    ray: Ray,
+    throughput: Colour,
+    // Density with respect to solid angle of the direction, zero after a specular bounce
+    pdf: f64,
+}
+
+impl Vertex {
+    fn camera(point: Vector) -> Vertex {
+        Vertex {
+            point,
+            normal: Vector::new(0.0, 0.0, 0.0),
+            hit: None,
+            scatter: None,
+            throughput: Colour::new(1.0, 1.0, 1.0),
+            delta: false,
+            pdf_forward: 0.0,
+            pdf_reverse: 0.0,
+        }
+    }
+
+    fn surface(hit: HitResult, throughput: Colour) -> Vertex {
+        Vertex {
+            point: hit.point,
+            normal: hit.face_normal(),
+            hit: Some(hit),
+            scatter: None,
+            throughput,
+            delta: false,
+            pdf_forward: 0.0,
+            pdf_reverse: 0.0,
+        }
+    }
+
+    pub fn hit(&self) -> &HitResult {
+        match &self.hit {
+            Some(hit) => hit,
+            None => panic!("The camera vertex has no hit"),
+        }
+    }
+
+    pub fn is_connectable(&self) -> bool {
+        self.scatter.is_some()
+    }
+
+    /// Attenuation of light scattered towards a direction, including the cosine term
+    fn scattered(&self, direction: &Vector) -> Colour {
+        match &self.scatter {
+            Some((attenuation, _)) => {
+                let hit = self.hit();
+                let ray = hit.ray.continued(self.point, *direction);
+                attenuation * hit.material.scattering_pdf(hit, &ray)
+            }
+            None => Colour::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Density with respect to solid angle of scattering towards a direction, for light arriving
+    /// the way the subpath did
+    fn scatter_pdf(&self, direction: &Vector) -> f64 {
+        match &self.scatter {
+            Some((_, pdf)) => pdf.value(direction),
+            None => 0.0,
+        }
+    }
+
+    /// Density with respect to solid angle of scattering towards the direction the subpath arrived
+    /// from, for light arriving from the given direction instead. Surfaces scatter the same way
+    /// wherever the light arrives from, while phase functions only depend on the angle between
+    /// the two directions and so give the density of leaving the other way
+    fn reverse_scatter_pdf(&self, towards_previous: &Vector, arriving_from: &Vector) -> f64 {
+        if self.is_volumetric() {
+            self.scatter_pdf(arriving_from)
+        } else {
+            self.scatter_pdf(towards_previous)
+        }
+    }
+
+    fn is_volumetric(&self) -> bool {
+        match &self.hit {
+            Some(hit) => hit.material.is_volumetric(),
+            None => false,
+        }
+    }
+
+    /// Whether the subpath went straight through a medium here. Such vertices only carry the
+    /// light emitted by the medium, and are left out when weighing strategies, as the same path
+    /// can be connected across them
+    fn is_null_collision(&self) -> bool {
+        self.delta && self.is_volumetric()
+    }
+
+    /// Converts a density with respect to solid angle of leaving this vertex into a density with
+    /// respect to area at the other vertex
+    fn to_area(&self, pdf: f64, other: &Vertex) -> f64 {
+        let offset = other.point - self.point;
+        let distance_squared = offset.len_squared();
+        if distance_squared <= 0.0 {
+            return 0.0;
+        }
+
+        // Media take in light from every direction alike, as does the camera
+        let cosine = match other.hit {
+            Some(_) if !other.is_volumetric() => {
+                Vector::dot(&other.normal, &offset.unit_vector()).abs()
+            }
+            _ => 1.0,
+        };
+        pdf * cosine / distance_squared
+    }
+
+    fn densities(&self) -> Densities {
+        Densities {
+            forward: self.pdf_forward,
+            reverse: self.pdf_reverse,
+            delta: self.delta,
+        }
+    }
+}
+
+pub fn colour(ray: &Ray, config: &Config, splats: &Splats, failed_rays: &AtomicUsize) -> Colour {
+    let mut camera_path = vec![Vertex::camera(*ray.origin())];
+    let escape = random_walk(
+        config,
+        *ray,
+        0.0,
+        Colour::new(1.0, 1.0, 1.0),
+        &mut camera_path,
+        failed_rays,
+    );
+    let (camera_vertex, vertices) = camera_path.split_at_mut(1);
+    if let Some(first) = vertices
+        .iter_mut()
+        .find(|vertex| !vertex.is_null_collision())
+    {
+        let pdf = config.camera().direction_pdf(ray.direction());
+        first.pdf_forward = camera_vertex[0].to_area(pdf, first);
+    }
+    let light_path = light_subpath(ray, config, failed_rays);
+
+    // Splats are not scaled by the caller like the returned colour is
+    let splat_weight = match ray.wavelength() {
+        Some(wavelength) => wavelength_weight(wavelength),
+        None => Colour::new(1.0, 1.0, 1.0),
+    };
+    for s in 2..=light_path.len() {
+        if let Some((row, col, colour)) = connect_to_camera(config, &light_path[..s], &camera_path)
+        {
+            splats.add(row, col, &(colour * splat_weight));
+        }
+    }
+
+    let mut total = match escape {
+        Some(escape) => escaped(&escape, config),
+        None => Colour::new(0.0, 0.0, 0.0),
+    };
+    for t in 2..=camera_path.len() {
+        let camera = &camera_path[..t];
+        total = total + sample_environment(config, &camera[t - 1]);
+        if let Some((attenuation, _)) = &camera[t - 1].scatter {
+            let vertex = &camera[t - 1];
+            total = total + vertex.throughput * direct_lighting(config, vertex.hit(), attenuation);
+        }
+
+        // A single light vertex is always sampled afresh from the camera vertex
+        let num_of_light_vertices = usize::max(1, light_path.len());
+        for s in 0..=num_of_light_vertices {
+            total = total + connect(config, &light_path, s, camera);
+        }
+    }
+    total
+}
+
+/// Traces a subpath starting at the point of a light picked in proportion to its power. The
+/// camera ray only gives the time and wavelength of the light
+pub fn light_subpath(camera_ray: &Ray, config: &Config, failed_rays: &AtomicUsize) -> Vec<Vertex> {
+    let mut vertices = vec![];
+    let light_tree = match config.light_tree() {
+        Some(light_tree) => light_tree,
+        None => return vertices,
+    };
+    let (hit, area_pdf) = match sample_light_point(config, light_tree, camera_ray) {
+        Some(sample) => sample,
+        None => return vertices,
+    };
+
+    let side = if hit.material.is_two_sided() && uniform::<f64>() < 0.5 {
+        -hit.surface_normal
+    } else {
+        hit.surface_normal
+    };
+    let direction = Pdf::Cosine(Onb::build_from_w(&side)).generate();
+    let direction_pdf = emission_pdf(&hit, &direction);
+    if direction_pdf <= 0.0 {
+        return vertices;
+    }
+
+    let emitted = emitted_towards(&hit, &direction, config);
+    let cosine = Vector::dot(&hit.surface_normal, &direction).abs();
+    let throughput = emitted * cosine / (area_pdf * direction_pdf);
+    let ray = camera_ray.continued(hit.point, direction);
+
+    let mut light = Vertex::surface(hit, emitted / area_pdf);
+    light.pdf_forward = area_pdf;
+    vertices.push(light);
+
+    random_walk(
+        config,
+        ray,
+        direction_pdf,
+        throughput,
+        &mut vertices,
+        failed_rays,
+    );
+    vertices
+}
+
+/// Extends a subpath from its last vertex along a ray, until it is terminated or leaves the scene.
+/// The density of the ray direction is with respect to solid angle
+fn random_walk(
+    config: &Config,
+    mut ray: Ray,
+    mut pdf: f64,
+    scale: Colour,
+    vertices: &mut Vec<Vertex>,
+    failed_rays: &AtomicUsize,
+) -> Option<Escape> {
+    let path_depths = config.path_depths();
+    let mut path = PathState::new();
+
+    loop {
+        let hit = match config.bvh().hit(&ray, 0.001, f64::MAX) {
+            Some(hit) => hit,
+            None => {
+                return Some(Escape {
+                    ray,
+                    throughput: scale * path.throughput,
+                    pdf,
+                })
+            }
+        };
+        let point = hit.point;
+        let hit = match hit.resolve_material(config.assets()) {
+            Some(hit) => hit,
+            None => {
+                // Transparent parts of surfaces are not vertices of the path
+                ray = ray.continued(point, *ray.direction());
+                continue;
+            }
+        };
+
+        let scatter = hit
+            .material
+            .scatter(&hit, config.assets())
+            .map(|scatter| scatter.at_wavelength(ray.wavelength()));
+        let is_volumetric = hit.material.is_volumetric();
+
+        // Null collisions leave the direction and its density as they were
+        let previous = vertices
+            .iter_mut()
+            .rev()
+            .find(|vertex| !vertex.is_null_collision())
+            .unwrap();
+        let mut vertex = Vertex::surface(hit, scale * path.throughput);
+        vertex.pdf_forward = previous.to_area(pdf, &vertex);
+
+        let (weight, bounce, scattered, scattered_pdf) = match scatter {
+            None => {
+                vertices.push(vertex);
+                return None;
+            }
+            Some(ScatterResult::Specular {
+                attenuation,
+                ray: scattered,
+            }) => {
+                vertex.delta = true;
+                if is_volumetric {
+                    (attenuation, Bounce::Null, scattered, pdf)
+                } else {
+                    (attenuation, Bounce::Specular, scattered, 0.0)
+                }
+            }
+            Some(ScatterResult::Diffuse {
+                attenuation,
+                pdf: scatter_pdf,
+            }) => {
+                let bounce = if is_volumetric {
+                    Bounce::Volume
+                } else {
+                    Bounce::Diffuse
+                };
+                if path.reached_max_depth(bounce, path_depths) {
+                    vertices.push(vertex);
+                    return None;
+                }
+
+                let direction = scatter_pdf.generate();
+                let pdf_value = scatter_pdf.value(&direction);
+                if pdf_value <= 0.0 {
+                    failed_rays.fetch_add(1, Ordering::SeqCst);
+                    vertices.push(vertex);
+                    return None;
+                }
+
+                let hit = vertex.hit();
+                let scattered = hit.ray.continued(point, direction);
+                let weight = attenuation * hit.material.scattering_pdf(hit, &scattered) / pdf_value;
+
+                vertex.scatter = Some((attenuation, scatter_pdf));
+                let reverse = vertex.reverse_scatter_pdf(&(previous.point - point), &direction);
+                previous.pdf_reverse = vertex.to_area(reverse, previous);
+                (weight, bounce, scattered, pdf_value)
+            }
+        };
+
+        if path.reached_max_depth(bounce, path_depths) {
+            vertices.push(vertex);
+            return None;
+        }
+        vertices.push(vertex);
+
+        path = match path.bounce(bounce, &weight, path_depths) {
+            Some((next, _)) => next,
+            None => return None,
+        };
+        ray = scattered;
+        pdf = scattered_pdf;
+    }
+}
+
+/// Weighted contribution of the path made of the first `s` vertices of the light subpath and the
+/// given camera subpath. For `s = 1` a new point is sampled on a light instead
+fn connect(config: &Config, light_path: &[Vertex], s: usize, camera: &[Vertex]) -> Colour {
+    let black = Colour::new(0.0, 0.0, 0.0);
+    let z = &camera[camera.len() - 1];
+
+    match s {
+        0 => {
+            let hit = z.hit();
+            let emitted = hit.material.emitted(hit, config.assets());
+            if emitted == black {
+                return black;
+            }
+            // Light subpaths never start inside media, so their emission is only found this way
+            if z.is_volumetric() {
+                return z.throughput * emitted;
+            }
+            z.throughput * emitted * mis_weight(config, &[], camera)
+        }
+        1 => {
+            let light_tree = match config.light_tree() {
+                Some(light_tree) => light_tree,
+                None => return black,
+            };
+            if !z.is_connectable() {
+                return black;
+            }
+            let (hit, area_pdf) = match sample_light_point(config, light_tree, &z.hit().ray) {
+                Some(sample) => sample,
+                None => return black,
+            };
+
+            let offset = hit.point - z.point;
+            let emitted = emitted_towards(&hit, &(-offset), config);
+            let cosine = Vector::dot(&hit.surface_normal, &offset.unit_vector()).abs();
+            let contribution = z.throughput * z.scattered(&offset) * emitted * cosine
+                / (offset.len_squared() * area_pdf);
+
+            let mut y = Vertex::surface(hit, emitted / area_pdf);
+            y.pdf_forward = area_pdf;
+            if contribution == black {
+                return black;
+            }
+            contribution
+                * transmittance_between(config, z, &y)
+                * mis_weight(config, std::slice::from_ref(&y), camera)
+        }
+        _ => {
+            let light = &light_path[..s];
+            let y = &light[s - 1];
+            if !y.is_connectable() || !z.is_connectable() {
+                return black;
+            }
+
+            let offset = y.point - z.point;
+            let contribution =
+                y.throughput * y.scattered(&(-offset)) * z.scattered(&offset) * z.throughput
+                    / offset.len_squared();
+            if contribution == black {
+                return black;
+            }
+            contribution * transmittance_between(config, z, y) * mis_weight(config, light, camera)
+        }
+    }
+}
+
+/// Weighted contribution of the path made of the first `s` vertices of the light subpath seen
+/// directly by the camera, along with the pixel it is seen through
+fn connect_to_camera(
+    config: &Config,
+    light: &[Vertex],
+    camera_path: &[Vertex],
+) -> Option<(u32, u32, Colour)> {
+    let y = &light[light.len() - 1];
+    let camera = &camera_path[..1];
+    let z = &camera[0];
+    if !y.is_connectable() {
+        return None;
+    }
+    let (row, col) = config.camera().pixel_towards(&z.point, &y.point, config)?;
+
+    // The camera responds to light from a direction in proportion to the density of its rays
+    let offset = z.point - y.point;
+    let pdf = config.camera().direction_pdf(&(-offset));
+    let contribution = y.throughput * y.scattered(&offset) * pdf / offset.len_squared()
+        * transmittance_between(config, y, z);
+    if contribution == Colour::new(0.0, 0.0, 0.0) {
+        return None;
+    }
+    Some((row, col, contribution * mis_weight(config, light, camera)))
+}
+
+/// Power heuristic weight of connecting the light and camera subpaths against all other ways of
+/// building the same path, see section 10.2 of the thesis
+fn mis_weight(config: &Config, light: &[Vertex], camera: &[Vertex]) -> f64 {
+    let light: Vec<&Vertex> = light.iter().filter(|v| !v.is_null_collision()).collect();
+    let camera: Vec<&Vertex> = camera.iter().filter(|v| !v.is_null_collision()).collect();
+    let s = light.len();
+    let t = camera.len();
+    let mut light_densities: Vec<Densities> = light.iter().map(|v| v.densities()).collect();
+    let mut camera_densities: Vec<Densities> = camera.iter().map(|v| v.densities()).collect();
+
+    // The densities next to the connection depend on where the other subpath ends
+    let z = &camera[t - 1];
+    if s == 0 {
+        let previous = &camera[t - 2];
+        camera_densities[t - 1].reverse = light_start_pdf(config, z.hit());
+        let direction = previous.point - z.point;
+        camera_densities[t - 2].reverse = z.to_area(emission_pdf(z.hit(), &direction), previous);
+    } else {
+        let y = &light[s - 1];
+        camera_densities[t - 1].reverse = if s == 1 {
+            y.to_area(emission_pdf(y.hit(), &(z.point - y.point)), z)
+        } else {
+            y.to_area(y.scatter_pdf(&(z.point - y.point)), z)
+        };
+        if t >= 2 {
+            let previous = &camera[t - 2];
+            let reverse = z.reverse_scatter_pdf(&(previous.point - z.point), &(y.point - z.point));
+            camera_densities[t - 2].reverse = z.to_area(reverse, previous);
+        }
+
+        let towards_y = y.point - z.point;
+        let z_pdf = if t == 1 {
+            config.camera().direction_pdf(&towards_y)
+        } else {
+            z.scatter_pdf(&towards_y)
+        };
+        light_densities[s - 1].reverse = z.to_area(z_pdf, y);
+        light_densities[s - 1].delta = false;
+        if s >= 2 {
+            let previous = &light[s - 2];
+            let reverse = y.reverse_scatter_pdf(&(previous.point - y.point), &(z.point - y.point));
+            light_densities[s - 2].reverse = y.to_area(reverse, previous);
+        }
+    }
+    camera_densities[t - 1].delta = false;
+
+    // Densities of delta vertices are zero in both directions, and cancel out
+    let remap = |pdf: f64| if pdf != 0.0 { pdf } else { 1.0 };
+    let mut sum = 0.0;
+
+    // Hand camera vertices over to the light subpath, down to the camera alone. A fresh light
+    // point is never connected to the camera, so that strategy is left out
+    let mut ratio = 1.0;
+    for i in (1..t).rev() {
+        ratio *= remap(camera_densities[i].reverse) / remap(camera_densities[i].forward);
+        let is_light_to_camera = i == 1 && s + t == 2;
+        if !camera_densities[i].delta && !camera_densities[i - 1].delta && !is_light_to_camera {
+            sum += ratio * ratio;
+        }
+    }
+
+    // Hand light vertices over to the camera subpath, down to the camera hitting the light
+    let mut ratio = 1.0;
+    for i in (0..s).rev() {
+        ratio *= remap(light_densities[i].reverse) / remap(light_densities[i].forward);
+        let previous_is_delta = i > 0 && light_densities[i - 1].delta;
+        if !light_densities[i].delta && !previous_is_delta {
+            sum += ratio * ratio;
+        }
+    }
+
+    1.0 / (1.0 + sum)
+}
+
+/// Point on a light picked in proportion to its power, along with the density of picking it with
+/// respect to area. The point is found by a ray along the normal so that its material is known
+fn sample_light_point(
+    config: &Config,
+    light_tree: &LightTree,
+    ray: &Ray,
+) -> Option<(HitResult, f64)> {
+    let (light, probability) = light_tree.sample_by_power();
+    let (point, normal) = light.random_point(ray.time());
+    let towards_point = ray.continued(point + SURFACE_OFFSET * normal, -normal);
+    let hit = light
+        .hit(&towards_point, 0.0, 2.0 * SURFACE_OFFSET)?
+        .resolve_material(config.assets())?;
+
+    Some((hit, probability / light.area()))
+}
+
+/// Density with respect to area of `sample_light_point` starting a light subpath at a point hit on
+/// a light, found by the same ray along the normal
+fn light_start_pdf(config: &Config, hit: &HitResult) -> f64 {
+    match config.light_tree() {
+        Some(light_tree) => {
+            let normal = hit.surface_normal;
+            let towards_point = hit
+                .ray
+                .continued(hit.point + SURFACE_OFFSET * normal, -normal);
+            light_tree.area_pdf_by_power(&towards_point, 0.0, 2.0 * SURFACE_OFFSET)
+        }
+        None => 0.0,
+    }
+}
+
+/// Density with respect to solid angle of light subpaths leaving a light in a direction
+fn emission_pdf(hit: &HitResult, direction: &Vector) -> f64 {
+    let cosine = Vector::dot(&hit.surface_normal, &direction.unit_vector());
+    if hit.material.is_two_sided() {
+        cosine.abs() / (2.0 * PI)
+    } else if cosine > 0.0 {
+        cosine / PI
+    } else {
+        0.0
+    }
+}
+
+fn emitted_towards(hit: &HitResult, direction: &Vector, config: &Config) -> Colour {
+    let hit = HitResult {
+        ray: hit.ray.continued(hit.point, -*direction),
+        ..hit.clone()
+    };
+    hit.material.emitted(&hit, config.assets())
+}
+
+/// Share of the light travelling between two vertices that makes it through the media between
+/// them, or nothing if anything opaque lies between them
+fn transmittance_between(config: &Config, from: &Vertex, to: &Vertex) -> Colour {
+    let to_vertex = to.point - from.point;
+    let distance = to_vertex.len();
+    let ray = from
+        .hit()
+        .ray
+        .continued(from.point, to_vertex / distance)
+        .shadow();
+    renderer::unoccluded(config, &ray, distance - 0.001)
+}
+
+/// Light arriving along a ray from the background, unless it hits an opaque surface first
+fn background_along(ray: &Ray, config: &Config) -> Colour {
+    let ray = ray.shadow();
+    match config.bvh().hit(&ray, 0.001, f64::MAX) {
+        None => renderer::transmittance(config, &ray, f64::MAX) * background(&ray, config),
+        Some(hit) => {
+            let transmittance = renderer::transmittance(config, &ray, hit.distance);
+            let point = hit.point;
+            match hit.resolve_material(config.assets()) {
+                Some(_) => Colour::new(0.0, 0.0, 0.0),
+                None => {
+                    transmittance
+                        * background_along(&ray.continued(point, *ray.direction()), config)
+                }
+            }
+        }
+    }
+}
+
+/// Background seen by a camera subpath, weighted against sampling the environment
+fn escaped(escape: &Escape, config: &Config) -> Colour {
+    let weight = match config.environment_sampler() {
+        Some(sampler) if escape.pdf > 0.0 => {
+            power_heuristic(escape.pdf, sampler.value(escape.ray.direction()))
+        }
+        _ => 1.0,
+    };
+    escape.throughput * background(&escape.ray, config) * weight
+}
+
+fn sample_environment(config: &Config, vertex: &Vertex) -> Colour {
+    let sampler = match config.environment_sampler() {
+        Some(sampler) if vertex.is_connectable() => sampler,
+        _ => return Colour::new(0.0, 0.0, 0.0),
+    };
+
+    let direction = sampler.generate();
+    let pdf = sampler.value(&direction);
+    if pdf <= 0.0 {
+        return Colour::new(0.0, 0.0, 0.0);
+    }
+
+    let ray = vertex.hit().ray.continued(vertex.point, direction);
+    let weight = power_heuristic(pdf, vertex.scatter_pdf(&direction));
+    vertex.throughput * vertex.scattered(&direction) * background_along(&ray, config) * weight / pdf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::tests::{
+        build_config, check_light_through_red_absorbing_medium, floor_hit, white,
+    };
+    use crate::renderer::Integrator;
+    use crate::world::geometry::rectangle::XzRect;
+    use crate::world::geometry::Geometry;
+    use crate::world::materials::emission::EmissionProfile;
+    use crate::world::materials::Material;
+    use crate::world::texture::Texture;
+    use assert_approx_eq::assert_approx_eq;
+
+    // The camera looks straight down at a white floor at the origin from five above it, and a
+    // square light facing both ways is centred two above and two to the side of the floor point,
+    // so that it is seen from there at 45 degrees
+    fn camera_point() -> Vector {
+        Vector::new(0.0, 5.0, 0.0)
+    }
+
+    fn light_point() -> Vector {
+        Vector::new(2.0, 2.0, 0.0)
+    }
+
+    fn light_material(size: f64) -> Material {
+        let brightness = 1.0 / (size * size);
+        Material::DiffuseLight {
+            emit: Texture::Constant {
+                colour: Colour::new(brightness, brightness, brightness),
+            },
+            two_sided: true,
+            profile: EmissionProfile::Lambertian,
+        }
+    }
+
+    fn light_geometry(size: f64) -> Geometry {
+        let half = size / 2.0;
+        XzRect::build(
+            (2.0 - half, 2.0 + half),
+            (-half, half),
+            2.0,
+            light_material(size),
+        )
+    }
+
+    fn scene(size: f64) -> Config {
+        build_config(
+            vec![light_geometry(size)],
+            vec![],
+            Integrator::Bidirectional,
+        )
+    }
+
+    fn floor(from: Vector, pdf_forward: f64) -> Vertex {
+        let hit = floor_hit(from, white());
+        let normal = hit.surface_normal;
+        let mut vertex = Vertex::surface(hit, Colour::new(1.0, 1.0, 1.0));
+        vertex.scatter = Some((
+            Colour::new(1.0, 1.0, 1.0),
+            Pdf::Cosine(Onb::build_from_w(&normal)),
+        ));
+        vertex.pdf_forward = pdf_forward;
+        vertex
+    }
+
+    /// Vertex at the middle of the light, found from the floor. Only its point, normal and
+    /// material are used in weighing strategies, wherever the subpath arrived from
+    fn light(pdf_forward: f64) -> Vertex {
+        let ray = Ray::new(Vector::new(0.0, 0.0, 0.0), light_point(), 0.0);
+        let hit = light_geometry(1.0).hit(&ray, 0.0, f64::MAX).unwrap();
+        let mut vertex = Vertex::surface(hit, Colour::new(1.0, 1.0, 1.0));
+        vertex.pdf_forward = pdf_forward;
+        vertex
+    }
+
+    // Densities with respect to area of each step of the path. The camera ray goes through the
+    // middle of the image, and the light and floor are both 2√2 apart and at 45 degrees to the
+    // path between them
+    fn camera_to_floor() -> f64 {
+        // A 90 degree field of view gives an image of area 4 at unit distance
+        0.25 / 25.0
+    }
+
+    fn floor_to_light() -> f64 {
+        (0.5_f64.sqrt() / PI) * 0.5_f64.sqrt() / 8.0
+    }
+
+    fn light_to_floor() -> f64 {
+        (0.5_f64.sqrt() / (2.0 * PI)) * 0.5_f64.sqrt() / 8.0
+    }
+
+    /// Power heuristic weights of the camera subpath hitting the light, of sampling a point on the
+    /// light from the floor, and of connecting the light subpath at the floor to the camera
+    fn expected_weights(light_start: f64) -> [f64; 3] {
+        let densities = [
+            camera_to_floor() * floor_to_light(),
+            light_start * camera_to_floor(),
+            light_start * light_to_floor(),
+        ];
+        let sum: f64 = densities.iter().map(|pdf| pdf * pdf).sum();
+        [
+            densities[0] * densities[0] / sum,
+            densities[1] * densities[1] / sum,
+            densities[2] * densities[2] / sum,
+        ]
+    }
+
+    #[test]
+    fn test_mis_weights_of_every_strategy() {
+        let config = scene(1.0);
+        let expected = expected_weights(1.0);
+
+        let camera_hits_light = mis_weight(
+            &config,
+            &[],
+            &[
+                Vertex::camera(camera_point()),
+                floor(camera_point(), camera_to_floor()),
+                light(floor_to_light()),
+            ],
+        );
+        let light_sampled = mis_weight(
+            &config,
+            &[light(1.0)],
+            &[
+                Vertex::camera(camera_point()),
+                floor(camera_point(), camera_to_floor()),
+            ],
+        );
+        let light_traced = mis_weight(
+            &config,
+            &[light(1.0), floor(light_point(), light_to_floor())],
+            &[Vertex::camera(camera_point())],
+        );
+
+        assert_approx_eq!(camera_hits_light, expected[0]);
+        assert_approx_eq!(light_sampled, expected[1]);
+        assert_approx_eq!(light_traced, expected[2]);
+        assert_approx_eq!(camera_hits_light + light_sampled + light_traced, 1.0);
+    }
+
+    #[test]
+    fn test_connect_to_sampled_light_point() {
+        // A light small enough for every point sampled on it to be in the middle
+        let size = 0.01;
+        let config = scene(size);
+        let camera = [
+            Vertex::camera(camera_point()),
+            floor(camera_point(), camera_to_floor()),
+        ];
+
+        // Cosine over π at the floor, times the brightness and cosine at the light over the
+        // squared distance and the density of picking the point
+        let unweighted = (0.5_f64.sqrt() / PI) * 0.5_f64.sqrt() / 8.0;
+        let expected = unweighted * expected_weights(1.0 / (size * size))[1];
+        for _ in 0..10 {
+            let contribution = connect(&config, &[], 1, &camera);
+            assert_approx_eq!(contribution.r(), expected, 1e-4);
+            assert_approx_eq!(contribution.b(), expected, 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_connect_light_subpath_to_camera() {
+        let config = scene(1.0);
+        let light_path = [light(1.0), floor(light_point(), light_to_floor())];
+
+        let (row, col, contribution) =
+            connect_to_camera(&config, &light_path, &[Vertex::camera(camera_point())]).unwrap();
+        assert_eq!((row, col), (2, 2));
+
+        // Cosine over π at the floor, times the density of the camera ray over the squared distance
+        let expected = (1.0 / PI) * camera_to_floor() * expected_weights(1.0)[2];
+        assert_approx_eq!(contribution.r(), expected);
+        assert_approx_eq!(contribution.g(), expected);
+    }
+
+    #[test]
+    fn test_light_through_coloured_medium_is_counted_once() {
+        check_light_through_red_absorbing_medium(Integrator::Bidirectional, |ray, config| {
+            colour(ray, config, &Splats::new(config), &AtomicUsize::new(0))
+        });
+    }
+}
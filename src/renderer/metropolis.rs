@@ -0,0 +1,163 @@
+//! Primary sample space Metropolis light transport, following Kelemen et al., "A Simple and Robust
+//! Mutation Strategy for the Metropolis Light Transport Algorithm" (2002). The path tracer is run
+//! on random numbers that are replayed and mutated by Markov chains, so once a chain finds a path
+//! carrying light it keeps exploring the paths around it. This finds light that reaches the
+//! camera through narrow openings, which independent samples almost never do
+//!
+//! A bootstrap pass of independent samples estimates the brightness of the whole image, which the
+//! chains do not know as they only compare paths with each other. Chains start from bootstrap
+//! samples picked in proportion to their brightness, and every proposal is recorded at the pixel
+//! it lands on, weighted by its chance of being accepted
+
+use crate::config::Config;
+use crate::data::colour::Colour;
+use crate::data::spectrum::{sample_wavelength, wavelength_weight};
+use crate::float;
+use crate::pdf::distribution::Distribution1D;
+use crate::pdf::primary_samples::PrimarySamples;
+use crate::pdf::uniform;
+use crate::renderer::{path, PathState, Splats};
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use std::sync::atomic::AtomicUsize;
+
+const NUM_OF_BOOTSTRAP_SAMPLES: u64 = 100_000;
+const NUM_OF_CHAINS: u64 = 1000;
+
+/// Light found by the path tracer for one set of random numbers, and where it landed
+struct PathSample {
+    row: u32,
+    col: u32,
+    colour: Colour,
+    // Brightness the chains are distributed by
+    contribution: f64,
+}
+
+pub fn render(
+    config: &Config,
+    progress_bar: &ProgressBar,
+    failed_rays: &AtomicUsize,
+) -> Vec<(u32, u32, Colour)> {
+    let large_step_probability = config.large_step_probability();
+    let pixel_coords = config.camera().pixels(config);
+
+    // Seeds differ between renders, as the rest of the renderers' random numbers do
+    let first_seed = u64::from(uniform::<u32>()) << 32;
+    let bootstrap: Vec<f64> = (0..NUM_OF_BOOTSTRAP_SAMPLES)
+        .into_par_iter()
+        .map(|index| {
+            let samples = PrimarySamples::new(first_seed + index, large_step_probability);
+            let (sample, _) = samples.run(|| trace(config, failed_rays));
+            sample.contribution
+        })
+        .collect();
+    let bootstrap = Distribution1D::new(bootstrap);
+    // Average brightness over all random numbers, i.e. of the whole image
+    let brightness = bootstrap.integral();
+
+    let num_of_mutations = pixel_coords.len() as u64 * config.num_of_rays();
+    let mutations_per_chain = num_of_mutations.div_ceil(NUM_OF_CHAINS);
+    progress_bar.set_length(mutations_per_chain * NUM_OF_CHAINS);
+
+    let splats = Splats::new(config);
+    if brightness > 0.0 {
+        (0..NUM_OF_CHAINS).into_par_iter().for_each(|_| {
+            let (_, index) = bootstrap.sample(uniform());
+            run_chain(
+                config,
+                first_seed + index as u64,
+                mutations_per_chain,
+                &splats,
+                progress_bar,
+                failed_rays,
+            );
+        });
+    }
+
+    // Every pixel is the share of the mutations that landed in it scaled by the brightness
+    let mutations_per_pixel =
+        (mutations_per_chain * NUM_OF_CHAINS) as f64 / pixel_coords.len() as f64;
+    pixel_coords
+        .iter()
+        .map(|(row, col)| {
+            let colour = splats.get(*row, *col) * brightness / mutations_per_pixel;
+            (*row, *col, colour)
+        })
+        .collect()
+}
+
+/// Runs a chain from the random numbers of a bootstrap sample, recording every proposal
+fn run_chain(
+    config: &Config,
+    seed: u64,
+    num_of_mutations: u64,
+    splats: &Splats,
+    progress_bar: &ProgressBar,
+    failed_rays: &AtomicUsize,
+) {
+    let samples = PrimarySamples::new(seed, config.large_step_probability());
+    let (mut current, mut samples) = samples.run(|| trace(config, failed_rays));
+
+    for _ in 0..num_of_mutations {
+        samples.start_iteration();
+        let (proposed, next_samples) = samples.run(|| trace(config, failed_rays));
+        samples = next_samples;
+
+        let acceptance = if current.contribution > 0.0 {
+            float::min(1.0, proposed.contribution / current.contribution)
+        } else {
+            1.0
+        };
+
+        // Both states are recorded, weighted by the chance of the chain moving to either of them,
+        // which lowers the noise compared to only recording where the chain ends up
+        if acceptance > 0.0 && proposed.contribution > 0.0 {
+            let colour = proposed.colour * acceptance / proposed.contribution;
+            splats.add(proposed.row, proposed.col, &colour);
+        }
+        if acceptance < 1.0 {
+            let colour = current.colour * (1.0 - acceptance) / current.contribution;
+            splats.add(current.row, current.col, &colour);
+        }
+
+        if uniform::<f64>() < acceptance {
+            current = proposed;
+            samples.accept();
+        } else {
+            samples.reject();
+        }
+        progress_bar.inc(1);
+    }
+}
+
+/// Path traced for the running random numbers, the first two of which pick the point on the image
+fn trace(config: &Config, failed_rays: &AtomicUsize) -> PathSample {
+    let h: f64 = uniform();
+    let v: f64 = uniform();
+    let ray = config.camera().ray(h, v);
+
+    let colour = if config.spectral() {
+        let wavelength = sample_wavelength();
+        let ray = ray.with_wavelength(wavelength);
+        path::colour(&ray, config, PathState::new(), 1.0, failed_rays)
+            * wavelength_weight(wavelength)
+    } else {
+        path::colour(&ray, config, PathState::new(), 1.0, failed_rays)
+    };
+
+    let row = u32::min((v * f64::from(config.height())) as u32, config.height() - 1);
+    let col = u32::min((h * f64::from(config.width())) as u32, config.width() - 1);
+    // Broken paths are never moved to
+    let contribution = colour.luminance();
+    let contribution = if contribution.is_finite() {
+        float::max(0.0, contribution)
+    } else {
+        0.0
+    };
+    PathSample {
+        row,
+        col,
+        colour,
+        contribution,
+    }
+}
@@ -0,0 +1,554 @@
+use crate::camera::Ray;
+use crate::config::{Config, PathDepths};
+use crate::data::colour::Colour;
+use crate::data::image::{Image, Pixel};
+use crate::data::spectrum::{at_wavelength, sample_wavelength, wavelength_weight};
+use crate::float;
+use crate::pdf::uniform;
+use crate::world::geometry::{HitResult, Hittable};
+use indicatif::ProgressBar;
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+mod bidirectional;
+mod metropolis;
+mod path;
+mod photon_mapping;
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Integrator {
+        Path,
+        Bidirectional,
+        PhotonMapping,
+        Metropolis,
+    }
+}
+
+pub struct RenderOutput {
+    pub image: Image,
+    pub failed_rays: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bounce {
+    Diffuse,
+    Specular,
+    Volume,
+    // Null collision in a medium, which leaves the ray going the same way and does not count
+    // towards any depth
+    Null,
+}
+
+/// Number of bounces of each kind taken so far along a path, and the share of the light arriving
+/// along the current ray that makes it back to the camera
+#[derive(Debug, Clone, Copy)]
+struct PathState {
+    diffuse: u64,
+    specular: u64,
+    volume: u64,
+    throughput: Colour,
+}
+
+impl PathState {
+    fn new() -> PathState {
+        PathState {
+            diffuse: 0,
+            specular: 0,
+            volume: 0,
+            throughput: Colour::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    fn depth(&self) -> u64 {
+        self.diffuse + self.specular + self.volume
+    }
+
+    fn reached_max_depth(&self, bounce: Bounce, path_depths: &PathDepths) -> bool {
+        match bounce {
+            Bounce::Diffuse => self.diffuse >= path_depths.diffuse,
+            Bounce::Specular => self.specular >= path_depths.specular,
+            Bounce::Volume => self.volume >= path_depths.volume,
+            Bounce::Null => false,
+        }
+    }
+
+    /// Path after a bounce that scales its light by the weight, along with the probability of it
+    /// surviving Russian roulette, or `None` if it was terminated
+    fn bounce(
+        &self,
+        bounce: Bounce,
+        weight: &Colour,
+        path_depths: &PathDepths,
+    ) -> Option<(PathState, f64)> {
+        let throughput = self.throughput * weight;
+
+        // Paths that carry little light are terminated early, and the survivors are weighted up
+        // to make up for them so that the result stays unbiased
+        let survival_probability = if self.depth() >= path_depths.roulette {
+            let max_channel =
+                float::max(throughput.r(), float::max(throughput.g(), throughput.b()));
+            float::min(1.0, max_channel)
+        } else {
+            1.0
+        };
+        if survival_probability <= 0.0 || uniform::<f64>() >= survival_probability {
+            return None;
+        }
+
+        let mut next = PathState {
+            throughput: throughput / survival_probability,
+            ..*self
+        };
+        match bounce {
+            Bounce::Diffuse => next.diffuse += 1,
+            Bounce::Specular => next.specular += 1,
+            Bounce::Volume => next.volume += 1,
+            Bounce::Null => {}
+        }
+        Some((next, survival_probability))
+    }
+}
+
+/// Light carried from the scene straight to the camera, which can land on any pixel rather than
+/// the one whose ray started the path
+struct Splats {
+    width: u32,
+    pixels: Vec<Mutex<Colour>>,
+}
+
+impl Splats {
+    fn new(config: &Config) -> Splats {
+        let num_of_pixels = config.width() as usize * config.height() as usize;
+        Splats {
+            width: config.width(),
+            pixels: (0..num_of_pixels)
+                .map(|_| Mutex::new(Colour::new(0.0, 0.0, 0.0)))
+                .collect(),
+        }
+    }
+
+    fn index(&self, row: u32, col: u32) -> usize {
+        row as usize * self.width as usize + col as usize
+    }
+
+    fn add(&self, row: u32, col: u32, colour: &Colour) {
+        let mut pixel = self.pixels[self.index(row, col)].lock().unwrap();
+        *pixel = *pixel + colour;
+    }
+
+    fn get(&self, row: u32, col: u32) -> Colour {
+        *self.pixels[self.index(row, col)].lock().unwrap()
+    }
+}
+
+pub fn render(config: &Config, progress_bar: &ProgressBar) -> RenderOutput {
+    let failed_rays = AtomicUsize::new(0);
+    let splats = Splats::new(config);
+
+    let colours = match config.integrator() {
+        Integrator::PhotonMapping => photon_mapping::render(config, progress_bar, &failed_rays),
+        Integrator::Metropolis => metropolis::render(config, progress_bar, &failed_rays),
+        _ => render_pixels(config, &splats, progress_bar, &failed_rays),
+    };
+
+    progress_bar.finish();
+
+    // Every ray added its own light to the splats, so they are averaged the same as the pixels
+    let num_of_rays = config.num_of_rays() as f64;
+    let pixels: Vec<Pixel> = colours
+        .iter()
+        .map(|(row, col, colour)| {
+            let colour = colour + splats.get(*row, *col) / num_of_rays;
+            // Light of a single wavelength can be outside the output gamut, which shows up as
+            // negative channels on pixels with few such rays
+            let colour = Colour::new(
+                float::max(0.0, colour.r()),
+                float::max(0.0, colour.g()),
+                float::max(0.0, colour.b()),
+            );
+
+            // Translate into the coordinate system expected by the image crate
+            Pixel::new(*row, *col, colour.gamma_2())
+        })
+        .collect();
+
+    let image = Image::from(&pixels);
+    RenderOutput {
+        image,
+        failed_rays: failed_rays.load(Ordering::SeqCst),
+    }
+}
+
+/// Colour of every pixel found by rendering them one by one
+fn render_pixels(
+    config: &Config,
+    splats: &Splats,
+    progress_bar: &ProgressBar,
+    failed_rays: &AtomicUsize,
+) -> Vec<(u32, u32, Colour)> {
+    let mut pixel_coords = config.camera().pixels(&config);
+
+    // When pixels are processed in order patterns in the image can affect the remaining time
+    // estimate produced by the progress bar, e.g. a empty part of the image can be processed first
+    // thus producing a false low remaining time estimate while the rest of the image might have a
+    // very high number of objects and be slower to process.
+    // Shuffle pixels to break up these patterns and improve the quality of the estimate
+    let mut rng = rand::thread_rng();
+    pixel_coords.shuffle(&mut rng);
+
+    pixel_coords
+        .par_iter()
+        .map(|(row, col)| {
+            let colour = pixel(*row, *col, &config, splats, &progress_bar, failed_rays);
+            (*row, *col, colour)
+        })
+        .collect()
+}
+
+fn pixel(
+    row: u32,
+    col: u32,
+    config: &Config,
+    splats: &Splats,
+    progress_bar: &ProgressBar,
+    failed_rays: &AtomicUsize,
+) -> Colour {
+    let rays = config.camera().rays(row, col, &config);
+
+    let colour_sum = panic::catch_unwind(|| {
+        rays.iter()
+            .map(|ray| {
+                if config.spectral() {
+                    // Every path carries light of a single wavelength, picked at random
+                    let wavelength = sample_wavelength();
+                    let ray = ray.with_wavelength(wavelength);
+                    colour(&ray, &config, splats, failed_rays) * wavelength_weight(wavelength)
+                } else {
+                    colour(&ray, &config, splats, failed_rays)
+                }
+            })
+            .sum()
+    });
+    let colour_sum: Colour = match colour_sum {
+        Ok(colour_sum) => colour_sum,
+        Err(err) => {
+            // A rayon parallel iter will not terminate other threads when one panics
+            eprintln!("A rendering thread panicked {:?}", err);
+            std::process::exit(1);
+        }
+    };
+
+    progress_bar.inc(1);
+
+    colour_sum / (rays.len() as f64)
+}
+
+fn colour(ray: &Ray, config: &Config, splats: &Splats, failed_rays: &AtomicUsize) -> Colour {
+    match config.integrator() {
+        Integrator::Path => path::colour(ray, config, PathState::new(), 1.0, failed_rays),
+        Integrator::Bidirectional => bidirectional::colour(ray, config, splats, failed_rays),
+        Integrator::PhotonMapping | Integrator::Metropolis => {
+            unreachable!("{} renders every pixel at once", config.integrator())
+        }
+    }
+}
+
+fn direct_lighting(config: &Config, hit: &HitResult, attenuation: &Colour) -> Colour {
+    // Lights without geometry can never be hit by a scattered ray, so sample each one with a
+    // shadow ray instead
+    config
+        .lights()
+        .iter()
+        .map(|light| {
+            let sample = light.sample(&hit.point);
+            let shadow_ray = hit.ray.continued(hit.point, sample.direction).shadow();
+
+            // Surfaces do not scatter light arriving from behind them, media scatter it all around
+            let scattering_pdf = hit.material.scattering_pdf(hit, &shadow_ray);
+            if scattering_pdf <= 0.0 {
+                return Colour::new(0.0, 0.0, 0.0);
+            }
+
+            let transmittance = unoccluded(config, &shadow_ray, sample.distance);
+            let incident = at_wavelength(&sample.incident, hit.ray.wavelength());
+            attenuation * scattering_pdf * incident * transmittance
+        })
+        .sum()
+}
+
+/// Share of the light travelling along a shadow ray that makes it through the media up to the
+/// given distance, at the wavelength the ray carries if it carries only one
+fn transmittance(config: &Config, ray: &Ray, distance: f64) -> Colour {
+    let transmittance = config.bvh().transmittance(ray, 0.001, distance);
+    at_wavelength(&transmittance, ray.wavelength())
+}
+
+/// Share of the light travelling along a shadow ray that makes it up to the given distance, which
+/// is none if an opaque surface is in the way. Transparent parts of cutouts are passed through
+fn unoccluded(config: &Config, ray: &Ray, distance: f64) -> Colour {
+    match config.bvh().hit(ray, 0.001, distance) {
+        None => transmittance(config, ray, distance),
+        Some(hit) => {
+            let transmittance = transmittance(config, ray, hit.distance);
+            let (point, remaining) = (hit.point, distance - hit.distance);
+            match hit.resolve_material(config.assets()) {
+                Some(_) => Colour::new(0.0, 0.0, 0.0),
+                None => {
+                    transmittance
+                        * unoccluded(config, &ray.continued(point, *ray.direction()), remaining)
+                }
+            }
+        }
+    }
+}
+
+fn background(ray: &Ray, config: &Config) -> Colour {
+    let colour = config.background().value(ray.direction(), config.assets());
+    at_wavelength(&colour, ray.wavelength())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::camera::{CameraSave, Lens};
+    use crate::cli::PathDepthArgs;
+    use crate::config::ConfigSave;
+    use crate::data::assets::Assets;
+    use crate::data::vector::Vector;
+    use crate::world::background::Background;
+    use crate::world::geometry::medium::ConstantMedium;
+    use crate::world::geometry::rectangle::XzRect;
+    use crate::world::geometry::sphere::Sphere;
+    use crate::world::geometry::Geometry;
+    use crate::world::lights::Light;
+    use crate::world::materials::emission::EmissionProfile;
+    use crate::world::materials::phase::PhaseFunction;
+    use crate::world::materials::Material;
+    use crate::world::texture::Texture;
+    use crate::world::WorldSave;
+    use assert_approx_eq::assert_approx_eq;
+    use std::f64::consts::PI;
+
+    /// Scene with a black background, viewed from above the origin
+    pub fn build_config(
+        geometries: Vec<Geometry>,
+        lights: Vec<Light>,
+        integrator: Integrator,
+    ) -> Config {
+        let camera = CameraSave::new(
+            &Vector::new(0.0, 5.0, 0.0),
+            &Vector::new(0.0, 0.0, 0.0),
+            &Vector::new(0.0, 0.0, -1.0),
+            1.0,
+            Lens::new(90.0, 0.0, 5.0),
+            0.0,
+            1.0,
+        );
+        let black = Colour::new(0.0, 0.0, 0.0);
+        let world = WorldSave::new(Background::new(black, black), geometries, lights);
+        let path_depth_args = PathDepthArgs {
+            diffuse: None,
+            specular: None,
+            volume: None,
+            roulette: None,
+        };
+        ConfigSave::new(1.0, camera, world).into_config(
+            4,
+            1,
+            false,
+            integrator,
+            0.3,
+            &path_depth_args,
+            Assets::new(&[]).unwrap(),
+        )
+    }
+
+    /// One by one light five above the floor, starting at the given x
+    pub fn light(x: f64, brightness: f64) -> Geometry {
+        XzRect::build(
+            (x, x + 1.0),
+            (0.0, 1.0),
+            5.0,
+            Material::DiffuseLight {
+                emit: Texture::Constant {
+                    colour: Colour::new(brightness, brightness, brightness),
+                },
+                two_sided: true,
+                profile: EmissionProfile::Lambertian,
+            },
+        )
+    }
+
+    pub fn white() -> Material {
+        Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(1.0, 1.0, 1.0),
+            },
+        }
+    }
+
+    /// Hit at the origin on a floor of the given material, by a ray from the given point
+    pub fn floor_hit(from: Vector, material: Material) -> HitResult {
+        let floor = XzRect::build((-1.0, 1.0), (-1.0, 1.0), 0.0, material);
+        floor
+            .hit(&Ray::new(from, -from, 0.0), 0.0, f64::MAX)
+            .unwrap()
+    }
+
+    /// Checks the light found on a white floor under a two by two light one above it, seen from
+    /// 0.1 above the floor in the middle of a ball of medium that only stops red. Every ray from
+    /// the floor to the light goes 0.5 through the medium, and green only meets null collisions,
+    /// so the light must be counted once whichever way it is found
+    pub fn check_light_through_red_absorbing_medium<F>(integrator: Integrator, colour: F)
+    where
+        F: Fn(&Ray, &Config) -> Colour,
+    {
+        let light = XzRect::build(
+            (-1.0, 1.0),
+            (-1.0, 1.0),
+            1.0,
+            Material::DiffuseLight {
+                emit: Texture::Constant {
+                    colour: Colour::new(1.0, 1.0, 1.0),
+                },
+                two_sided: true,
+                profile: EmissionProfile::Lambertian,
+            },
+        );
+        let floor = XzRect::build((-10.0, 10.0), (-10.0, 10.0), 0.0, white());
+        let medium = ConstantMedium::build_with_material(
+            Sphere::build(Vector::new(0.0, 0.0, 0.0), 0.5, white()),
+            1.0,
+            Material::Medium {
+                absorption: Colour::new(2.0, 0.0, 0.0),
+                scattering: Colour::new(0.0, 0.0, 0.0),
+                emission: Colour::new(0.0, 0.0, 0.0),
+                phase: PhaseFunction::Isotropic,
+            },
+        );
+        let config = build_config(vec![light, floor, medium], vec![], integrator);
+
+        let ray = Ray::new(Vector::new(0.0, 0.1, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+        let num_of_samples = 40_000;
+        let total = (0..num_of_samples)
+            .map(|_| colour(&ray, &config))
+            .fold(Colour::new(0.0, 0.0, 0.0), |total, sample| total + sample);
+        let mean = total / f64::from(num_of_samples);
+
+        // Radiance of a white floor under a square light centred above it, from the form factor
+        // of each quarter of the light
+        let quarter = (0.5_f64.sqrt() * 0.5_f64.sqrt().atan()) / PI;
+        let unobstructed = 4.0 * quarter;
+        assert_approx_eq!(mean.g(), unobstructed, 0.02);
+        assert_approx_eq!(mean.b(), unobstructed, 0.02);
+        assert_approx_eq!(mean.r(), unobstructed * (-2.0_f64 * 0.6).exp(), 0.02);
+    }
+
+    fn cutout(opacity: f64) -> Material {
+        Material::Cutout {
+            inner: Box::new(white()),
+            opacity: Texture::Constant {
+                colour: Colour::new(opacity, opacity, opacity),
+            },
+            threshold: Some(0.5),
+        }
+    }
+
+    #[test]
+    fn test_point_light_shines_through_transparent_cutout() {
+        let light = Light::Point {
+            position: Vector::new(0.0, 2.0, 0.0),
+            intensity: Colour::new(4.0, 4.0, 4.0),
+        };
+        let attenuation = Colour::new(1.0, 1.0, 1.0);
+        let floor = floor_hit(Vector::new(0.0, 1.0, 0.0), white());
+
+        let transparent = XzRect::build((-1.0, 1.0), (-1.0, 1.0), 1.0, cutout(0.0));
+        let config = build_config(vec![transparent], vec![light.clone()], Integrator::Path);
+        let lit = direct_lighting(&config, &floor, &attenuation);
+        assert_approx_eq!(lit.r(), 1.0 / PI);
+        assert_approx_eq!(lit.b(), 1.0 / PI);
+
+        let opaque = XzRect::build((-1.0, 1.0), (-1.0, 1.0), 1.0, cutout(1.0));
+        let config = build_config(vec![opaque], vec![light], Integrator::Path);
+        let shadowed = direct_lighting(&config, &floor, &attenuation);
+        assert_approx_eq!(shadowed.r(), 0.0);
+    }
+
+    #[test]
+    fn test_paths_are_not_terminated_before_roulette_depth() {
+        let path_depths = PathDepths::default();
+        let path = PathState {
+            diffuse: path_depths.roulette - 1,
+            ..PathState::new()
+        };
+        let weight = Colour::new(1e-6, 1e-6, 1e-6);
+        for _ in 0..1000 {
+            let (next, survival_probability) =
+                path.bounce(Bounce::Diffuse, &weight, &path_depths).unwrap();
+            assert_approx_eq!(survival_probability, 1.0);
+            assert_approx_eq!(next.throughput.r(), 1e-6);
+            assert_eq!(next.diffuse, path_depths.roulette);
+        }
+    }
+
+    #[test]
+    fn test_roulette_survivors_are_reweighted() {
+        let path_depths = PathDepths::default();
+        let path = PathState {
+            specular: path_depths.roulette,
+            ..PathState::new()
+        };
+        // Survives a quarter of the time, with the brightest channel
+        let weight = Colour::new(0.1, 0.25, 0.05);
+
+        let num_of_samples = 20_000;
+        let mut survivors = 0;
+        let mut total = Colour::new(0.0, 0.0, 0.0);
+        for _ in 0..num_of_samples {
+            if let Some((next, survival_probability)) =
+                path.bounce(Bounce::Specular, &weight, &path_depths)
+            {
+                assert_approx_eq!(survival_probability, 0.25);
+                assert_approx_eq!(next.throughput.r(), 0.4);
+                assert_approx_eq!(next.throughput.g(), 1.0);
+                assert_approx_eq!(next.throughput.b(), 0.2);
+                survivors += 1;
+                total = total + next.throughput;
+            }
+        }
+
+        assert_approx_eq!(
+            f64::from(survivors) / f64::from(num_of_samples),
+            0.25,
+            0.015
+        );
+        // Light carried on average is left as it was
+        let mean = total / f64::from(num_of_samples);
+        assert_approx_eq!(mean.r(), 0.1, 0.006);
+        assert_approx_eq!(mean.g(), 0.25, 0.015);
+    }
+
+    #[test]
+    fn test_null_collisions_do_not_count_towards_depth() {
+        let path_depths = PathDepths {
+            volume: 1,
+            ..PathDepths::default()
+        };
+        let path = PathState {
+            volume: 1,
+            ..PathState::new()
+        };
+        assert!(path.reached_max_depth(Bounce::Volume, &path_depths));
+        assert!(!path.reached_max_depth(Bounce::Null, &path_depths));
+
+        let weight = Colour::new(1.0, 1.0, 1.0);
+        let (next, _) = path.bounce(Bounce::Null, &weight, &path_depths).unwrap();
+        assert_eq!(next.depth(), 1);
+        assert_eq!(next.volume, 1);
+    }
+}
@@ -0,0 +1,298 @@
+//! Unidirectional path tracing, which follows rays from the camera and samples the lights at
+//! every diffuse bounce
+
+use crate::camera::Ray;
+use crate::config::Config;
+use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::pdf::{power_heuristic, Pdf};
+use crate::renderer::{background, direct_lighting, transmittance, Bounce, PathState};
+use crate::world::geometry::{HitResult, Hittable};
+use crate::world::materials::ScatterResult;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Colour of the light arriving along a ray. Light emitted by the first surface hit, or by the
+/// background if nothing is hit, is scaled by the emission weight, which is below 1 when the
+/// previous bounce could also have reached it by sampling a light
+pub fn colour(
+    ray: &Ray,
+    config: &Config,
+    path: PathState,
+    emission_weight: f64,
+    failed_rays: &AtomicUsize,
+) -> Colour {
+    config
+        .bvh()
+        .hit(&ray, 0.001, core::f64::MAX)
+        .map(|hit| {
+            let point = hit.point;
+            let hit = match hit.resolve_material(config.assets()) {
+                Some(hit) => hit,
+                None => {
+                    // The ray went through a transparent part of the surface. Carry on from the
+                    // hit point without counting it as a bounce
+                    let ray = ray.continued(point, *ray.direction());
+                    return colour(&ray, config, path, emission_weight, failed_rays);
+                }
+            };
+
+            // Media are never sampled as lights, so their emission is never weighted down
+            let emitted = if hit.material.is_volumetric() {
+                hit.material.emitted(&hit, config.assets())
+            } else {
+                emission_weight * hit.material.emitted(&hit, config.assets())
+            };
+
+            hit.material
+                .scatter(&hit, &config.assets())
+                .map(|scatter| {
+                    colour_from_scatter(
+                        config,
+                        path,
+                        &hit,
+                        emitted,
+                        emission_weight,
+                        scatter,
+                        failed_rays,
+                    )
+                })
+                .unwrap_or(emitted)
+        })
+        .unwrap_or_else(|| emission_weight * background(&ray, &config))
+}
+
+fn colour_from_scatter(
+    config: &Config,
+    path: PathState,
+    hit: &HitResult,
+    emitted: Colour,
+    emission_weight: f64,
+    scatter: ScatterResult,
+    failed_rays: &AtomicUsize,
+) -> Colour {
+    let scatter = scatter.at_wavelength(hit.ray.wavelength());
+    let path_depths = config.path_depths();
+
+    match scatter {
+        ScatterResult::Specular { attenuation, ray } => {
+            // Media only carry rays on unchanged, at null collisions
+            let bounce = if hit.material.is_volumetric() {
+                Bounce::Null
+            } else {
+                Bounce::Specular
+            };
+            if path.reached_max_depth(bounce, path_depths) {
+                return emitted;
+            }
+
+            // Null collisions leave the ray going the same way, so a light it reaches could still
+            // have been found by sampling the lights at the last diffuse bounce
+            let emission_weight = if bounce == Bounce::Null {
+                emission_weight
+            } else {
+                1.0
+            };
+            match path.bounce(bounce, &attenuation, path_depths) {
+                Some((next, survival_probability)) => {
+                    emitted
+                        + attenuation * colour(&ray, &config, next, emission_weight, failed_rays)
+                            / survival_probability
+                }
+                None => emitted,
+            }
+        }
+        ScatterResult::Diffuse { attenuation, pdf } => {
+            // Media scatter light with phase functions, which are sampled like diffuse surfaces
+            let bounce = if hit.material.is_volumetric() {
+                Bounce::Volume
+            } else {
+                Bounce::Diffuse
+            };
+            if path.reached_max_depth(bounce, path_depths) {
+                return emitted;
+            }
+
+            let light_pdf = light_pdf(config, &hit.point);
+
+            let direction = pdf.generate();
+            let pdf_value = pdf.value(&direction);
+
+            if pdf_value <= 0.0 {
+                // This means there is no valid scattered ray we should sample.
+                // Return just emitted to avoid a NaN from the division by 0
+                failed_rays.fetch_add(1, Ordering::SeqCst);
+                return emitted;
+            }
+
+            // Lights hit by the scattered ray were also sampled directly below, so weigh their
+            // emission by how likely each strategy was to find them
+            let emission_weight = match &light_pdf {
+                Some(light_pdf) => power_heuristic(pdf_value, light_pdf.value(&direction)),
+                None => 1.0,
+            };
+
+            let scattered = hit.ray.continued(hit.point, direction);
+            let scattering_pdf = hit.material.scattering_pdf(hit, &scattered);
+            let weight = attenuation * scattering_pdf / pdf_value;
+            let scatter_colour = match path.bounce(bounce, &weight, path_depths) {
+                Some((next, survival_probability)) => {
+                    weight * colour(&scattered, &config, next, emission_weight, failed_rays)
+                        / survival_probability
+                }
+                None => Colour::new(0.0, 0.0, 0.0),
+            };
+
+            let light_colour = match &light_pdf {
+                Some(light_pdf) => sample_light(config, hit, &attenuation, &pdf, light_pdf),
+                None => Colour::new(0.0, 0.0, 0.0),
+            };
+            let direct_colour = direct_lighting(config, hit, &attenuation);
+            emitted + light_colour + direct_colour + scatter_colour
+        }
+    }
+}
+
+/// Pdf for sampling the lights, the environment and the attractors that do not emit, e.g. glass,
+/// seen from a point, if there are any. Scattered rays only follow the material, so attractors
+/// are weighed against it along with the lights
+pub fn light_pdf<'a>(config: &'a Config, origin: &Vector) -> Option<Pdf<'a>> {
+    let lights = config.light_tree().map(|light_tree| Pdf::Lights {
+        light_tree,
+        origin: *origin,
+    });
+    let environment = config.environment_sampler().map(Pdf::Environment);
+    let attractors = Some(config.attractors())
+        .filter(|attractors| !attractors.is_empty())
+        .map(|geometries| Pdf::Geometry {
+            geometries,
+            origin: *origin,
+        });
+
+    // Half of the samples go to the first of these, half of the rest to the next and so on
+    let mut pdfs = vec![lights, environment, attractors]
+        .into_iter()
+        .flatten()
+        .rev();
+    let last = pdfs.next()?;
+    Some(pdfs.fold(last, |rest, pdf| Pdf::Mixture(pdf.boxed(), rest.boxed())))
+}
+
+/// Next event estimation: light arriving from a direction picked by sampling the lights, weighted
+/// against the chance of the scattered ray finding the same light
+pub fn sample_light(
+    config: &Config,
+    hit: &HitResult,
+    attenuation: &Colour,
+    scatter_pdf: &Pdf,
+    light_pdf: &Pdf,
+) -> Colour {
+    let direction = light_pdf.generate();
+    let light_pdf_value = light_pdf.value(&direction);
+    if light_pdf_value <= 0.0 {
+        return Colour::new(0.0, 0.0, 0.0);
+    }
+
+    let shadow_ray = hit.ray.continued(hit.point, direction);
+    let scattering_pdf = hit.material.scattering_pdf(hit, &shadow_ray);
+    if scattering_pdf <= 0.0 {
+        return Colour::new(0.0, 0.0, 0.0);
+    }
+
+    let weight = power_heuristic(light_pdf_value, scatter_pdf.value(&direction));
+    let incident = emitted_along(&shadow_ray, config);
+    attenuation * scattering_pdf * incident * weight / light_pdf_value
+}
+
+/// Light emitted towards the origin of a ray by the first surface it hits, or by the background.
+/// Blocked rays simply find a surface that does not emit, and media on the way attenuate the light
+pub fn emitted_along(ray: &Ray, config: &Config) -> Colour {
+    let ray = ray.shadow();
+    match config.bvh().hit(&ray, 0.001, f64::MAX) {
+        Some(hit) => {
+            let transmittance = transmittance(config, &ray, hit.distance);
+            let point = hit.point;
+            let emitted = match hit.resolve_material(config.assets()) {
+                Some(hit) => hit.material.emitted(&hit, config.assets()),
+                None => emitted_along(&ray.continued(point, *ray.direction()), config),
+            };
+            transmittance * emitted
+        }
+        None => transmittance(config, &ray, f64::MAX) * background(&ray, config),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::tests::{build_config, check_light_through_red_absorbing_medium, light};
+    use crate::renderer::Integrator;
+    use crate::world::geometry::sphere::Sphere;
+    use crate::world::materials::Material;
+    use assert_approx_eq::assert_approx_eq;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_light_pdf_picks_lights_by_light_tree() {
+        // The light straight above is 25 away squared and the one to the side, four times as
+        // bright, is 125 away, so the tree picks the one above 5 times out of 9
+        let config = build_config(
+            vec![light(0.0, 1.0), light(10.0, 4.0)],
+            vec![],
+            Integrator::Path,
+        );
+        let origin = Vector::new(0.5, 0.0, 0.5);
+        let pdf = light_pdf(&config, &origin).unwrap();
+        let pick_above = 5.0 / 9.0;
+
+        // Density of a direction on the light above, which is 5 away and faces it
+        let up = Vector::new(0.0, 1.0, 0.0);
+        assert_approx_eq!(pdf.value(&up), pick_above * 25.0);
+
+        let num_of_samples = 20_000;
+        let above = (0..num_of_samples)
+            .map(|_| pdf.generate())
+            .filter(|direction| {
+                let x = origin.x() + direction.x() * 5.0 / direction.y();
+                (0.0..=1.0).contains(&x)
+            })
+            .count();
+        assert_approx_eq!(above as f64 / f64::from(num_of_samples), pick_above, 0.015);
+    }
+
+    #[test]
+    fn test_light_pdf_aims_at_attractors_along_with_lights() {
+        // Glass ten to the side of the origin, as wide as the light straight above it
+        let glass = Sphere::build(
+            Vector::new(10.5, 0.0, 0.5),
+            1.0,
+            Material::Dielectric {
+                refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
+            },
+        );
+        let config = build_config(vec![light(0.0, 1.0), glass], vec![], Integrator::Path);
+        let origin = Vector::new(0.5, 0.0, 0.5);
+        let pdf = light_pdf(&config, &origin).unwrap();
+
+        // Half of the samples go to each
+        let up = Vector::new(0.0, 1.0, 0.0);
+        assert_approx_eq!(pdf.value(&up), 0.5 * 25.0);
+        let solid_angle = 2.0 * PI * (1.0 - 0.99_f64.sqrt());
+        let towards_glass = Vector::new(1.0, 0.0, 0.0);
+        assert_approx_eq!(pdf.value(&towards_glass), 0.5 / solid_angle);
+
+        let num_of_samples = 20_000;
+        let towards_glass = (0..num_of_samples)
+            .filter(|_| pdf.generate().unit_vector().x() > 0.9)
+            .count();
+        assert_approx_eq!(towards_glass as f64 / f64::from(num_of_samples), 0.5, 0.015);
+    }
+
+    #[test]
+    fn test_light_through_coloured_medium_is_counted_once() {
+        check_light_through_red_absorbing_medium(Integrator::Path, |ray, config| {
+            colour(ray, config, PathState::new(), 1.0, &AtomicUsize::new(0))
+        });
+    }
+}
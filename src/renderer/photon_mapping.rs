@@ -0,0 +1,457 @@
+//! Stochastic progressive photon mapping, following Hachisuka and Jensen, "Stochastic Progressive
+//! Photon Mapping" (2009). Every pass traces one ray per pixel through specular bounces to its
+//! first diffuse hit, traces a batch of photons from the lights with geometry and gathers the
+//! photons that landed near each hit. The gather radius of each pixel shrinks as its photons add
+//! up, so the blur of the estimate fades over the passes while the noise stays low. Caustics,
+//! which camera paths only find by chance, are carried straight to the diffuse hits by photons
+//!
+//! Light arriving straight from the lights and the environment is sampled at the diffuse hit as in
+//! the path tracer, so photons only carry light that has bounced at least once. For spectral
+//! rendering every pass picks a single wavelength for its camera rays and photons
+//!
+//! Emissive media are seen by camera rays passing through them, but photons are only traced from
+//! the lights, so the light such media cast onto diffuse surfaces is left out
+
+use crate::camera::Ray;
+use crate::config::Config;
+use crate::data::colour::Colour;
+use crate::data::kd_tree::KdTree;
+use crate::data::spectrum::{sample_wavelength, wavelength_weight};
+use crate::data::vector::Vector;
+use crate::pdf::{power_heuristic, uniform_between, Pdf};
+use crate::renderer::bidirectional::light_subpath;
+use crate::renderer::path::{emitted_along, light_pdf, sample_light};
+use crate::renderer::{background, direct_lighting, Bounce, PathState};
+use crate::world::geometry::{HitResult, Hittable};
+use crate::world::materials::ScatterResult;
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Share of the photons found in a pass that are kept when shrinking the gather radius. Lower
+// values shrink the radius faster, trading less blur for more noise
+const PHOTON_RETENTION: f64 = 2.0 / 3.0;
+
+// Initial gather radius as a share of the size of the scene
+const INITIAL_RADIUS_SCALE: f64 = 0.005;
+
+/// Photon estimate of a pixel, refined with every pass
+struct PixelEstimate {
+    radius: f64,
+    num_of_photons: f64,
+    // Flux of the gathered photons, scaled down along with the gather area
+    flux: Colour,
+    // Sum of the light found by the camera rays themselves
+    direct: Colour,
+}
+
+struct Photon {
+    // Direction the photon was travelling in when it landed
+    direction: Vector,
+    flux: Colour,
+}
+
+/// First diffuse hit of a camera ray, where photons are gathered
+struct VisiblePoint {
+    hit: HitResult,
+    attenuation: Colour,
+    throughput: Colour,
+}
+
+pub fn render(
+    config: &Config,
+    progress_bar: &ProgressBar,
+    failed_rays: &AtomicUsize,
+) -> Vec<(u32, u32, Colour)> {
+    let pixel_coords = config.camera().pixels(config);
+    let num_of_passes = config.num_of_rays();
+    let num_of_photons = pixel_coords.len();
+    progress_bar.set_length(num_of_passes * pixel_coords.len() as u64);
+
+    let radius = initial_radius(config);
+    let mut estimates: Vec<PixelEstimate> = pixel_coords
+        .iter()
+        .map(|_| PixelEstimate {
+            radius,
+            num_of_photons: 0.0,
+            flux: Colour::new(0.0, 0.0, 0.0),
+            direct: Colour::new(0.0, 0.0, 0.0),
+        })
+        .collect();
+
+    for _ in 0..num_of_passes {
+        let wavelength = if config.spectral() {
+            Some(sample_wavelength())
+        } else {
+            None
+        };
+        let weight = match wavelength {
+            Some(wavelength) => wavelength_weight(wavelength),
+            None => Colour::new(1.0, 1.0, 1.0),
+        };
+
+        let photons = KdTree::build(
+            (0..num_of_photons)
+                .into_par_iter()
+                .flat_map(|_| trace_photon(config, wavelength, failed_rays))
+                .collect(),
+        );
+
+        estimates
+            .par_iter_mut()
+            .zip(pixel_coords.par_iter())
+            .for_each(|(estimate, (row, col))| {
+                let ray = config.camera().random_ray(*row, *col, config);
+                let ray = match wavelength {
+                    Some(wavelength) => ray.with_wavelength(wavelength),
+                    None => ray,
+                };
+
+                let (direct, visible_point) = trace_camera_ray(ray, config, failed_rays);
+                estimate.direct = estimate.direct + direct * weight;
+                if let Some(visible_point) = visible_point {
+                    gather(estimate, &visible_point, &photons, &weight);
+                }
+                progress_bar.inc(1);
+            });
+    }
+
+    let num_of_passes = num_of_passes as f64;
+    let num_of_photons = num_of_passes * num_of_photons as f64;
+    pixel_coords
+        .iter()
+        .zip(estimates.iter())
+        .map(|((row, col), estimate)| {
+            let area = PI * estimate.radius * estimate.radius;
+            let colour = estimate.direct / num_of_passes + estimate.flux / (num_of_photons * area);
+            (*row, *col, colour)
+        })
+        .collect()
+}
+
+fn initial_radius(config: &Config) -> f64 {
+    let camera = config.camera();
+    match config
+        .bvh()
+        .bounding_box(camera.time_start(), camera.time_end())
+    {
+        Some(bounding_box) => {
+            INITIAL_RADIUS_SCALE * (bounding_box.max() - bounding_box.min()).len()
+        }
+        None => 1.0,
+    }
+}
+
+/// Photons left at the diffuse surfaces hit by a light subpath, skipping the first surface as
+/// light arriving straight from the lights is sampled by the camera rays. Photons are only
+/// gathered on surfaces, so none are left inside media
+fn trace_photon(
+    config: &Config,
+    wavelength: Option<f64>,
+    failed_rays: &AtomicUsize,
+) -> Vec<(Vector, Photon)> {
+    let camera = config.camera();
+    let time = uniform_between(camera.time_start(), camera.time_end());
+    let ray = Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 0.0), time);
+    let ray = match wavelength {
+        Some(wavelength) => ray.with_wavelength(wavelength),
+        None => ray,
+    };
+
+    light_subpath(&ray, config, failed_rays)
+        .iter()
+        .skip(2)
+        .filter(|vertex| vertex.is_connectable() && !vertex.hit().material.is_volumetric())
+        .map(|vertex| {
+            let photon = Photon {
+                direction: *vertex.hit().ray.direction(),
+                flux: vertex.throughput,
+            };
+            (vertex.point, photon)
+        })
+        .collect()
+}
+
+/// Light found by a camera ray up to its first diffuse hit, including the light sampled there,
+/// along with the hit itself
+fn trace_camera_ray(
+    mut ray: Ray,
+    config: &Config,
+    failed_rays: &AtomicUsize,
+) -> (Colour, Option<VisiblePoint>) {
+    let path_depths = config.path_depths();
+    let mut path = PathState::new();
+    let mut light = Colour::new(0.0, 0.0, 0.0);
+
+    loop {
+        let hit = match config.bvh().hit(&ray, 0.001, f64::MAX) {
+            Some(hit) => hit,
+            None => return (light + path.throughput * background(&ray, config), None),
+        };
+        let point = hit.point;
+        let hit = match hit.resolve_material(config.assets()) {
+            Some(hit) => hit,
+            None => {
+                ray = ray.continued(point, *ray.direction());
+                continue;
+            }
+        };
+        light = light + path.throughput * hit.material.emitted(&hit, config.assets());
+
+        let scatter = hit
+            .material
+            .scatter(&hit, config.assets())
+            .map(|scatter| scatter.at_wavelength(ray.wavelength()));
+        match scatter {
+            None => return (light, None),
+            Some(ScatterResult::Specular {
+                attenuation,
+                ray: scattered,
+            }) => {
+                let bounce = if hit.material.is_volumetric() {
+                    Bounce::Null
+                } else {
+                    Bounce::Specular
+                };
+                if path.reached_max_depth(bounce, path_depths) {
+                    return (light, None);
+                }
+                path = match path.bounce(bounce, &attenuation, path_depths) {
+                    Some((next, _)) => next,
+                    None => return (light, None),
+                };
+                ray = scattered;
+            }
+            Some(ScatterResult::Diffuse { attenuation, pdf }) if hit.material.is_volumetric() => {
+                // Camera rays go on through media, with the light they scatter found by photons
+                // on the surfaces beyond
+                if path.reached_max_depth(Bounce::Volume, path_depths) {
+                    return (light, None);
+                }
+                let direction = pdf.generate();
+                let pdf_value = pdf.value(&direction);
+                if pdf_value <= 0.0 {
+                    failed_rays.fetch_add(1, Ordering::SeqCst);
+                    return (light, None);
+                }
+                let scattered = hit.ray.continued(hit.point, direction);
+                let weight =
+                    attenuation * hit.material.scattering_pdf(&hit, &scattered) / pdf_value;
+                path = match path.bounce(Bounce::Volume, &weight, path_depths) {
+                    Some((next, _)) => next,
+                    None => return (light, None),
+                };
+                ray = scattered;
+            }
+            Some(ScatterResult::Diffuse { attenuation, pdf }) => {
+                let direct = sample_direct_light(config, &hit, &attenuation, &pdf, failed_rays);
+                light = light + path.throughput * direct;
+                let visible_point = VisiblePoint {
+                    hit,
+                    attenuation,
+                    throughput: path.throughput,
+                };
+                return (light, Some(visible_point));
+            }
+        }
+    }
+}
+
+/// Light arriving straight from the lights and the environment, found both by sampling them and
+/// by scattering, weighted against each other
+fn sample_direct_light(
+    config: &Config,
+    hit: &HitResult,
+    attenuation: &Colour,
+    scatter_pdf: &Pdf,
+    failed_rays: &AtomicUsize,
+) -> Colour {
+    let light_pdf = light_pdf(config, &hit.point);
+    let sampled = match &light_pdf {
+        Some(light_pdf) => sample_light(config, hit, attenuation, scatter_pdf, light_pdf),
+        None => Colour::new(0.0, 0.0, 0.0),
+    };
+    let sampled = sampled + direct_lighting(config, hit, attenuation);
+
+    let direction = scatter_pdf.generate();
+    let pdf_value = scatter_pdf.value(&direction);
+    if pdf_value <= 0.0 {
+        failed_rays.fetch_add(1, Ordering::SeqCst);
+        return sampled;
+    }
+
+    let weight = match &light_pdf {
+        Some(light_pdf) => power_heuristic(pdf_value, light_pdf.value(&direction)),
+        None => 1.0,
+    };
+    let scattered = hit.ray.continued(hit.point, direction);
+    let scattering_pdf = hit.material.scattering_pdf(hit, &scattered);
+    sampled + attenuation * scattering_pdf * emitted_along(&scattered, config) * weight / pdf_value
+}
+
+/// Adds the photons around a visible point to the estimate of its pixel, then shrinks the gather
+/// radius as in section 4.2 of the paper
+fn gather(
+    estimate: &mut PixelEstimate,
+    visible_point: &VisiblePoint,
+    photons: &KdTree<Photon>,
+    weight: &Colour,
+) {
+    let hit = &visible_point.hit;
+    let normal = hit.face_normal();
+    let mut found = 0.0;
+    let mut flux = Colour::new(0.0, 0.0, 0.0);
+
+    photons.within(&hit.point, estimate.radius, |_, photon| {
+        let towards_light = -photon.direction.unit_vector();
+        let cosine = Vector::dot(&normal, &towards_light);
+        if cosine <= 0.0 {
+            return;
+        }
+
+        // The material scatters towards the camera the light arriving from the photon direction
+        let scattered = hit.ray.continued(hit.point, towards_light);
+        let scattering_pdf = hit.material.scattering_pdf(hit, &scattered);
+        flux = flux + visible_point.attenuation * scattering_pdf / cosine * photon.flux;
+        found += 1.0;
+    });
+    if found <= 0.0 {
+        return;
+    }
+
+    let num_of_photons = estimate.num_of_photons + PHOTON_RETENTION * found;
+    let radius = estimate.radius * (num_of_photons / (estimate.num_of_photons + found)).sqrt();
+    let shrink = (radius / estimate.radius).powi(2);
+    estimate.flux = (estimate.flux + visible_point.throughput * flux * weight) * shrink;
+    estimate.num_of_photons = num_of_photons;
+    estimate.radius = radius;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::tests::{build_config, floor_hit, white};
+    use crate::renderer::Integrator;
+    use crate::world::geometry::rectangle::{XyRect, XzRect};
+    use crate::world::materials::Material;
+    use crate::world::texture::Texture;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn grey(albedo: f64) -> Material {
+        Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(albedo, albedo, albedo),
+            },
+        }
+    }
+
+    /// White floor at the origin, seen from straight above
+    fn visible_point() -> VisiblePoint {
+        VisiblePoint {
+            hit: floor_hit(Vector::new(0.0, 1.0, 0.0), white()),
+            attenuation: Colour::new(1.0, 1.0, 1.0),
+            throughput: Colour::new(0.5, 0.5, 0.5),
+        }
+    }
+
+    fn photon(x: f64, direction: Vector) -> (Vector, Photon) {
+        let photon = Photon {
+            direction,
+            flux: Colour::new(0.3, 0.3, 0.3),
+        };
+        (Vector::new(x, 0.0, 0.0), photon)
+    }
+
+    #[test]
+    fn test_gather_shrinks_radius_and_scales_flux() {
+        let down = Vector::new(0.0, -1.0, 0.0);
+        let photons = KdTree::build(vec![
+            photon(0.0, down),
+            photon(0.2, down),
+            photon(-0.4, down),
+            // Outside the radius
+            photon(2.0, down),
+            // Arriving from below the floor
+            photon(0.1, Vector::new(0.0, 1.0, 0.0)),
+        ]);
+        let mut estimate = PixelEstimate {
+            radius: 1.0,
+            num_of_photons: 0.0,
+            flux: Colour::new(0.0, 0.0, 0.0),
+            direct: Colour::new(0.0, 0.0, 0.0),
+        };
+        let weight = Colour::new(1.0, 1.0, 1.0);
+
+        // Each photon straight down on a white Lambertian floor brings in 0.3 / pi, and two
+        // thirds of the three found are kept, so the area shrinks to 2 / 3
+        gather(&mut estimate, &visible_point(), &photons, &weight);
+        assert_approx_eq!(estimate.num_of_photons, 2.0);
+        assert_approx_eq!(estimate.radius, (2.0_f64 / 3.0).sqrt());
+        assert_approx_eq!(estimate.flux.r(), 0.5 * 0.9 / PI * 2.0 / 3.0);
+
+        // The same three are still within the smaller radius: 2 + 2 of 2 + 3 are kept
+        gather(&mut estimate, &visible_point(), &photons, &weight);
+        assert_approx_eq!(estimate.num_of_photons, 4.0);
+        assert_approx_eq!(estimate.radius, (8.0_f64 / 15.0).sqrt());
+        assert_approx_eq!(estimate.flux.r(), (0.3 + 0.45) / PI * 0.8);
+        assert_approx_eq!(estimate.flux.b(), 0.6 / PI);
+    }
+
+    #[test]
+    fn test_gather_without_photons_leaves_estimate() {
+        let photons = KdTree::build(vec![photon(2.0, Vector::new(0.0, -1.0, 0.0))]);
+        let mut estimate = PixelEstimate {
+            radius: 1.0,
+            num_of_photons: 3.0,
+            flux: Colour::new(0.1, 0.1, 0.1),
+            direct: Colour::new(0.0, 0.0, 0.0),
+        };
+        let weight = Colour::new(1.0, 1.0, 1.0);
+        gather(&mut estimate, &visible_point(), &photons, &weight);
+        assert_approx_eq!(estimate.radius, 1.0);
+        assert_approx_eq!(estimate.num_of_photons, 3.0);
+        assert_approx_eq!(estimate.flux.r(), 0.1);
+    }
+
+    #[test]
+    fn test_camera_ray_stops_at_first_diffuse_hit() {
+        let floor = XzRect::build((-1.0, 1.0), (-0.5, 0.5), 0.0, grey(0.5));
+        let mirror = XyRect::build(
+            (-1.0, 1.0),
+            (0.1, 2.0),
+            -1.0,
+            Material::Metal {
+                albedo: Colour::new(0.8, 0.8, 0.8),
+                fuzz: 0.0,
+                thin_film: None,
+            },
+        );
+        let config = build_config(vec![floor, mirror], vec![], Integrator::PhotonMapping);
+        let failed_rays = AtomicUsize::new(0);
+
+        // Straight down onto the floor
+        let ray = Ray::new(Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+        let visible_point = trace_camera_ray(ray, &config, &failed_rays).1.unwrap();
+        assert_approx_eq!(visible_point.hit.point.y(), 0.0);
+        assert_approx_eq!(visible_point.attenuation.r(), 0.5);
+        assert_approx_eq!(visible_point.throughput.r(), 1.0);
+
+        // Off the mirror and back down onto the floor at the origin
+        let ray = Ray::new(
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(0.0, -0.5, -1.0),
+            0.0,
+        );
+        let visible_point = trace_camera_ray(ray, &config, &failed_rays).1.unwrap();
+        assert_approx_eq!(visible_point.hit.point.x(), 0.0);
+        assert_approx_eq!(visible_point.hit.point.y(), 0.0);
+        assert_approx_eq!(visible_point.hit.point.z(), 0.0);
+        assert_approx_eq!(visible_point.attenuation.r(), 0.5);
+        assert_approx_eq!(visible_point.throughput.r(), 0.8);
+
+        // Away from everything, out to the background
+        let ray = Ray::new(Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, 1.0, 0.0), 0.0);
+        assert!(trace_camera_ray(ray, &config, &failed_rays).1.is_none());
+    }
+}
@@ -48,6 +48,7 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         Material::Metal {
             albedo: Colour::new(0.8, 0.6, 0.2),
             fuzz: 0.1,
+            thin_film: None,
         },
     ));
     geometries.push(Sphere::build(
@@ -55,6 +56,8 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         -0.45,
         Material::Dielectric {
             refractive_index: 1.5,
+            thin_film: None,
+            dispersion: None,
         },
     ));
 
@@ -62,7 +65,7 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
     let blue = Colour::new(0.5, 0.7, 1.0);
     let background = Background::new(blue, white);
 
-    let world = WorldSave::new(background, geometries);
+    let world = WorldSave::new(background, geometries, vec![]);
 
     Ok(ConfigSave::new(aspect, camera, world))
 }
@@ -6,6 +6,7 @@ use crate::world::background::Background;
 use crate::world::geometry::cube::Cube;
 use crate::world::geometry::rectangle::{XyRect, XzRect, YzRect};
 use crate::world::geometry::Geometry;
+use crate::world::materials::emission::EmissionProfile;
 use crate::world::materials::Material;
 use crate::world::texture::Texture;
 use crate::world::WorldSave;
@@ -44,6 +45,8 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         emit: Texture::Constant {
             colour: Colour::new(15.0, 15.0, 15.0),
         },
+        two_sided: false,
+        profile: EmissionProfile::Lambertian,
     };
 
     geometries.push(YzRect::build((0.0, 555.0), (0.0, 555.0), 555.0, green).flip());
@@ -79,7 +82,7 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
     let black = Colour::new(0.0, 0.0, 0.0);
     let background = Background::new(black, black);
 
-    let world = WorldSave::new(background, geometries);
+    let world = WorldSave::new(background, geometries, vec![]);
 
     Ok(ConfigSave::new(aspect, camera, world))
 }
@@ -0,0 +1,87 @@
+use crate::camera::{CameraSave, Lens};
+use crate::config::ConfigSave;
+use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::world::background::Background;
+use crate::world::geometry::medium::{DensityField, HeterogeneousMedium};
+use crate::world::geometry::rectangle::{XyRect, XzRect, YzRect};
+use crate::world::geometry::sphere::Sphere;
+use crate::world::geometry::Geometry;
+use crate::world::materials::emission::EmissionProfile;
+use crate::world::materials::phase::PhaseFunction;
+use crate::world::materials::Material;
+use crate::world::texture::perlin::build_noise_config;
+use crate::world::texture::Texture;
+use crate::world::WorldSave;
+
+pub fn build() -> Result<ConfigSave, anyhow::Error> {
+    let aspect = 1.0;
+
+    let camera = CameraSave::new(
+        &Vector::new(278.0, 278.0, -800.0),
+        &Vector::new(278.0, 278.0, 0.0),
+        &Vector::new(0.0, 1.0, 0.0),
+        aspect,
+        Lens::new(40.0, 0.0, 10.0),
+        0.0,
+        1.0,
+    );
+
+    let mut geometries: Vec<Geometry> = Vec::with_capacity(7);
+
+    let red = Material::Lambertian {
+        albedo: Texture::Constant {
+            colour: Colour::new(0.65, 0.05, 0.05),
+        },
+    };
+    let white = Material::Lambertian {
+        albedo: Texture::Constant {
+            colour: Colour::new(0.73, 0.73, 0.73),
+        },
+    };
+    let green = Material::Lambertian {
+        albedo: Texture::Constant {
+            colour: Colour::new(0.12, 0.45, 0.15),
+        },
+    };
+    let light = Material::DiffuseLight {
+        emit: Texture::Constant {
+            colour: Colour::new(15.0, 15.0, 15.0),
+        },
+        two_sided: false,
+        profile: EmissionProfile::Lambertian,
+    };
+
+    geometries.push(YzRect::build((0.0, 555.0), (0.0, 555.0), 555.0, green).flip());
+    geometries.push(YzRect::build((0.0, 555.0), (0.0, 555.0), 0.0, red));
+    geometries.push(XzRect::build((213.0, 343.0), (227.0, 332.0), 554.0, light).flip());
+    geometries.push(XzRect::build((0.0, 555.0), (0.0, 555.0), 555.0, white.clone()).flip());
+    geometries.push(XzRect::build(
+        (0.0, 555.0),
+        (0.0, 555.0),
+        0.0,
+        white.clone(),
+    ));
+    geometries.push(XyRect::build((0.0, 555.0), (0.0, 555.0), 555.0, white.clone()).flip());
+
+    let cloud_boundary = Sphere::build(Vector::new(278.0, 260.0, 278.0), 180.0, white);
+    geometries.push(HeterogeneousMedium::build(
+        cloud_boundary,
+        0.1,
+        DensityField::Noise {
+            scale: 0.03,
+            noise_config: build_noise_config(),
+        },
+        Texture::Constant {
+            colour: Colour::new(0.95, 0.95, 0.95),
+        },
+        PhaseFunction::HenyeyGreenstein { asymmetry: 0.6 },
+    ));
+
+    let black = Colour::new(0.0, 0.0, 0.0);
+    let background = Background::new(black, black);
+
+    let world = WorldSave::new(background, geometries, vec![]);
+
+    Ok(ConfigSave::new(aspect, camera, world))
+}
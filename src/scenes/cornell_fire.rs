@@ -0,0 +1,110 @@
+use crate::camera::{CameraSave, Lens};
+use crate::config::ConfigSave;
+use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::world::background::Background;
+use crate::world::geometry::cube::Cube;
+use crate::world::geometry::medium::{ConstantMedium, DensityField, HeterogeneousMedium};
+use crate::world::geometry::rectangle::{XyRect, XzRect, YzRect};
+use crate::world::geometry::sphere::Sphere;
+use crate::world::geometry::Geometry;
+use crate::world::materials::emission::EmissionProfile;
+use crate::world::materials::phase::PhaseFunction;
+use crate::world::materials::Material;
+use crate::world::texture::perlin::build_noise_config;
+use crate::world::texture::Texture;
+use crate::world::WorldSave;
+
+pub fn build() -> Result<ConfigSave, anyhow::Error> {
+    let aspect = 1.0;
+
+    let camera = CameraSave::new(
+        &Vector::new(278.0, 278.0, -800.0),
+        &Vector::new(278.0, 278.0, 0.0),
+        &Vector::new(0.0, 1.0, 0.0),
+        aspect,
+        Lens::new(40.0, 0.0, 10.0),
+        0.0,
+        1.0,
+    );
+
+    let mut geometries: Vec<Geometry> = Vec::with_capacity(8);
+
+    let red = Material::Lambertian {
+        albedo: Texture::Constant {
+            colour: Colour::new(0.65, 0.05, 0.05),
+        },
+    };
+    let white = Material::Lambertian {
+        albedo: Texture::Constant {
+            colour: Colour::new(0.73, 0.73, 0.73),
+        },
+    };
+    let green = Material::Lambertian {
+        albedo: Texture::Constant {
+            colour: Colour::new(0.12, 0.45, 0.15),
+        },
+    };
+    let light = Material::DiffuseLight {
+        emit: Texture::Constant {
+            colour: Colour::new(7.0, 7.0, 7.0),
+        },
+        two_sided: false,
+        profile: EmissionProfile::Lambertian,
+    };
+
+    geometries.push(YzRect::build((0.0, 555.0), (0.0, 555.0), 555.0, green).flip());
+    geometries.push(YzRect::build((0.0, 555.0), (0.0, 555.0), 0.0, red));
+    geometries.push(XzRect::build((213.0, 343.0), (227.0, 332.0), 554.0, light).flip());
+    geometries.push(XzRect::build((0.0, 555.0), (0.0, 555.0), 555.0, white.clone()).flip());
+    geometries.push(XzRect::build(
+        (0.0, 555.0),
+        (0.0, 555.0),
+        0.0,
+        white.clone(),
+    ));
+    geometries.push(XyRect::build((0.0, 555.0), (0.0, 555.0), 555.0, white.clone()).flip());
+
+    // Coloured liquid, absorbing blue and green far more than red
+    let tank_boundary = Cube::build(
+        Vector::new(0.0, 0.0, 0.0),
+        Vector::new(165.0, 165.0, 165.0),
+        white.clone(),
+    )
+    .rotate_y(-18.0)?
+    .translate(Vector::new(130.0, 0.0, 65.0));
+    geometries.push(ConstantMedium::build_with_material(
+        tank_boundary,
+        1.0,
+        Material::Medium {
+            absorption: Colour::new(0.001, 0.006, 0.015),
+            scattering: Colour::new(0.002, 0.002, 0.002),
+            emission: Colour::new(0.0, 0.0, 0.0),
+            phase: PhaseFunction::Isotropic,
+        },
+    ));
+
+    // Fire, glowing where the noise is dense and barely scattering any light
+    let fire_boundary = Sphere::build(Vector::new(370.0, 200.0, 350.0), 110.0, white);
+    geometries.push(HeterogeneousMedium::build_with_material(
+        fire_boundary,
+        1.0,
+        DensityField::Noise {
+            scale: 0.03,
+            noise_config: build_noise_config(),
+        },
+        Material::Medium {
+            absorption: Colour::new(0.01, 0.01, 0.01),
+            scattering: Colour::new(0.002, 0.002, 0.002),
+            emission: Colour::new(8.0, 2.5, 0.5),
+            phase: PhaseFunction::Isotropic,
+        },
+    ));
+
+    let black = Colour::new(0.0, 0.0, 0.0);
+    let background = Background::new(black, black);
+
+    let world = WorldSave::new(background, geometries, vec![]);
+
+    Ok(ConfigSave::new(aspect, camera, world))
+}
@@ -6,6 +6,7 @@ use crate::world::background::Background;
 use crate::world::geometry::cube::Cube;
 use crate::world::geometry::rectangle::{XyRect, XzRect, YzRect};
 use crate::world::geometry::Geometry;
+use crate::world::materials::emission::EmissionProfile;
 use crate::world::materials::Material;
 use crate::world::texture::Texture;
 use crate::world::WorldSave;
@@ -44,10 +45,13 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         emit: Texture::Constant {
             colour: Colour::new(15.0, 15.0, 15.0),
         },
+        two_sided: false,
+        profile: EmissionProfile::Lambertian,
     };
     let metal = Material::Metal {
         albedo: Colour::new(0.8, 0.85, 0.88),
         fuzz: 0.0,
+        thin_film: None,
     };
 
     geometries.push(YzRect::build((0.0, 555.0), (0.0, 555.0), 555.0, green).flip());
@@ -83,7 +87,7 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
     let black = Colour::new(0.0, 0.0, 0.0);
     let background = Background::new(black, black);
 
-    let world = WorldSave::new(background, geometries);
+    let world = WorldSave::new(background, geometries, vec![]);
 
     Ok(ConfigSave::new(aspect, camera, world))
 }
@@ -7,6 +7,8 @@ use crate::world::geometry::cube::Cube;
 use crate::world::geometry::medium::ConstantMedium;
 use crate::world::geometry::rectangle::{XyRect, XzRect, YzRect};
 use crate::world::geometry::Geometry;
+use crate::world::materials::emission::EmissionProfile;
+use crate::world::materials::phase::PhaseFunction;
 use crate::world::materials::Material;
 use crate::world::texture::Texture;
 use crate::world::WorldSave;
@@ -45,6 +47,8 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         emit: Texture::Constant {
             colour: Colour::new(7.0, 7.0, 7.0),
         },
+        two_sided: false,
+        profile: EmissionProfile::Lambertian,
     };
 
     geometries.push(YzRect::build((0.0, 555.0), (0.0, 555.0), 555.0, green).flip());
@@ -80,6 +84,7 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         Texture::Constant {
             colour: Colour::new(1.0, 1.0, 1.0),
         },
+        PhaseFunction::Isotropic,
     ));
     geometries.push(ConstantMedium::build(
         box_boundary_b,
@@ -87,12 +92,13 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         Texture::Constant {
             colour: Colour::new(0.0, 0.0, 0.0),
         },
+        PhaseFunction::Isotropic,
     ));
 
     let black = Colour::new(0.0, 0.0, 0.0);
     let background = Background::new(black, black);
 
-    let world = WorldSave::new(background, geometries);
+    let world = WorldSave::new(background, geometries, vec![]);
 
     Ok(ConfigSave::new(aspect, camera, world))
 }
@@ -7,6 +7,7 @@ use crate::world::geometry::cube::Cube;
 use crate::world::geometry::rectangle::{XyRect, XzRect, YzRect};
 use crate::world::geometry::sphere::Sphere;
 use crate::world::geometry::Geometry;
+use crate::world::materials::emission::EmissionProfile;
 use crate::world::materials::Material;
 use crate::world::texture::Texture;
 use crate::world::WorldSave;
@@ -45,9 +46,13 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         emit: Texture::Constant {
             colour: Colour::new(15.0, 15.0, 15.0),
         },
+        two_sided: false,
+        profile: EmissionProfile::Lambertian,
     };
     let dielectric = Material::Dielectric {
         refractive_index: 1.5,
+        thin_film: None,
+        dispersion: None,
     };
 
     geometries.push(YzRect::build((0.0, 555.0), (0.0, 555.0), 555.0, green).flip());
@@ -79,7 +84,7 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
     let black = Colour::new(0.0, 0.0, 0.0);
     let background = Background::new(black, black);
 
-    let world = WorldSave::new(background, geometries);
+    let world = WorldSave::new(background, geometries, vec![]);
 
     Ok(ConfigSave::new(aspect, camera, world))
 }
@@ -56,6 +56,8 @@ pub fn build(motion_blur: bool, checker_texture: bool) -> Result<ConfigSave, any
         1.0,
         Material::Dielectric {
             refractive_index: 1.5,
+            thin_film: None,
+            dispersion: None,
         },
     ));
     geometries.push(Sphere::build(
@@ -73,6 +75,7 @@ pub fn build(motion_blur: bool, checker_texture: bool) -> Result<ConfigSave, any
         Material::Metal {
             albedo: Colour::new(0.7, 0.6, 0.5),
             fuzz: 0.0,
+            thin_film: None,
         },
     ));
 
@@ -130,6 +133,7 @@ pub fn build(motion_blur: bool, checker_texture: bool) -> Result<ConfigSave, any
                                 0.5 * (1.0 + uniform::<f64>()),
                             ),
                             fuzz: 0.5 * uniform::<f64>(),
+                            thin_film: None,
                         },
                     ));
                 } else {
@@ -138,6 +142,8 @@ pub fn build(motion_blur: bool, checker_texture: bool) -> Result<ConfigSave, any
                         0.2,
                         Material::Dielectric {
                             refractive_index: 1.5,
+                            thin_film: None,
+                            dispersion: None,
                         },
                     ));
                 }
@@ -149,7 +155,7 @@ pub fn build(motion_blur: bool, checker_texture: bool) -> Result<ConfigSave, any
     let blue = Colour::new(0.5, 0.7, 1.0);
     let background = Background::new(white, blue);
 
-    let world = WorldSave::new(background, geometries);
+    let world = WorldSave::new(background, geometries, vec![]);
 
     Ok(ConfigSave::new(aspect, camera, world))
 }
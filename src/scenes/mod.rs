@@ -2,6 +2,8 @@ use crate::config::ConfigSave;
 
 mod basic;
 mod cornell_box;
+mod cornell_cloud;
+mod cornell_fire;
 mod cornell_metal;
 mod cornell_smoke;
 mod cornell_sphere;
@@ -25,6 +27,8 @@ arg_enum! {
         CornellSmoke,
         CornellMetal,
         CornellSphere,
+        CornellCloud,
+        CornellFire,
         NextWeekFinal,
     }
 }
@@ -42,6 +46,8 @@ pub fn build_scene_config(scene: &Scene) -> Result<ConfigSave, anyhow::Error> {
         Scene::CornellSmoke => cornell_smoke::build(),
         Scene::CornellMetal => cornell_metal::build(),
         Scene::CornellSphere => cornell_sphere::build(),
+        Scene::CornellCloud => cornell_cloud::build(),
+        Scene::CornellFire => cornell_fire::build(),
         Scene::NextWeekFinal => next_week_final::build(),
     }
 }
@@ -4,12 +4,15 @@ use crate::data::colour::Colour;
 use crate::data::vector::Vector;
 use crate::pdf::uniform;
 use crate::world::background::Background;
+use crate::world::fog::{Fog, FogDensity};
 use crate::world::geometry::bounding_volume_hierarchy::BoundingVolumeHierarchyNode;
 use crate::world::geometry::cube::Cube;
 use crate::world::geometry::medium::ConstantMedium;
 use crate::world::geometry::rectangle::XzRect;
 use crate::world::geometry::sphere::{MovingSphere, Sphere};
 use crate::world::geometry::Geometry;
+use crate::world::materials::emission::EmissionProfile;
+use crate::world::materials::phase::PhaseFunction;
 use crate::world::materials::Material;
 use crate::world::texture::perlin::build_noise_config;
 use crate::world::texture::Texture;
@@ -40,10 +43,6 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
     geometries.push(boundary);
     geometries.push(medium);
 
-    let (boundary, medium) = subsurface_material_b();
-    geometries.push(boundary);
-    geometries.push(medium);
-
     geometries.push(earth());
     geometries.push(perlin());
 
@@ -57,7 +56,13 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
     let black = Colour::new(0.0, 0.0, 0.0);
     let background = Background::new(black, black);
 
-    let world = WorldSave::new(background, geometries);
+    // Thin mist filling the whole scene
+    let fog = Fog::new(
+        FogDensity::Homogeneous { density: 0.0001 },
+        Colour::new(1.0, 1.0, 1.0),
+        PhaseFunction::Isotropic,
+    );
+    let world = WorldSave::new(background, geometries, vec![]).with_fog(fog);
 
     Ok(ConfigSave::new(aspect, camera, world))
 }
@@ -94,6 +99,8 @@ fn light() -> Geometry {
         emit: Texture::Constant {
             colour: Colour::new(7.0, 7.0, 7.0),
         },
+        two_sided: false,
+        profile: EmissionProfile::Lambertian,
     };
     XzRect::build((123.0, 423.0), (147.0, 412.0), 554.0, light).flip()
 }
@@ -115,6 +122,8 @@ fn dielectric_a() -> Geometry {
         50.0,
         Material::Dielectric {
             refractive_index: 1.5,
+            thin_film: None,
+            dispersion: None,
         },
     )
 }
@@ -126,6 +135,7 @@ fn metal_a() -> Geometry {
         Material::Metal {
             albedo: Colour::new(0.8, 0.8, 0.9),
             fuzz: 1.0,
+            thin_film: None,
         },
     )
 }
@@ -136,6 +146,8 @@ fn subsurface_material_a() -> (Geometry, Geometry) {
         70.0,
         Material::Dielectric {
             refractive_index: 1.5,
+            thin_film: None,
+            dispersion: None,
         },
     );
     let medium = ConstantMedium::build(
@@ -144,24 +156,7 @@ fn subsurface_material_a() -> (Geometry, Geometry) {
         Texture::Constant {
             colour: Colour::new(0.2, 0.4, 0.9),
         },
-    );
-    (boundary, medium)
-}
-
-fn subsurface_material_b() -> (Geometry, Geometry) {
-    let boundary = Sphere::build(
-        Vector::new(0.0, 0.0, 0.0),
-        5000.0,
-        Material::Dielectric {
-            refractive_index: 1.5,
-        },
-    );
-    let medium = ConstantMedium::build(
-        boundary.clone(),
-        0.0001,
-        Texture::Constant {
-            colour: Colour::new(1.0, 1.0, 1.0),
-        },
+        PhaseFunction::Isotropic,
     );
     (boundary, medium)
 }
@@ -54,7 +54,7 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
     let blue = Colour::new(0.5, 0.7, 1.0);
     let background = Background::new(white, blue);
 
-    let world = WorldSave::new(background, geometries);
+    let world = WorldSave::new(background, geometries, vec![]);
 
     Ok(ConfigSave::new(aspect, camera, world))
 }
@@ -73,7 +73,7 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
     let background_colour = Colour::new(1.0, 1.0, 1.0);
     let background = Background::new(background_colour, background_colour);
 
-    let world = WorldSave::new(background, geometries);
+    let world = WorldSave::new(background, geometries, vec![]);
 
     Ok(ConfigSave::new(aspect, camera, world))
 }
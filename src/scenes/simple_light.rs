@@ -6,6 +6,7 @@ use crate::world::background::Background;
 use crate::world::geometry::rectangle::XyRect;
 use crate::world::geometry::sphere::Sphere;
 use crate::world::geometry::Geometry;
+use crate::world::materials::emission::EmissionProfile;
 use crate::world::materials::Material;
 use crate::world::texture::perlin::build_noise_config;
 use crate::world::texture::Texture;
@@ -57,6 +58,8 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
             emit: Texture::Constant {
                 colour: Colour::new(4.0, 4.0, 4.0),
             },
+            two_sided: false,
+            profile: EmissionProfile::Lambertian,
         },
     ));
     geometries.push(XyRect::build(
@@ -67,13 +70,15 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
             emit: Texture::Constant {
                 colour: Colour::new(4.0, 4.0, 4.0),
             },
+            two_sided: false,
+            profile: EmissionProfile::Lambertian,
         },
     ));
 
     let black = Colour::new(0.0, 0.0, 0.0);
     let background = Background::new(black, black);
 
-    let world = WorldSave::new(background, geometries);
+    let world = WorldSave::new(background, geometries, vec![]);
 
     Ok(ConfigSave::new(aspect, camera, world))
 }
@@ -1,21 +1,324 @@
+use crate::data::assets::Assets;
 use crate::data::colour::Colour;
+use crate::data::image::{Image, Pixel};
+use crate::data::vector::Vector;
+use crate::float;
+use crate::onb::Onb;
+use crate::pdf::distribution::Distribution2D;
+use crate::pdf::uniform;
+use crate::world::geometry::sphere::random_to_sphere;
+use crate::world::sky::{sun_cos_max, Sky};
+use serde::{Deserialize, Deserializer};
+use std::f64::consts::PI;
+
+// Share of the samples of a sky that go towards the sun disk
+const SUN_SAMPLING_RATIO: f64 = 0.5;
+// Resolution of the image a sky is tabulated into to importance sample it
+const SKY_SAMPLING_WIDTH: u32 = 128;
+const SKY_SAMPLING_HEIGHT: u32 = 64;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Background {
-    top: Colour,
-    bottom: Colour,
+pub enum Background {
+    // Vertical gradient between two colours
+    Gradient {
+        top: Colour,
+        bottom: Colour,
+    },
+    // Equirectangular image asset, ideally HDR, wrapped around the scene with its centre column
+    // towards +z. The rotation in degrees turns it about the y axis
+    Environment {
+        asset_name: String,
+        rotation: f64,
+        intensity: f64,
+    },
+    // Physically based clear daylight sky with a sun disk. Angles are in degrees, see `Sky::new`.
+    // Luminances are in units of 683 cd / m², so the intensity of a daylight scene is usually
+    // well below 1
+    Sky {
+        #[serde(flatten)]
+        sky: Sky,
+        intensity: f64,
+    },
+}
+
+/// Background as read from a config. Configs from before there were other kinds of background give
+/// the colours of the gradient directly
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BackgroundSave {
+    Background(Background),
+    Gradient { top: Colour, bottom: Colour },
+}
+
+/// Importance samples directions towards the bright parts of an environment map or sky
+#[derive(Debug)]
+pub struct EnvironmentSampler {
+    distribution: Distribution2D,
+    rotation: f64,
+    // Direction of a sun disk that is too small to show up in the distribution
+    sun: Option<Vector>,
 }
 
 impl Background {
     pub fn new(top: Colour, bottom: Colour) -> Background {
-        Background { top, bottom }
+        Background::Gradient { top, bottom }
+    }
+
+    /// Reads a background in either the current or the old config format
+    pub fn deserialize_any_format<'de, D>(deserializer: D) -> Result<Background, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match BackgroundSave::deserialize(deserializer)? {
+            BackgroundSave::Background(background) => background,
+            BackgroundSave::Gradient { top, bottom } => Background::new(top, bottom),
+        })
     }
 
-    pub fn top(&self) -> &Colour {
-        &self.top
+    pub fn value(&self, direction: &Vector, assets: &Assets) -> Colour {
+        match self {
+            Background::Gradient { top, bottom } => {
+                let t = 0.5 * (direction.unit_vector().y() + 1.0);
+                (1.0 - t) * bottom + t * top
+            }
+            Background::Environment {
+                asset_name,
+                rotation,
+                intensity,
+            } => {
+                let image = assets.get_asset(asset_name);
+                let (u, v) = direction_to_uv(direction, *rotation);
+                let (row, col) = uv_to_pixel(image, u, v);
+                *intensity * image.get_pixel(row, col)
+            }
+            Background::Sky { sky, intensity } => *intensity * sky.value(direction),
+        }
     }
 
-    pub fn bottom(&self) -> &Colour {
-        &self.bottom
+    pub fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        match self {
+            Background::Gradient { .. } | Background::Sky { .. } => Ok(()),
+            Background::Environment { asset_name, .. } => Ok(assets.validate(asset_name)?),
+        }
+    }
+
+    pub fn sampler(&self, assets: &Assets) -> Option<EnvironmentSampler> {
+        match self {
+            Background::Gradient { .. } => None,
+            Background::Environment {
+                asset_name,
+                rotation,
+                ..
+            } => Some(EnvironmentSampler::new(
+                assets.get_asset(asset_name),
+                *rotation,
+            )),
+            Background::Sky { sky, .. } => Some(EnvironmentSampler::for_sky(sky)),
+        }
+    }
+}
+
+impl EnvironmentSampler {
+    pub fn new(image: &Image, rotation: f64) -> EnvironmentSampler {
+        let height = image.height();
+
+        // Rows near the poles cover a smaller solid angle, so weigh them by sin θ
+        let function = (0..height)
+            .map(|row_from_top| {
+                let theta = PI * (f64::from(row_from_top) + 0.5) / f64::from(height);
+                (0..image.width())
+                    .map(|col| {
+                        let colour = image.get_pixel(height - row_from_top - 1, col);
+                        f64::max(0.0, colour.luminance()) * theta.sin()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        EnvironmentSampler {
+            distribution: Distribution2D::new(function),
+            rotation,
+            sun: None,
+        }
+    }
+
+    pub fn for_sky(sky: &Sky) -> EnvironmentSampler {
+        let pixels: Vec<Pixel> = iproduct!(0..SKY_SAMPLING_HEIGHT, 0..SKY_SAMPLING_WIDTH)
+            .map(|(row, col)| {
+                let u = (f64::from(col) + 0.5) / f64::from(SKY_SAMPLING_WIDTH);
+                let v = 1.0 - (f64::from(row) + 0.5) / f64::from(SKY_SAMPLING_HEIGHT);
+                let direction = uv_to_direction(u, v, 0.0);
+                let cos_gamma = Vector::dot(&direction, sky.sun_direction());
+                Pixel::new(row, col, sky.sky_value(&direction, cos_gamma))
+            })
+            .collect();
+
+        let sun = if sky.sun_direction().y() > 0.0 {
+            Some(*sky.sun_direction())
+        } else {
+            None
+        };
+
+        EnvironmentSampler {
+            sun,
+            ..EnvironmentSampler::new(&Image::from(&pixels), 0.0)
+        }
+    }
+
+    /// Probability density with respect to solid angle
+    pub fn value(&self, direction: &Vector) -> f64 {
+        let (u, v) = direction_to_uv(direction, self.rotation);
+        let sin_theta = (PI * v).sin();
+        let environment = if sin_theta > 0.0 {
+            self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+        } else {
+            0.0
+        };
+
+        match &self.sun {
+            None => environment,
+            Some(sun) => {
+                let cos_max = sun_cos_max();
+                let sun_value = if Vector::dot(&direction.unit_vector(), sun) >= cos_max {
+                    1.0 / (2.0 * PI * (1.0 - cos_max))
+                } else {
+                    0.0
+                };
+                (1.0 - SUN_SAMPLING_RATIO) * environment + SUN_SAMPLING_RATIO * sun_value
+            }
+        }
+    }
+
+    pub fn generate(&self) -> Vector {
+        if let Some(sun) = &self.sun {
+            if uniform::<f64>() < SUN_SAMPLING_RATIO {
+                let cos_max = sun_cos_max();
+                let onb = Onb::build_from_w(sun);
+                return onb.local_from_vec(&random_to_sphere(1.0 - cos_max * cos_max));
+            }
+        }
+
+        let ((u, v), _) = self.distribution.sample(uniform(), uniform());
+        uv_to_direction(u, v, self.rotation)
+    }
+}
+
+/// Maps a direction to coordinates in the unit square, with u around the y axis and v from the
+/// top of the image
+fn direction_to_uv(direction: &Vector, rotation: f64) -> (f64, f64) {
+    let direction = direction.unit_vector();
+    let theta = float::min(1.0, float::max(-1.0, direction.y())).acos();
+    let phi = direction.x().atan2(direction.z()) - rotation.to_radians();
+
+    ((phi / (2.0 * PI) + 0.5).rem_euclid(1.0), theta / PI)
+}
+
+fn uv_to_direction(u: f64, v: f64, rotation: f64) -> Vector {
+    let theta = PI * v;
+    let phi = 2.0 * PI * (u - 0.5) + rotation.to_radians();
+
+    Vector::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        theta.sin() * phi.cos(),
+    )
+}
+
+fn uv_to_pixel(image: &Image, u: f64, v: f64) -> (u32, u32) {
+    let col = u32::min((u * f64::from(image.width())) as u32, image.width() - 1);
+    let row_from_top = u32::min((v * f64::from(image.height())) as u32, image.height() - 1);
+
+    (image.height() - row_from_top - 1, col)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::image::Pixel;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_direction_roundtrip() {
+        let direction = Vector::new(0.3, -0.5, -0.8).unit_vector();
+        let (u, v) = direction_to_uv(&direction, 30.0);
+        let roundtrip = uv_to_direction(u, v, 30.0);
+
+        assert_approx_eq!(roundtrip.x(), direction.x());
+        assert_approx_eq!(roundtrip.y(), direction.y());
+        assert_approx_eq!(roundtrip.z(), direction.z());
+    }
+
+    #[test]
+    fn test_environment_sampler_favours_bright_pixels() {
+        // A dark 4x2 image with a single bright pixel in the top row
+        let pixels: Vec<Pixel> = iproduct!(0..2, 0..4)
+            .map(|(row, col)| {
+                let value = if row == 1 && col == 2 { 100.0 } else { 0.01 };
+                Pixel::new(row, col, Colour::new(value, value, value))
+            })
+            .collect();
+        let image = Image::from(&pixels);
+        let sampler = EnvironmentSampler::new(&image, 0.0);
+
+        let bright = (0..100)
+            .map(|_| sampler.generate())
+            .filter(|direction| {
+                let (u, v) = direction_to_uv(direction, 0.0);
+                uv_to_pixel(&image, u, v) == (1, 2)
+            })
+            .count();
+        assert!(bright > 90);
+
+        // The density integrates to one over the sphere
+        let steps = 200;
+        let mut total = 0.0;
+        for i in 0..steps {
+            for j in 0..steps {
+                let u = (f64::from(i) + 0.5) / f64::from(steps);
+                let v = (f64::from(j) + 0.5) / f64::from(steps);
+                let solid_angle = 2.0 * PI * PI * (PI * v).sin() / f64::from(steps * steps);
+                total += sampler.value(&uv_to_direction(u, v, 0.0)) * solid_angle;
+            }
+        }
+        assert_approx_eq!(total, 1.0, 1e-3);
+    }
+
+    #[test]
+    fn test_sky_sampler_samples_the_sun() {
+        let sampler = EnvironmentSampler::for_sky(&Sky::new(45.0, 90.0, 3.0));
+        let sun = sampler.sun.unwrap();
+
+        let towards_sun = (0..200)
+            .map(|_| sampler.generate())
+            .filter(|direction| Vector::dot(&direction.unit_vector(), &sun) >= sun_cos_max())
+            .count();
+        assert!(towards_sun > 60 && towards_sun < 140);
+        assert!(sampler.value(&sun) > 1000.0);
+
+        // Below the horizon the sun is not sampled at all
+        let sampler = EnvironmentSampler::for_sky(&Sky::new(-10.0, 90.0, 3.0));
+        assert!(sampler.sun.is_none());
+    }
+
+    #[test]
+    fn test_sky_is_read_from_its_parameters() {
+        let yaml = "
+Sky:
+  sun_elevation: 30.0
+  sun_azimuth: 90.0
+  turbidity: 3.0
+  intensity: 0.5
+";
+        let background: Background = serde_yaml::from_str(yaml).unwrap();
+        let assets = Assets::new(&[]).unwrap();
+        let direction = Vector::new(0.3, 0.5, -0.8);
+        let expected = 0.5 * Sky::new(30.0, 90.0, 3.0).value(&direction);
+        let value = background.value(&direction, &assets);
+        assert_approx_eq!(value.r(), expected.r());
+        assert_approx_eq!(value.b(), expected.b());
+
+        let serialised = serde_yaml::to_string(&background).unwrap();
+        assert!(serialised.contains("sun_elevation: 30.0"));
+        assert!(serialised.contains("intensity: 0.5"));
     }
 }
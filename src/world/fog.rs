@@ -0,0 +1,262 @@
+//! Fog filling the whole scene, which rays can scatter in anywhere along the way, including on
+//! their way out to the background. Its density only depends on the height, so distances through
+//! it are sampled exactly rather than by tracking
+
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::pdf::uniform;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::phase::PhaseFunction;
+use crate::world::materials::Material;
+use crate::world::texture::Texture;
+use thiserror::Error;
+
+// Below this rate of change of the density along a ray it is taken as constant along it
+const MIN_FALLOFF: f64 = 1e-12;
+
+#[derive(Debug, Error)]
+pub enum FogError {
+    #[error("fog density <{0}> must not be negative")]
+    InvalidDensity(f64),
+    #[error("fog falloff <{0}> must not be negative")]
+    InvalidFalloff(f64),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FogDensity {
+    // Same density everywhere. The fog never ends, so it hides the background and lights at
+    // infinity completely
+    Homogeneous {
+        density: f64,
+    },
+    // Density at the base height, falling off exponentially above it and growing below it,
+    // e.g. mist lying in a valley or haze thinning out towards the sky
+    HeightExponential {
+        density: f64,
+        base_height: f64,
+        falloff: f64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fog {
+    density: FogDensity,
+    albedo: Colour,
+    #[serde(default)]
+    phase: PhaseFunction,
+}
+
+impl Fog {
+    pub fn new(density: FogDensity, albedo: Colour, phase: PhaseFunction) -> Fog {
+        Fog {
+            density,
+            albedo,
+            phase,
+        }
+    }
+
+    /// Density at the given distance along the ray times the length of its direction, and how
+    /// quickly that falls off with the distance along it
+    fn density_along(&self, ray: &Ray, distance: f64) -> (f64, f64) {
+        let length = ray.direction().len();
+        match self.density {
+            FogDensity::Homogeneous { density } => (density * length, 0.0),
+            FogDensity::HeightExponential {
+                density,
+                base_height,
+                falloff,
+            } => {
+                let height = ray.point(distance).y();
+                (
+                    density * length * (-falloff * (height - base_height)).exp(),
+                    falloff * ray.direction().y(),
+                )
+            }
+        }
+    }
+
+    /// Optical depth of the fog along the ray between the given distances
+    fn optical_depth(&self, ray: &Ray, tmin: f64, tmax: f64) -> f64 {
+        let (density, falloff) = self.density_along(ray, tmin);
+        if density <= 0.0 || tmax <= tmin {
+            return 0.0;
+        }
+        if falloff.abs() < MIN_FALLOFF {
+            return density * (tmax - tmin);
+        }
+        density * (1.0 - (-falloff * (tmax - tmin)).exp()) / falloff
+    }
+
+    /// Distance along the ray at which it scatters in the fog, inverting the optical depth, or
+    /// `None` if it gets past the given distances first
+    fn sample_distance(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<f64> {
+        let (density, falloff) = self.density_along(ray, tmin);
+        if density <= 0.0 {
+            return None;
+        }
+
+        let optical_depth = -uniform::<f64>().ln();
+        let distance = if falloff.abs() < MIN_FALLOFF {
+            tmin + optical_depth / density
+        } else {
+            // Rays climbing out of height fog only ever see so much of it
+            let remaining = 1.0 - optical_depth * falloff / density;
+            if remaining <= 0.0 {
+                return None;
+            }
+            tmin - remaining.ln() / falloff
+        };
+
+        if distance < tmax {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+
+    pub fn transmittance(&self, ray: &Ray, tmin: f64, tmax: f64) -> f64 {
+        (-self.optical_depth(ray, tmin, tmax)).exp()
+    }
+
+    fn hit(&self, ray: &Ray, distance: f64) -> HitResult {
+        HitResult {
+            distance,
+            ray: *ray,
+            point: ray.point(distance),
+            surface_normal: Vector::new(1.0, 0.0, 0.0), // Arbitrary
+            shading_normal: Vector::new(1.0, 0.0, 0.0),
+            material: Material::Isotropic {
+                albedo: Texture::Constant {
+                    colour: self.albedo,
+                },
+                phase: self.phase,
+            },
+            texture_coords: (0.0, 0.0),
+            tangent: Vector::new(0.0, 1.0, 0.0),
+            bitangent: Vector::new(0.0, 0.0, 1.0),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let (density, falloff) = match self.density {
+            FogDensity::Homogeneous { density } => (density, 0.0),
+            FogDensity::HeightExponential {
+                density, falloff, ..
+            } => (density, falloff),
+        };
+        if density < 0.0 {
+            return Err(FogError::InvalidDensity(density).into());
+        }
+        if falloff < 0.0 {
+            return Err(FogError::InvalidFalloff(falloff).into());
+        }
+        self.phase.validate()?;
+        Ok(())
+    }
+}
+
+/// Geometry of the whole scene along with the fog around it, if there is any. Shadow rays are
+/// attenuated by the fog rather than scattering in it, as in media
+#[derive(Debug)]
+pub struct FoggyGeometry {
+    geometry: Geometry,
+    fog: Option<Fog>,
+}
+
+impl FoggyGeometry {
+    pub fn new(geometry: Geometry, fog: Option<Fog>) -> FoggyGeometry {
+        FoggyGeometry { geometry, fog }
+    }
+}
+
+impl Hittable for FoggyGeometry {
+    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
+        let hit = self.geometry.hit(ray, tmin, tmax);
+        let fog = match &self.fog {
+            Some(fog) if !ray.is_shadow() => fog,
+            _ => return hit,
+        };
+
+        let distance = hit.as_ref().map_or(tmax, |hit| hit.distance);
+        match fog.sample_distance(ray, tmin, distance) {
+            Some(distance) => Some(fog.hit(ray, distance)),
+            None => hit,
+        }
+    }
+
+    fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        self.geometry.bounding_box(time_start, time_end)
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        if let Some(fog) = &self.fog {
+            fog.validate()?;
+        }
+        self.geometry.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        false
+    }
+
+    fn transmittance(&self, ray: &Ray, tmin: f64, tmax: f64) -> Colour {
+        let transmittance = self.geometry.transmittance(ray, tmin, tmax);
+        match &self.fog {
+            Some(fog) => fog.transmittance(ray, tmin, tmax) * transmittance,
+            None => transmittance,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn height_fog() -> Fog {
+        Fog::new(
+            FogDensity::HeightExponential {
+                density: 0.5,
+                base_height: 1.0,
+                falloff: 2.0,
+            },
+            Colour::new(1.0, 1.0, 1.0),
+            PhaseFunction::Isotropic,
+        )
+    }
+
+    #[test]
+    fn test_height_fog_optical_depth() {
+        let fog = height_fog();
+        // Straight up from the base height, the density integrates to density / falloff
+        let up = Ray::new(Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, 2.0, 0.0), 0.0);
+        assert_approx_eq!(fog.optical_depth(&up, 0.0, f64::MAX), 0.25);
+        assert_approx_eq!(fog.transmittance(&up, 0.0, f64::MAX), (-0.25_f64).exp());
+
+        // Level with the base height the density stays the same
+        let level = Ray::new(Vector::new(0.0, 1.0, 0.0), Vector::new(3.0, 0.0, 4.0), 0.0);
+        assert_approx_eq!(fog.optical_depth(&level, 1.0, 3.0), 5.0);
+        assert_approx_eq!(fog.transmittance(&level, 0.0, f64::MAX), 0.0);
+    }
+
+    #[test]
+    fn test_height_fog_distances_follow_transmittance() {
+        let fog = height_fog();
+        let ray = Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 1.0, 0.0), 0.0);
+
+        let num_of_samples = 20_000;
+        for &distance in [0.5, 1.0, 2.0].iter() {
+            let got_past = (0..num_of_samples)
+                .filter(|_| fog.sample_distance(&ray, 0.0, distance).is_none())
+                .count();
+            assert_approx_eq!(
+                got_past as f64 / f64::from(num_of_samples),
+                fog.transmittance(&ray, 0.0, distance),
+                0.015
+            );
+        }
+    }
+}
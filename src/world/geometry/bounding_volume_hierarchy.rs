@@ -1,5 +1,6 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
+use crate::data::colour::Colour;
 use crate::pdf::uniform;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::{Geometry, HitResult, Hittable};
@@ -195,4 +196,21 @@ impl Hittable for BoundingVolumeHierarchyNode {
         // We should not be using bounding boxes when importance sampling attractors
         false
     }
+
+    fn transmittance(&self, ray: &Ray, tmin: f64, tmax: f64) -> Colour {
+        if !self.bounding_box.intersection(ray, tmin, tmax) {
+            return Colour::new(1.0, 1.0, 1.0);
+        }
+        self.left
+            .iter()
+            .chain(self.right.iter())
+            .map(|geometry| geometry.transmittance(ray, tmin, tmax))
+            .product()
+    }
+
+    fn load_assets(&mut self, assets: &Assets) {
+        for geometry in self.left.iter_mut().chain(self.right.iter_mut()) {
+            geometry.load_assets(assets);
+        }
+    }
 }
@@ -1,6 +1,7 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
 use crate::data::vector::Vector;
+use crate::pdf::uniform;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::rectangle::{XyRect, XzRect, YzRect};
 use crate::world::geometry::{Geometry, HitResult, Hittable};
@@ -71,6 +72,31 @@ impl Cube {
     }
 }
 
+impl Cube {
+    /// Areas of the faces in the same order as the rectangles
+    fn face_areas(&self) -> [f64; 6] {
+        let size = self.pmax - self.pmin;
+        let xy = size.x() * size.y();
+        let xz = size.x() * size.z();
+        let yz = size.y() * size.z();
+        [xy, xy, xz, xz, yz, yz]
+    }
+
+    fn random_face(&self) -> &Geometry {
+        let face_areas = self.face_areas();
+        let total_area: f64 = face_areas.iter().sum();
+
+        let mut choice = uniform::<f64>() * total_area;
+        for (rectangle, area) in self.rectangles.iter().zip(face_areas.iter()) {
+            if choice < *area {
+                return rectangle;
+            }
+            choice -= area;
+        }
+        self.rectangles.last().unwrap()
+    }
+}
+
 impl Hittable for Cube {
     fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
         self.rectangles
@@ -91,7 +117,40 @@ impl Hittable for Cube {
     }
 
     fn is_attractor(&self) -> bool {
-        false
+        self.rectangles
+            .iter()
+            .any(|rectangle| rectangle.is_attractor())
+    }
+
+    // Faces are picked in proportion to their area, which gives a point uniformly distributed over
+    // the surface of the cube
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
+        let total_area: f64 = self.face_areas().iter().sum();
+        self.rectangles
+            .iter()
+            .zip(self.face_areas().iter())
+            .map(|(rectangle, area)| area / total_area * rectangle.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: &Vector) -> Vector {
+        self.random_face().random(origin)
+    }
+
+    fn area(&self) -> f64 {
+        self.face_areas().iter().sum()
+    }
+
+    fn random_point(&self, time: f64) -> (Vector, Vector) {
+        self.random_face().random_point(time)
+    }
+
+    fn power(&self, assets: &Assets) -> f64 {
+        self.rectangles
+            .iter()
+            .map(|rectangle| rectangle.power(assets))
+            .sum()
     }
 }
 
@@ -107,6 +166,8 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         );
 
@@ -130,6 +191,8 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         );
 
@@ -145,6 +208,8 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         );
 
@@ -161,6 +226,8 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         );
 
@@ -170,4 +237,28 @@ mod tests {
         assert_approx_eq!(u, 0.5);
         assert_approx_eq!(v, 0.5);
     }
+
+    #[test]
+    fn test_cube_pdf_value() {
+        let cube = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 1.0),
+            Material::Dielectric {
+                refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
+            },
+        );
+
+        // The ray passes through the near face 4 away and the far face 5 away, each of which is
+        // picked a sixth of the time
+        let origin = Vector::new(0.5, 0.5, 5.0);
+        let pdf = cube.pdf_value(&origin, &Vector::new(0.0, 0.0, -1.0));
+        assert_approx_eq!(pdf, (16.0 + 25.0) / 6.0);
+
+        for _ in 0..20 {
+            let direction = cube.random(&origin);
+            assert!(cube.pdf_value(&origin, &direction) > 0.0);
+        }
+    }
 }
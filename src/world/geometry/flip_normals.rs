@@ -1,5 +1,6 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
+use crate::data::colour::Colour;
 use crate::data::vector::Vector;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::{Geometry, HitResult, Hittable};
@@ -21,6 +22,7 @@ impl Hittable for FlipNormals {
     fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
         self.geometry.hit(ray, tmin, tmax).map(|hit| HitResult {
             surface_normal: -hit.surface_normal,
+            shading_normal: -hit.shading_normal,
             ..hit
         })
     }
@@ -44,4 +46,25 @@ impl Hittable for FlipNormals {
     fn random(&self, origin: &Vector) -> Vector {
         self.geometry.random(origin)
     }
+
+    fn area(&self) -> f64 {
+        self.geometry.area()
+    }
+
+    fn random_point(&self, time: f64) -> (Vector, Vector) {
+        let (point, normal) = self.geometry.random_point(time);
+        (point, -normal)
+    }
+
+    fn power(&self, assets: &Assets) -> f64 {
+        self.geometry.power(assets)
+    }
+
+    fn transmittance(&self, ray: &Ray, tmin: f64, tmax: f64) -> Colour {
+        self.geometry.transmittance(ray, tmin, tmax)
+    }
+
+    fn load_assets(&mut self, assets: &Assets) {
+        self.geometry.load_assets(assets)
+    }
 }
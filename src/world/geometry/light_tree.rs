@@ -0,0 +1,313 @@
+//! Hierarchy over the lights, i.e. emitting attractors, of a scene used to pick which one to
+//! sample from a point. Lights are picked in proportion to their power over their squared
+//! distance, so that scenes with many lights mostly sample the bright and nearby ones, and both
+//! picking a light and working out the probability of a direction take logarithmic time
+//!
+//! Orientation is not taken into account as lights can be two sided
+
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float;
+use crate::pdf::uniform;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, Hittable};
+use std::cmp::Ordering;
+
+#[derive(Debug)]
+pub struct LightTree {
+    root: LightNode,
+}
+
+#[derive(Debug)]
+enum LightNode {
+    Leaf {
+        geometry: Geometry,
+        bounding_box: AxisAlignedBoundingBox,
+        power: f64,
+    },
+    Node {
+        left: Box<LightNode>,
+        right: Box<LightNode>,
+        bounding_box: AxisAlignedBoundingBox,
+        power: f64,
+    },
+}
+
+impl LightTree {
+    pub fn build(
+        lights: Vec<Geometry>,
+        time_start: f64,
+        time_end: f64,
+        assets: &Assets,
+    ) -> Option<LightTree> {
+        if lights.is_empty() {
+            return None;
+        }
+
+        let leaves: Vec<LightNode> = lights
+            .into_iter()
+            .map(|geometry| {
+                let bounding_box = match geometry.bounding_box(time_start, time_end) {
+                    Some(bounding_box) => bounding_box,
+                    None => panic!("Geometries with no bounding boxes are not supported"),
+                };
+                LightNode::Leaf {
+                    power: geometry.power(assets),
+                    geometry,
+                    bounding_box,
+                }
+            })
+            .collect();
+
+        Some(LightTree {
+            root: LightNode::build(leaves),
+        })
+    }
+
+    pub fn generate(&self, origin: &Vector) -> Vector {
+        self.root.generate(origin)
+    }
+
+    pub fn value(&self, origin: &Vector, direction: &Vector) -> f64 {
+        self.root
+            .value_along(&Ray::new(*origin, *direction, 0.0), 1.0)
+    }
+
+    /// Total power of all the lights
+    pub fn power(&self) -> f64 {
+        self.root.power()
+    }
+
+    /// Picks a light in proportion to its power, regardless of where it is seen from, along with
+    /// the probability of picking it
+    pub fn sample_by_power(&self) -> (&Geometry, f64) {
+        self.root.sample_by_power(1.0)
+    }
+
+    /// Density with respect to area of picking a light by its power and then a point uniformly on
+    /// it, for the point on a light that the ray hits between the given distances
+    pub fn area_pdf_by_power(&self, ray: &Ray, tmin: f64, tmax: f64) -> f64 {
+        let power = self.power();
+        if power <= 0.0 {
+            return 0.0;
+        }
+        self.root.power_per_area_along(ray, tmin, tmax) / power
+    }
+}
+
+impl LightNode {
+    fn build(mut leaves: Vec<LightNode>) -> LightNode {
+        if leaves.len() == 1 {
+            return leaves.remove(0);
+        }
+
+        // Split in half along the axis the attractors are most spread out on
+        let centres: Vec<Vector> = leaves
+            .iter()
+            .map(|leaf| centre(leaf.bounding_box()))
+            .collect();
+        let extent = |axis: fn(&Vector) -> f64| {
+            let values = centres.iter().map(axis);
+            values.clone().fold(f64::MIN, float::max) - values.fold(f64::MAX, float::min)
+        };
+        let axes: [fn(&Vector) -> f64; 3] = [Vector::x, Vector::y, Vector::z];
+        let axis = *axes
+            .iter()
+            .max_by(|a, b| extent(**a).partial_cmp(&extent(**b)).unwrap())
+            .unwrap();
+
+        leaves.sort_by(|left, right| {
+            axis(&centre(left.bounding_box()))
+                .partial_cmp(&axis(&centre(right.bounding_box())))
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let right_leaves = leaves.split_off(leaves.len() / 2);
+        let left = LightNode::build(leaves);
+        let right = LightNode::build(right_leaves);
+
+        let bounding_box = AxisAlignedBoundingBox::surrounding(
+            &Some(left.bounding_box().clone()),
+            &Some(right.bounding_box().clone()),
+        )
+        .unwrap();
+
+        LightNode::Node {
+            power: left.power() + right.power(),
+            left: Box::from(left),
+            right: Box::from(right),
+            bounding_box,
+        }
+    }
+
+    fn bounding_box(&self) -> &AxisAlignedBoundingBox {
+        match self {
+            LightNode::Leaf { bounding_box, .. } => bounding_box,
+            LightNode::Node { bounding_box, .. } => bounding_box,
+        }
+    }
+
+    fn power(&self) -> f64 {
+        match self {
+            LightNode::Leaf { power, .. } => *power,
+            LightNode::Node { power, .. } => *power,
+        }
+    }
+
+    /// Estimate of the light reaching the origin from everything below this node
+    fn importance(&self, origin: &Vector) -> f64 {
+        let bounding_box = self.bounding_box();
+        let distance_squared = (centre(bounding_box) - origin).len_squared();
+        // Stops nodes that contain the origin from getting an infinite importance
+        let radius_squared = (bounding_box.max() - bounding_box.min()).len_squared() / 4.0;
+
+        self.power() / float::max(distance_squared, radius_squared)
+    }
+
+    /// Probability of picking the left child of a node from the origin
+    fn left_probability(left: &LightNode, right: &LightNode, origin: &Vector) -> f64 {
+        let left_importance = left.importance(origin);
+        let right_importance = right.importance(origin);
+        let total = left_importance + right_importance;
+
+        if total > 0.0 {
+            left_importance / total
+        } else {
+            0.5
+        }
+    }
+
+    fn generate(&self, origin: &Vector) -> Vector {
+        match self {
+            LightNode::Leaf { geometry, .. } => geometry.random(origin),
+            LightNode::Node { left, right, .. } => {
+                if uniform::<f64>() < LightNode::left_probability(left, right, origin) {
+                    left.generate(origin)
+                } else {
+                    right.generate(origin)
+                }
+            }
+        }
+    }
+
+    fn sample_by_power(&self, probability: f64) -> (&Geometry, f64) {
+        match self {
+            LightNode::Leaf { geometry, .. } => (geometry, probability),
+            LightNode::Node { left, right, .. } => {
+                let left_probability = if self.power() > 0.0 {
+                    left.power() / self.power()
+                } else {
+                    0.5
+                };
+                if uniform::<f64>() < left_probability {
+                    left.sample_by_power(probability * left_probability)
+                } else {
+                    right.sample_by_power(probability * (1.0 - left_probability))
+                }
+            }
+        }
+    }
+
+    fn power_per_area_along(&self, ray: &Ray, tmin: f64, tmax: f64) -> f64 {
+        if !self.bounding_box().intersection(ray, tmin, tmax) {
+            return 0.0;
+        }
+
+        match self {
+            LightNode::Leaf {
+                geometry, power, ..
+            } => match geometry.hit(ray, tmin, tmax) {
+                Some(_) => power / geometry.area(),
+                None => 0.0,
+            },
+            LightNode::Node { left, right, .. } => {
+                left.power_per_area_along(ray, tmin, tmax)
+                    + right.power_per_area_along(ray, tmin, tmax)
+            }
+        }
+    }
+
+    fn value_along(&self, ray: &Ray, probability: f64) -> f64 {
+        // Only attractors the ray can reach have a non zero pdf, so skip all other branches
+        if probability <= 0.0 || !self.bounding_box().intersection(ray, 0.001, f64::MAX) {
+            return 0.0;
+        }
+
+        match self {
+            LightNode::Leaf { geometry, .. } => {
+                probability * geometry.pdf_value(ray.origin(), ray.direction())
+            }
+            LightNode::Node { left, right, .. } => {
+                let left_probability = LightNode::left_probability(left, right, ray.origin());
+                left.value_along(ray, probability * left_probability)
+                    + right.value_along(ray, probability * (1.0 - left_probability))
+            }
+        }
+    }
+}
+
+fn centre(bounding_box: &AxisAlignedBoundingBox) -> Vector {
+    (bounding_box.min() + bounding_box.max()) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::tests::light;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_light_tree_favours_bright_lights() {
+        let assets = Assets::new(&[]).unwrap();
+        let lights = (0..16)
+            .map(|i| light(f64::from(i) * 2.0, if i == 5 { 1000.0 } else { 1.0 }))
+            .collect();
+        let tree = LightTree::build(lights, 0.0, 0.0, &assets).unwrap();
+
+        let origin = Vector::new(15.0, 0.0, 0.5);
+        let towards_bright = (0..200)
+            .map(|_| tree.generate(&origin) + origin)
+            .filter(|point| point.x() >= 10.0 && point.x() <= 11.0)
+            .count();
+        assert!(towards_bright > 150);
+    }
+
+    #[test]
+    fn test_light_tree_value_matches_leaf_probability() {
+        let assets = Assets::new(&[]).unwrap();
+        let tree =
+            LightTree::build(vec![light(0.0, 1.0), light(4.0, 3.0)], 0.0, 0.0, &assets).unwrap();
+
+        // Directly below the second light, which is 3 times brighter at the same distance
+        let origin = Vector::new(4.5, 0.0, 0.5);
+        let direction = Vector::new(0.0, 1.0, 0.0);
+        let to_first = Vector::new(-4.0, 5.0, 0.0);
+
+        let (left, right) = match &tree.root {
+            LightNode::Node { left, right, .. } => (left, right),
+            _ => panic!("Expected a node"),
+        };
+        let left_probability = LightNode::left_probability(left, right, &origin);
+
+        let expected = (1.0 - left_probability) * light(4.0, 3.0).pdf_value(&origin, &direction);
+        assert_approx_eq!(tree.value(&origin, &direction), expected);
+        let expected = left_probability * light(0.0, 1.0).pdf_value(&origin, &to_first);
+        assert_approx_eq!(tree.value(&origin, &to_first), expected);
+        assert!(left_probability < 0.25);
+    }
+
+    #[test]
+    fn test_light_tree_sample_by_power() {
+        let assets = Assets::new(&[]).unwrap();
+        let tree =
+            LightTree::build(vec![light(0.0, 1.0), light(4.0, 3.0)], 0.0, 0.0, &assets).unwrap();
+
+        // Both lights are two sided
+        assert_approx_eq!(tree.power(), 8.0);
+        for _ in 0..20 {
+            let (geometry, probability) = tree.sample_by_power();
+            assert_approx_eq!(probability, geometry.power(&assets) / tree.power());
+        }
+    }
+}
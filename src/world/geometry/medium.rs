@@ -1,12 +1,17 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
+use crate::data::colour::Colour;
+use crate::data::density_grid::DensityGrid;
 use crate::data::vector::Vector;
 use crate::float;
 use crate::pdf::uniform;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::phase::PhaseFunction;
 use crate::world::materials::Material;
+use crate::world::texture::perlin::{perlin_turbulence, NoiseConfig};
 use crate::world::texture::Texture;
+use std::sync::Arc;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ConstantMedium {
@@ -16,54 +21,284 @@ pub struct ConstantMedium {
 }
 
 impl ConstantMedium {
-    pub fn build(boundary: Geometry, density: f64, albedo: Texture) -> Geometry {
+    pub fn build(
+        boundary: Geometry,
+        density: f64,
+        albedo: Texture,
+        phase: PhaseFunction,
+    ) -> Geometry {
+        ConstantMedium::build_with_material(
+            boundary,
+            density,
+            Material::Isotropic { albedo, phase },
+        )
+    }
+
+    /// Medium made of any volumetric material, e.g. `Material::Medium` for media that absorb,
+    /// scatter and emit by different amounts in each channel
+    pub fn build_with_material(boundary: Geometry, density: f64, material: Material) -> Geometry {
         Geometry::ConstantMedium(Box::from(ConstantMedium {
             boundary: Box::from(boundary),
             density,
-            material: Material::Isotropic { albedo },
+            material,
         }))
     }
 }
 
+/// Samples the distance travelled through a medium of the given density before the next
+/// scattering event
+pub fn sample_free_flight(density: f64) -> f64 {
+    -(1.0 / density) * uniform::<f64>().ln()
+}
+
+// Distance past a hit on a boundary from which to look for the next one
+const BOUNDARY_OFFSET: f64 = 0.0001;
+
+/// Stretches of a ray between the given distances that are inside a boundary, in order along the
+/// ray, each along with the hit where it starts or where it ends if the ray starts inside. The
+/// ray goes in where it hits the outside of the boundary and out where it hits the inside, so
+/// any closed boundary works, even one that the ray crosses many times
+fn inside_boundary(
+    boundary: &Geometry,
+    ray: &Ray,
+    tmin: f64,
+    tmax: f64,
+) -> Vec<(f64, f64, HitResult)> {
+    let mut stretches = Vec::new();
+    let mut entry: Option<HitResult> = None;
+    let mut distance = tmin;
+    let mut is_first_hit = true;
+
+    while let Some(hit) = boundary.hit(ray, distance, f64::MAX) {
+        distance = hit.distance + BOUNDARY_OFFSET;
+        if hit.front_face() {
+            if hit.distance >= tmax {
+                break;
+            }
+            entry = Some(hit);
+        } else {
+            // Leaving without having come in means the ray started inside
+            let start = match entry.take() {
+                Some(entry) => Some((entry.distance, entry)),
+                None if is_first_hit => Some((tmin, hit.clone())),
+                None => None,
+            };
+            if let Some((start, start_hit)) = start {
+                let end = float::min(hit.distance, tmax);
+                if start < end {
+                    stretches.push((start, end, start_hit));
+                }
+            }
+            if hit.distance >= tmax {
+                break;
+            }
+        }
+        is_first_hit = false;
+    }
+    stretches
+}
+
 impl Hittable for ConstantMedium {
     fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
-        self.boundary
-            .hit(ray, std::f64::MIN, std::f64::MAX)
-            .and_then(|first_hit| {
-                self.boundary
-                    .hit(ray, &first_hit.distance + 0.0001, std::f64::MAX)
-                    .map(|second_hit| (first_hit, second_hit))
-            })
-            .and_then(|(first_hit, second_hit)| {
-                let mut d1 = first_hit.distance;
-                let mut d2 = second_hit.distance;
-
-                d1 = float::max(d1, tmin);
-                d2 = float::min(d2, tmax);
-
-                if d1 >= d2 {
-                    return None;
+        if ray.is_shadow() {
+            return None;
+        }
+
+        // Free flights carry on across the gaps between stretches, as no distance is travelled
+        // through the medium there
+        let mut hit_distance = sample_free_flight(self.density * self.material.majorant());
+        for (d1, d2, first_hit) in inside_boundary(&self.boundary, ray, tmin, tmax) {
+            let distance_inside_boundary = (d2 - d1) * ray.direction().len();
+            if hit_distance >= distance_inside_boundary {
+                hit_distance -= distance_inside_boundary;
+                continue;
+            }
+
+            let distance = d1 + hit_distance / ray.direction().len();
+
+            return Some(HitResult {
+                distance,
+                ray: *ray,
+                point: ray.point(distance),
+                surface_normal: Vector::new(1.0, 0.0, 0.0), // Arbitrary,
+                shading_normal: Vector::new(1.0, 0.0, 0.0),
+                material: self.material.clone(),
+                ..first_hit
+            });
+        }
+        None
+    }
+
+    fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
+        self.boundary.bounding_box(time_start, time_end)
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.material.validate(assets)?;
+        self.boundary.validate(assets)?;
+        Ok(())
+    }
+
+    fn is_attractor(&self) -> bool {
+        false
+    }
+
+    fn transmittance(&self, ray: &Ray, tmin: f64, tmax: f64) -> Colour {
+        let length: f64 = inside_boundary(&self.boundary, ray, tmin, tmax)
+            .iter()
+            .map(|(d1, d2, _)| d2 - d1)
+            .sum();
+        let optical_depth = self.density * length * ray.direction().len();
+        let extinction = self.material.extinction();
+        Colour::new(
+            (-optical_depth * extinction.r()).exp(),
+            (-optical_depth * extinction.g()).exp(),
+            (-optical_depth * extinction.b()).exp(),
+        )
+    }
+}
+
+/// Where the density of a heterogeneous medium comes from, as a share of its maximum density
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum DensityField {
+    /// Density grid asset stretched over the bounding box of the boundary, e.g. a smoke plume
+    Grid {
+        asset_name: String,
+        #[serde(skip)]
+        grid: Option<Arc<DensityGrid>>,
+    },
+    /// Perlin turbulence, as used by `Texture::Noise`, e.g. for clouds. Larger scales give
+    /// smaller puffs
+    Noise {
+        scale: f64,
+        noise_config: NoiseConfig,
+    },
+}
+
+impl DensityField {
+    fn value(&self, point: &Vector, bounding_box: &AxisAlignedBoundingBox) -> f64 {
+        match self {
+            DensityField::Grid { grid, .. } => {
+                let grid = grid.as_ref().expect("density grid has not been loaded");
+                if grid.max() <= 0.0 {
+                    return 0.0;
                 }
-                d1 = float::max(d1, 0.0);
+                let extent = bounding_box.max() - bounding_box.min();
+                let offset = point - bounding_box.min();
+                let coords = Vector::new(
+                    offset.x() / extent.x(),
+                    offset.y() / extent.y(),
+                    offset.z() / extent.z(),
+                );
+                grid.value(&coords) / grid.max()
+            }
+            DensityField::Noise {
+                scale,
+                noise_config,
+            } => float::min(1.0, perlin_turbulence(noise_config, &(*scale * point), 7)),
+        }
+    }
+}
+
+/// Medium whose density changes from point to point, e.g. clouds and smoke. Distances are
+/// sampled by delta tracking against the maximum density, following Woodcock et al. (1965), and
+/// shadow rays are attenuated by ratio tracking, following Novák et al., "Residual Ratio Tracking
+/// for Estimating Attenuation in Participating Media" (2014)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HeterogeneousMedium {
+    boundary: Box<Geometry>,
+    // Density where the field is densest, which no other point in the medium goes above
+    max_density: f64,
+    density: DensityField,
+    material: Material,
+}
 
-                let distance_inside_boundary = (d2 - d1) * ray.direction().len();
-                let hit_distance = -(1.0 / self.density) * uniform::<f64>().ln();
+impl HeterogeneousMedium {
+    pub fn build(
+        boundary: Geometry,
+        max_density: f64,
+        density: DensityField,
+        albedo: Texture,
+        phase: PhaseFunction,
+    ) -> Geometry {
+        HeterogeneousMedium::build_with_material(
+            boundary,
+            max_density,
+            density,
+            Material::Isotropic { albedo, phase },
+        )
+    }
 
-                if hit_distance >= distance_inside_boundary {
-                    return None;
+    /// Like `build`, but with any volumetric material in place of an albedo
+    pub fn build_with_material(
+        boundary: Geometry,
+        max_density: f64,
+        density: DensityField,
+        material: Material,
+    ) -> Geometry {
+        Geometry::HeterogeneousMedium(Box::from(HeterogeneousMedium {
+            boundary: Box::from(boundary),
+            max_density,
+            density,
+            material,
+        }))
+    }
+
+    /// Calls the visitor at tentative collisions along the ray inside the boundary, spaced as in
+    /// a medium of the maximum density everywhere, with the share of the maximum density there
+    /// and the boundary hit of the stretch they are in, until it returns false
+    fn track<F>(&self, ray: &Ray, tmin: f64, tmax: f64, mut visitor: F)
+    where
+        F: FnMut(f64, f64, &HitResult) -> bool,
+    {
+        let bounding_box = match self.boundary.bounding_box(ray.time(), ray.time()) {
+            Some(bounding_box) => bounding_box,
+            None => return,
+        };
+        let length = ray.direction().len();
+
+        for (d1, d2, first_hit) in inside_boundary(&self.boundary, ray, tmin, tmax) {
+            let mut distance = d1;
+            loop {
+                distance +=
+                    sample_free_flight(self.max_density * self.material.majorant()) / length;
+                if distance >= d2 {
+                    break;
+                }
+                let share = self.density.value(&ray.point(distance), &bounding_box);
+                if !visitor(distance, share, &first_hit) {
+                    return;
                 }
+            }
+        }
+    }
+}
+
+impl Hittable for HeterogeneousMedium {
+    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
+        if ray.is_shadow() {
+            return None;
+        }
 
-                let distance = d1 + hit_distance / ray.direction().len();
+        // Tentative collisions are real in proportion to the density there, the rest are null
+        // collisions that let the ray carry on unchanged
+        let mut collision = None;
+        self.track(ray, tmin, tmax, |distance, share, first_hit| {
+            if uniform::<f64>() < share {
+                collision = Some((distance, first_hit.clone()));
+            }
+            collision.is_none()
+        });
 
-                Some(HitResult {
-                    distance,
-                    ray: *ray,
-                    point: ray.point(distance),
-                    surface_normal: Vector::new(1.0, 0.0, 0.0), // Arbitrary,
-                    material: self.material.clone(),
-                    ..first_hit
-                })
-            })
+        collision.map(|(distance, first_hit)| HitResult {
+            distance,
+            ray: *ray,
+            point: ray.point(distance),
+            surface_normal: Vector::new(1.0, 0.0, 0.0), // Arbitrary,
+            shading_normal: Vector::new(1.0, 0.0, 0.0),
+            material: self.material.clone(),
+            ..first_hit
+        })
     }
 
     fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
@@ -71,6 +306,9 @@ impl Hittable for ConstantMedium {
     }
 
     fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        if let DensityField::Grid { asset_name, .. } = &self.density {
+            assets.validate_density_grid(asset_name)?;
+        }
         self.material.validate(assets)?;
         self.boundary.validate(assets)?;
         Ok(())
@@ -79,4 +317,139 @@ impl Hittable for ConstantMedium {
     fn is_attractor(&self) -> bool {
         false
     }
+
+    fn transmittance(&self, ray: &Ray, tmin: f64, tmax: f64) -> Colour {
+        // Every tentative collision lets through the share of light a null collision would. The
+        // majorant is taken over the channels, so channels stopped less quickly let more through
+        let extinction = self.material.extinction() / self.material.majorant();
+        let mut transmittance = Colour::new(1.0, 1.0, 1.0);
+        self.track(ray, tmin, tmax, |_, share, _| {
+            transmittance = transmittance
+                * Colour::new(
+                    1.0 - share * extinction.r(),
+                    1.0 - share * extinction.g(),
+                    1.0 - share * extinction.b(),
+                );
+            transmittance != Colour::new(0.0, 0.0, 0.0)
+        });
+        transmittance
+    }
+
+    fn load_assets(&mut self, assets: &Assets) {
+        if let DensityField::Grid { asset_name, grid } = &mut self.density {
+            *grid = Some(assets.get_density_grid(asset_name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::geometry::bounding_volume_hierarchy::BoundingVolumeHierarchyNode;
+    use crate::world::geometry::cube::Cube;
+    use crate::world::texture::perlin::build_noise_config;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn medium(max_density: f64, density: DensityField) -> Geometry {
+        let white = Texture::Constant {
+            colour: Colour::new(1.0, 1.0, 1.0),
+        };
+        let boundary = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 1.0),
+            Material::Lambertian {
+                albedo: white.clone(),
+            },
+        );
+        HeterogeneousMedium::build(
+            boundary,
+            max_density,
+            density,
+            white,
+            PhaseFunction::Isotropic,
+        )
+    }
+
+    #[test]
+    fn test_heterogeneous_medium_transmittance() {
+        // The ray crosses the lower half of the grid, where the density is half the maximum
+        let medium = medium(
+            4.0,
+            DensityField::Grid {
+                asset_name: String::from("layers.grid"),
+                grid: Some(Arc::new(DensityGrid::parse("1 1 2 0.5 1.0").unwrap())),
+            },
+        );
+        let ray = Ray::new(
+            Vector::new(-1.0, 0.5, 0.25),
+            Vector::new(1.0, 0.0, 0.0),
+            0.0,
+        );
+
+        let num_of_samples = 20_000;
+        let mean = (0..num_of_samples)
+            .map(|_| medium.transmittance(&ray.shadow(), 0.0, f64::MAX))
+            .sum::<Colour>()
+            / f64::from(num_of_samples);
+        assert_approx_eq!(mean.r(), (-2.0_f64).exp(), 0.01);
+        assert_approx_eq!(mean.b(), (-2.0_f64).exp(), 0.01);
+
+        assert!(medium.hit(&ray.shadow(), 0.0, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn test_constant_medium_with_boundary_crossed_many_times() {
+        let white = Texture::Constant {
+            colour: Colour::new(1.0, 1.0, 1.0),
+        };
+        let cube = |pmin: Vector| {
+            Cube::build(
+                pmin,
+                pmin + Vector::new(1.0, 1.0, 1.0),
+                Material::Lambertian {
+                    albedo: white.clone(),
+                },
+            )
+        };
+        // Two boxes along x with a gap between them, the second one turned about its centre
+        let boundary = BoundingVolumeHierarchyNode::build(
+            vec![
+                cube(Vector::new(0.0, 0.0, 0.0)),
+                cube(Vector::new(-0.5, 0.0, -0.5))
+                    .rotate_y(90.0)
+                    .unwrap()
+                    .translate(Vector::new(3.5, 0.0, 0.5)),
+            ],
+            0.0,
+            1.0,
+        );
+        let medium = ConstantMedium::build(boundary, 1.0, white, PhaseFunction::Isotropic);
+
+        let from =
+            |x: f64| Ray::new(Vector::new(x, 0.5, 0.5), Vector::new(1.0, 0.0, 0.0), 0.0).shadow();
+        assert_approx_eq!(
+            medium.transmittance(&from(-1.0), 0.001, f64::MAX).r(),
+            (-2.0_f64).exp()
+        );
+        // Starting inside the first box, and stopping inside the second
+        assert_approx_eq!(
+            medium.transmittance(&from(0.5), 0.0, 3.0).r(),
+            (-1.0_f64).exp()
+        );
+    }
+
+    #[test]
+    fn test_heterogeneous_medium_noise_density_is_bounded() {
+        let field = DensityField::Noise {
+            scale: 4.0,
+            noise_config: build_noise_config(),
+        };
+        let bounding_box =
+            AxisAlignedBoundingBox::new(Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 1.0, 1.0));
+        for i in 0..100 {
+            let point = Vector::new(f64::from(i) / 100.0, 0.3, 0.7);
+            let share = field.value(&point, &bounding_box);
+            assert!((0.0..=1.0).contains(&share));
+        }
+    }
 }
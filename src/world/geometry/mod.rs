@@ -2,6 +2,7 @@ pub mod axis_aligned_bounding_box;
 pub mod bounding_volume_hierarchy;
 pub mod cube;
 pub mod flip_normals;
+pub mod light_tree;
 pub mod medium;
 pub mod rectangle;
 pub mod rotate;
@@ -10,12 +11,13 @@ pub mod translate;
 
 use crate::camera::Ray;
 use crate::data::assets::Assets;
+use crate::data::colour::Colour;
 use crate::data::vector::Vector;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::bounding_volume_hierarchy::BoundingVolumeHierarchyNode;
 use crate::world::geometry::cube::Cube;
 use crate::world::geometry::flip_normals::FlipNormals;
-use crate::world::geometry::medium::ConstantMedium;
+use crate::world::geometry::medium::{ConstantMedium, HeterogeneousMedium};
 use crate::world::geometry::rectangle::{XyRect, XzRect, YzRect};
 use crate::world::geometry::rotate::RotateY;
 use crate::world::geometry::sphere::{MovingSphere, Sphere};
@@ -32,6 +34,7 @@ pub enum Geometry {
     MovingSphere(Box<MovingSphere>),
     Cube(Box<Cube>),
     ConstantMedium(Box<ConstantMedium>),
+    HeterogeneousMedium(Box<HeterogeneousMedium>),
     XyRect(Box<XyRect>),
     XzRect(Box<XzRect>),
     YzRect(Box<YzRect>),
@@ -48,6 +51,7 @@ impl Hittable for Geometry {
             Geometry::MovingSphere(inner) => inner.hit(ray, tmin, tmax),
             Geometry::Cube(inner) => inner.hit(ray, tmin, tmax),
             Geometry::ConstantMedium(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::HeterogeneousMedium(inner) => inner.hit(ray, tmin, tmax),
             Geometry::XyRect(inner) => inner.hit(ray, tmin, tmax),
             Geometry::XzRect(inner) => inner.hit(ray, tmin, tmax),
             Geometry::YzRect(inner) => inner.hit(ray, tmin, tmax),
@@ -64,6 +68,7 @@ impl Hittable for Geometry {
             Geometry::MovingSphere(inner) => inner.bounding_box(time_start, time_end),
             Geometry::Cube(inner) => inner.bounding_box(time_start, time_end),
             Geometry::ConstantMedium(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::HeterogeneousMedium(inner) => inner.bounding_box(time_start, time_end),
             Geometry::XyRect(inner) => inner.bounding_box(time_start, time_end),
             Geometry::XzRect(inner) => inner.bounding_box(time_start, time_end),
             Geometry::YzRect(inner) => inner.bounding_box(time_start, time_end),
@@ -80,6 +85,7 @@ impl Hittable for Geometry {
             Geometry::MovingSphere(inner) => inner.validate(assets),
             Geometry::Cube(inner) => inner.validate(assets),
             Geometry::ConstantMedium(inner) => inner.validate(assets),
+            Geometry::HeterogeneousMedium(inner) => inner.validate(assets),
             Geometry::XyRect(inner) => inner.validate(assets),
             Geometry::XzRect(inner) => inner.validate(assets),
             Geometry::YzRect(inner) => inner.validate(assets),
@@ -96,6 +102,7 @@ impl Hittable for Geometry {
             Geometry::MovingSphere(inner) => inner.is_attractor(),
             Geometry::Cube(inner) => inner.is_attractor(),
             Geometry::ConstantMedium(inner) => inner.is_attractor(),
+            Geometry::HeterogeneousMedium(inner) => inner.is_attractor(),
             Geometry::XyRect(inner) => inner.is_attractor(),
             Geometry::XzRect(inner) => inner.is_attractor(),
             Geometry::YzRect(inner) => inner.is_attractor(),
@@ -112,6 +119,7 @@ impl Hittable for Geometry {
             Geometry::MovingSphere(inner) => inner.pdf_value(origin, direction),
             Geometry::Cube(inner) => inner.pdf_value(origin, direction),
             Geometry::ConstantMedium(inner) => inner.pdf_value(origin, direction),
+            Geometry::HeterogeneousMedium(inner) => inner.pdf_value(origin, direction),
             Geometry::XyRect(inner) => inner.pdf_value(origin, direction),
             Geometry::XzRect(inner) => inner.pdf_value(origin, direction),
             Geometry::YzRect(inner) => inner.pdf_value(origin, direction),
@@ -128,6 +136,7 @@ impl Hittable for Geometry {
             Geometry::MovingSphere(inner) => inner.random(origin),
             Geometry::Cube(inner) => inner.random(origin),
             Geometry::ConstantMedium(inner) => inner.random(origin),
+            Geometry::HeterogeneousMedium(inner) => inner.random(origin),
             Geometry::XyRect(inner) => inner.random(origin),
             Geometry::XzRect(inner) => inner.random(origin),
             Geometry::YzRect(inner) => inner.random(origin),
@@ -136,6 +145,91 @@ impl Hittable for Geometry {
             Geometry::RotateY(inner) => inner.random(origin),
         }
     }
+
+    fn area(&self) -> f64 {
+        match self {
+            Geometry::Bvh(inner) => inner.area(),
+            Geometry::Sphere(inner) => inner.area(),
+            Geometry::MovingSphere(inner) => inner.area(),
+            Geometry::Cube(inner) => inner.area(),
+            Geometry::ConstantMedium(inner) => inner.area(),
+            Geometry::HeterogeneousMedium(inner) => inner.area(),
+            Geometry::XyRect(inner) => inner.area(),
+            Geometry::XzRect(inner) => inner.area(),
+            Geometry::YzRect(inner) => inner.area(),
+            Geometry::Flip(inner) => inner.area(),
+            Geometry::Translate(inner) => inner.area(),
+            Geometry::RotateY(inner) => inner.area(),
+        }
+    }
+
+    fn random_point(&self, time: f64) -> (Vector, Vector) {
+        match self {
+            Geometry::Bvh(inner) => inner.random_point(time),
+            Geometry::Sphere(inner) => inner.random_point(time),
+            Geometry::MovingSphere(inner) => inner.random_point(time),
+            Geometry::Cube(inner) => inner.random_point(time),
+            Geometry::ConstantMedium(inner) => inner.random_point(time),
+            Geometry::HeterogeneousMedium(inner) => inner.random_point(time),
+            Geometry::XyRect(inner) => inner.random_point(time),
+            Geometry::XzRect(inner) => inner.random_point(time),
+            Geometry::YzRect(inner) => inner.random_point(time),
+            Geometry::Flip(inner) => inner.random_point(time),
+            Geometry::Translate(inner) => inner.random_point(time),
+            Geometry::RotateY(inner) => inner.random_point(time),
+        }
+    }
+
+    fn power(&self, assets: &Assets) -> f64 {
+        match self {
+            Geometry::Bvh(inner) => inner.power(assets),
+            Geometry::Sphere(inner) => inner.power(assets),
+            Geometry::MovingSphere(inner) => inner.power(assets),
+            Geometry::Cube(inner) => inner.power(assets),
+            Geometry::ConstantMedium(inner) => inner.power(assets),
+            Geometry::HeterogeneousMedium(inner) => inner.power(assets),
+            Geometry::XyRect(inner) => inner.power(assets),
+            Geometry::XzRect(inner) => inner.power(assets),
+            Geometry::YzRect(inner) => inner.power(assets),
+            Geometry::Flip(inner) => inner.power(assets),
+            Geometry::Translate(inner) => inner.power(assets),
+            Geometry::RotateY(inner) => inner.power(assets),
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, tmin: f64, tmax: f64) -> Colour {
+        match self {
+            Geometry::Bvh(inner) => inner.transmittance(ray, tmin, tmax),
+            Geometry::Sphere(inner) => inner.transmittance(ray, tmin, tmax),
+            Geometry::MovingSphere(inner) => inner.transmittance(ray, tmin, tmax),
+            Geometry::Cube(inner) => inner.transmittance(ray, tmin, tmax),
+            Geometry::ConstantMedium(inner) => inner.transmittance(ray, tmin, tmax),
+            Geometry::HeterogeneousMedium(inner) => inner.transmittance(ray, tmin, tmax),
+            Geometry::XyRect(inner) => inner.transmittance(ray, tmin, tmax),
+            Geometry::XzRect(inner) => inner.transmittance(ray, tmin, tmax),
+            Geometry::YzRect(inner) => inner.transmittance(ray, tmin, tmax),
+            Geometry::Flip(inner) => inner.transmittance(ray, tmin, tmax),
+            Geometry::Translate(inner) => inner.transmittance(ray, tmin, tmax),
+            Geometry::RotateY(inner) => inner.transmittance(ray, tmin, tmax),
+        }
+    }
+
+    fn load_assets(&mut self, assets: &Assets) {
+        match self {
+            Geometry::Bvh(inner) => inner.load_assets(assets),
+            Geometry::Sphere(inner) => inner.load_assets(assets),
+            Geometry::MovingSphere(inner) => inner.load_assets(assets),
+            Geometry::Cube(inner) => inner.load_assets(assets),
+            Geometry::ConstantMedium(inner) => inner.load_assets(assets),
+            Geometry::HeterogeneousMedium(inner) => inner.load_assets(assets),
+            Geometry::XyRect(inner) => inner.load_assets(assets),
+            Geometry::XzRect(inner) => inner.load_assets(assets),
+            Geometry::YzRect(inner) => inner.load_assets(assets),
+            Geometry::Flip(inner) => inner.load_assets(assets),
+            Geometry::Translate(inner) => inner.load_assets(assets),
+            Geometry::RotateY(inner) => inner.load_assets(assets),
+        }
+    }
 }
 
 impl Geometry {
@@ -169,6 +263,31 @@ pub trait Hittable: Debug {
     fn random(&self, _origin: &Vector) -> Vector {
         unimplemented!("{:?} is not implemented as an attractor", self)
     }
+
+    fn area(&self) -> f64 {
+        unimplemented!("{:?} is not implemented as an attractor", self)
+    }
+
+    /// Point picked uniformly over the surface at the given time and the surface normal there,
+    /// used to start paths on lights
+    fn random_point(&self, _time: f64) -> (Vector, Vector) {
+        unimplemented!("{:?} is not implemented as an attractor", self)
+    }
+
+    /// Rough emitted power, only used to decide how often to sample each attractor
+    fn power(&self, _assets: &Assets) -> f64 {
+        unimplemented!("{:?} is not implemented as an attractor", self)
+    }
+
+    /// Share of the light in each channel travelling along a shadow ray that makes it through any
+    /// media between the given distances. Surfaces do not attenuate light, they are hit instead
+    fn transmittance(&self, _ray: &Ray, _tmin: f64, _tmax: f64) -> Colour {
+        Colour::new(1.0, 1.0, 1.0)
+    }
+
+    /// Keeps hold of the assets needed to hit the geometry, which unlike the assets of materials
+    /// cannot be looked up once the geometry has been hit
+    fn load_assets(&mut self, _assets: &Assets) {}
 }
 
 #[derive(Debug, Clone)]
@@ -177,8 +296,14 @@ pub struct HitResult {
     pub ray: Ray,
     pub point: Vector,
     pub surface_normal: Vector,
+    // Normal the material shades with, which normal and bump maps tilt away from the surface
+    // normal. Which side of the surface was hit is always found from the surface normal
+    pub shading_normal: Vector,
     pub material: Material,
     pub texture_coords: (f64, f64),
+    // Directions of increasing texture column and row respectively
+    pub tangent: Vector,
+    pub bitangent: Vector,
 }
 
 impl HitResult {
@@ -193,6 +318,22 @@ impl HitResult {
             -self.surface_normal
         }
     }
+
+    /// Shading normal turned to the side of the surface that was hit
+    pub fn shading_face_normal(&self) -> Vector {
+        if self.front_face() {
+            self.shading_normal
+        } else {
+            -self.shading_normal
+        }
+    }
+
+    pub fn resolve_material(self, assets: &Assets) -> Option<HitResult> {
+        if !self.material.needs_resolving() {
+            return Some(self);
+        }
+        self.material.clone().resolve(self, assets)
+    }
 }
 
 impl Ord for HitResult {
@@ -227,10 +368,15 @@ mod tests {
             ray: Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 0.0), 0.0),
             point: Vector::new(0.0, 0.0, 0.0),
             surface_normal: Vector::new(0.0, 0.0, 0.0),
+            shading_normal: Vector::new(0.0, 0.0, 0.0),
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
             texture_coords: (1.0, 0.5),
+            tangent: Vector::new(0.0, 0.0, 0.0),
+            bitangent: Vector::new(0.0, 0.0, 0.0),
         };
         assert_eq!(hit_result.clone(), hit_result.clone());
 
@@ -239,10 +385,15 @@ mod tests {
             ray: Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 0.0), 0.0),
             point: Vector::new(0.0, 0.0, 0.0),
             surface_normal: Vector::new(0.0, 0.0, 0.0),
+            shading_normal: Vector::new(0.0, 0.0, 0.0),
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
             texture_coords: (1.0, 0.5),
+            tangent: Vector::new(0.0, 0.0, 0.0),
+            bitangent: Vector::new(0.0, 0.0, 0.0),
         };
         assert_ne!(hit_result, other_hit_result);
     }
@@ -254,20 +405,30 @@ mod tests {
             ray: Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 0.0), 0.0),
             point: Vector::new(0.0, 0.0, 0.0),
             surface_normal: Vector::new(0.0, 0.0, 0.0),
+            shading_normal: Vector::new(0.0, 0.0, 0.0),
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
             texture_coords: (1.0, 0.5),
+            tangent: Vector::new(0.0, 0.0, 0.0),
+            bitangent: Vector::new(0.0, 0.0, 0.0),
         };
         let other_hit_result = HitResult {
             distance: 1.0,
             ray: Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 0.0), 0.0),
             point: Vector::new(0.0, 0.0, 0.0),
             surface_normal: Vector::new(0.0, 0.0, 0.0),
+            shading_normal: Vector::new(0.0, 0.0, 0.0),
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
             texture_coords: (1.0, 0.5),
+            tangent: Vector::new(0.0, 0.0, 0.0),
+            bitangent: Vector::new(0.0, 0.0, 0.0),
         };
         assert!(other_hit_result > hit_result);
         assert!(hit_result < other_hit_result);
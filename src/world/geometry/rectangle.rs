@@ -56,11 +56,14 @@ impl Hittable for XyRect {
             ray: *ray,
             point: ray.point(distance),
             surface_normal: Vector::new(0.0, 0.0, 1.0),
+            shading_normal: Vector::new(0.0, 0.0, 1.0),
             material: self.material.clone(),
             texture_coords: (
                 (x - self.x0) / (self.x1 - self.x0),
                 (y - self.y0) / (self.y1 - self.y0),
             ),
+            tangent: Vector::new(0.0, 1.0, 0.0),
+            bitangent: Vector::new(1.0, 0.0, 0.0),
         })
     }
 
@@ -102,6 +105,24 @@ impl Hittable for XyRect {
         );
         random_point - origin
     }
+
+    fn area(&self) -> f64 {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
+
+    fn random_point(&self, _time: f64) -> (Vector, Vector) {
+        let point = Vector::new(
+            uniform_between(self.x0, self.x1),
+            uniform_between(self.y0, self.y1),
+            self.k,
+        );
+        (point, Vector::new(0.0, 0.0, 1.0))
+    }
+
+    fn power(&self, assets: &Assets) -> f64 {
+        let centre = Vector::new((self.x0 + self.x1) / 2.0, (self.y0 + self.y1) / 2.0, self.k);
+        self.area() * self.material.emitted_luminance(&centre, assets)
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -154,11 +175,14 @@ impl Hittable for XzRect {
             ray: *ray,
             point: ray.point(distance),
             surface_normal: Vector::new(0.0, 1.0, 0.0),
+            shading_normal: Vector::new(0.0, 1.0, 0.0),
             material: self.material.clone(),
             texture_coords: (
                 (x - self.x0) / (self.x1 - self.x0),
                 (z - self.z0) / (self.z1 - self.z0),
             ),
+            tangent: Vector::new(0.0, 0.0, 1.0),
+            bitangent: Vector::new(1.0, 0.0, 0.0),
         })
     }
 
@@ -200,6 +224,24 @@ impl Hittable for XzRect {
         );
         random_point - origin
     }
+
+    fn area(&self) -> f64 {
+        (self.x1 - self.x0) * (self.z1 - self.z0)
+    }
+
+    fn random_point(&self, _time: f64) -> (Vector, Vector) {
+        let point = Vector::new(
+            uniform_between(self.x0, self.x1),
+            self.k,
+            uniform_between(self.z0, self.z1),
+        );
+        (point, Vector::new(0.0, 1.0, 0.0))
+    }
+
+    fn power(&self, assets: &Assets) -> f64 {
+        let centre = Vector::new((self.x0 + self.x1) / 2.0, self.k, (self.z0 + self.z1) / 2.0);
+        self.area() * self.material.emitted_luminance(&centre, assets)
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
@@ -252,11 +294,14 @@ impl Hittable for YzRect {
             ray: *ray,
             point: ray.point(distance),
             surface_normal: Vector::new(1.0, 0.0, 0.0),
+            shading_normal: Vector::new(1.0, 0.0, 0.0),
             material: self.material.clone(),
             texture_coords: (
                 (y - self.y0) / (self.y1 - self.y0),
                 (z - self.z0) / (self.z1 - self.z0),
             ),
+            tangent: Vector::new(0.0, 0.0, 1.0),
+            bitangent: Vector::new(0.0, 1.0, 0.0),
         })
     }
 
@@ -298,6 +343,24 @@ impl Hittable for YzRect {
         );
         random_point - origin
     }
+
+    fn area(&self) -> f64 {
+        (self.y1 - self.y0) * (self.z1 - self.z0)
+    }
+
+    fn random_point(&self, _time: f64) -> (Vector, Vector) {
+        let point = Vector::new(
+            self.k,
+            uniform_between(self.y0, self.y1),
+            uniform_between(self.z0, self.z1),
+        );
+        (point, Vector::new(1.0, 0.0, 0.0))
+    }
+
+    fn power(&self, assets: &Assets) -> f64 {
+        let centre = Vector::new(self.k, (self.y0 + self.y1) / 2.0, (self.z0 + self.z1) / 2.0);
+        self.area() * self.material.emitted_luminance(&centre, assets)
+    }
 }
 
 #[cfg(test)]
@@ -315,6 +378,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(0.5, 0.5, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
@@ -333,6 +398,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(0.5, 0.5, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
@@ -351,6 +418,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
 
@@ -372,6 +441,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(0.5, 0.5, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
@@ -382,6 +453,37 @@ mod tests {
         assert_approx_eq!(v, 0.5);
     }
 
+    #[test]
+    fn test_xy_rect_tangent_frame() {
+        let rect = XyRect {
+            x0: 0.0,
+            x1: 2.0,
+            y0: 0.0,
+            y1: 1.0,
+            k: 0.0,
+            material: Material::Dielectric {
+                refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
+            },
+        };
+        let ray = Ray::new(Vector::new(0.5, 0.5, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+
+        let hit_result = rect.hit(&ray, 0.0, f64::MAX).unwrap();
+        let (row, col) = hit_result.texture_coords;
+
+        // Moving along the tangent frame must move along the texture coordinates
+        let moved = ray.offset(-0.1 * hit_result.tangent);
+        let (moved_row, moved_col) = rect.hit(&moved, 0.0, f64::MAX).unwrap().texture_coords;
+        assert_approx_eq!(moved_row, row);
+        assert!(moved_col > col);
+
+        let moved = ray.offset(-0.1 * hit_result.bitangent);
+        let (moved_row, moved_col) = rect.hit(&moved, 0.0, f64::MAX).unwrap().texture_coords;
+        assert!(moved_row > row);
+        assert_approx_eq!(moved_col, col);
+    }
+
     #[test]
     fn test_xz_rect_hit() {
         let rect = XzRect {
@@ -392,6 +494,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(0.5, 1.0, 0.5), Vector::new(0.0, -1.0, 0.0), 0.0);
@@ -410,6 +514,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(0.5, 1.0, 0.5), Vector::new(0.0, -1.0, 0.0), 0.0);
@@ -428,6 +534,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
 
@@ -449,6 +557,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(0.5, 1.0, 0.5), Vector::new(0.0, -1.0, 0.0), 0.0);
@@ -469,6 +579,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(1.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
@@ -487,6 +599,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(1.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
@@ -505,6 +619,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
 
@@ -526,6 +642,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(1.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
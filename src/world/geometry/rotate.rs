@@ -1,5 +1,6 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
+use crate::data::colour::Colour;
 use crate::data::vector::Vector;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::{Geometry, HitResult, Hittable};
@@ -33,14 +34,20 @@ impl Hittable for RotateY {
     fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
         let origin = ray.origin().rotate_y(-self.angle);
         let direction = ray.direction().rotate_y(-self.angle);
-        let rotated_ray = Ray::new(origin, direction, ray.time());
+        let rotated_ray = ray.continued(origin, direction);
 
         self.geometry.hit(&rotated_ray, tmin, tmax).map(|hit| {
             let point = hit.point.rotate_y(self.angle);
             let surface_normal = hit.surface_normal.rotate_y(self.angle);
+            let tangent = hit.tangent.rotate_y(self.angle);
+            let bitangent = hit.bitangent.rotate_y(self.angle);
             HitResult {
+                ray: *ray,
                 point,
                 surface_normal,
+                shading_normal: hit.shading_normal.rotate_y(self.angle),
+                tangent,
+                bitangent,
                 ..hit
             }
         })
@@ -82,11 +89,40 @@ impl Hittable for RotateY {
     }
 
     fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
-        self.geometry.pdf_value(origin, direction)
+        self.geometry.pdf_value(
+            &origin.rotate_y(-self.angle),
+            &direction.rotate_y(-self.angle),
+        )
     }
 
     fn random(&self, origin: &Vector) -> Vector {
-        self.geometry.random(origin)
+        self.geometry
+            .random(&origin.rotate_y(-self.angle))
+            .rotate_y(self.angle)
+    }
+
+    fn area(&self) -> f64 {
+        self.geometry.area()
+    }
+
+    fn random_point(&self, time: f64) -> (Vector, Vector) {
+        let (point, normal) = self.geometry.random_point(time);
+        (point.rotate_y(self.angle), normal.rotate_y(self.angle))
+    }
+
+    fn power(&self, assets: &Assets) -> f64 {
+        self.geometry.power(assets)
+    }
+
+    fn transmittance(&self, ray: &Ray, tmin: f64, tmax: f64) -> Colour {
+        let origin = ray.origin().rotate_y(-self.angle);
+        let direction = ray.direction().rotate_y(-self.angle);
+        self.geometry
+            .transmittance(&ray.continued(origin, direction), tmin, tmax)
+    }
+
+    fn load_assets(&mut self, assets: &Assets) {
+        self.geometry.load_assets(assets)
     }
 }
 
@@ -94,6 +130,7 @@ impl Hittable for RotateY {
 mod tests {
     use super::*;
     use crate::world::geometry::cube::Cube;
+    use crate::world::geometry::rectangle::XyRect;
     use crate::world::materials::Material;
     use assert_approx_eq::assert_approx_eq;
 
@@ -104,6 +141,8 @@ mod tests {
             Vector::new(2.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         );
 
@@ -124,6 +163,8 @@ mod tests {
             Vector::new(2.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         );
 
@@ -148,6 +189,8 @@ mod tests {
             Vector::new(2.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         );
 
@@ -178,6 +221,8 @@ mod tests {
             Vector::new(2.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         );
 
@@ -194,4 +239,50 @@ mod tests {
         assert_approx_eq!(u, 0.25);
         assert_approx_eq!(v, 0.5);
     }
+
+    #[test]
+    fn test_rotate_pdf_value() {
+        let rectangle = XyRect::build(
+            (1.0, 2.0),
+            (0.0, 1.0),
+            0.0,
+            Material::Dielectric {
+                refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
+            },
+        )
+        .rotate_y(90.0)
+        .unwrap();
+
+        // Rotated onto the plane x = 0, spanning z from -2 to -1
+        let origin = Vector::new(3.0, 0.5, -1.5);
+        let pdf = rectangle.pdf_value(&origin, &Vector::new(-1.0, 0.0, 0.0));
+        assert_approx_eq!(pdf, 9.0);
+
+        for _ in 0..20 {
+            let direction = rectangle.random(&origin);
+            assert!(rectangle.pdf_value(&origin, &direction) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_rotate_hit_keeps_world_ray() {
+        let cube = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(2.0, 1.0, 1.0),
+            Material::Dielectric {
+                refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
+            },
+        )
+        .rotate_y(45.0)
+        .unwrap();
+
+        let ray = Ray::new(Vector::new(5.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
+        let hit_result = cube.hit(&ray, 0.0, f64::MAX).unwrap();
+        assert_eq!(hit_result.ray.direction(), ray.direction());
+        assert!(hit_result.front_face());
+    }
 }
@@ -2,7 +2,7 @@ use crate::camera::Ray;
 use crate::data::assets::Assets;
 use crate::data::vector::Vector;
 use crate::onb::Onb;
-use crate::pdf::uniform;
+use crate::pdf::{random_point_in_unit_sphere, uniform};
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::{Geometry, HitResult, Hittable};
 use crate::world::materials::Material;
@@ -65,6 +65,24 @@ fn sphere_texture_coords(hit_point: &Vector, centre: &Vector, radius: f64) -> (f
     (row, col)
 }
 
+fn sphere_tangent_frame(hit_point: &Vector, centre: &Vector, radius: f64) -> (Vector, Vector) {
+    // Derivatives of the point on the sphere with respect to the angles used to compute the
+    // texture coordinates, see `sphere_texture_coords`
+    let point = (hit_point - centre) / radius;
+
+    let theta = PI - f64::acos(point.y());
+    let phi = f64::atan2(point.x(), point.z());
+
+    let tangent = Vector::new(f64::cos(phi), 0.0, -f64::sin(phi));
+    let bitangent = Vector::new(
+        f64::cos(theta) * f64::sin(phi),
+        f64::sin(theta),
+        f64::cos(theta) * f64::cos(phi),
+    );
+
+    (tangent, bitangent)
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Sphere {
     centre: Vector,
@@ -95,14 +113,18 @@ impl Hittable for Sphere {
             let surface_normal = self.surface_normal(&ray, distance);
 
             let texture_coords = sphere_texture_coords(&point, &self.centre, self.radius);
+            let (tangent, bitangent) = sphere_tangent_frame(&point, &self.centre, self.radius);
 
             HitResult {
                 distance,
                 ray: *ray,
                 point,
                 surface_normal,
+                shading_normal: surface_normal,
                 material: self.material.clone(),
                 texture_coords,
+                tangent,
+                bitangent,
             }
         })
     }
@@ -120,42 +142,68 @@ impl Hittable for Sphere {
     }
 
     fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
-        // If origin is on the sphere itself or very close to it then some directions that were
-        // generated by this sphere's `random` method will result n rays that intersect the sphere
-        // in less than the `tmin` passed to `hit`. Therefore this method will return a pdf value
-        // of 0 which the calling code needs to handle
-
-        let hit = self.hit(&Ray::new(*origin, *direction, 0.0), 0.001, std::f64::MAX);
-        match hit {
-            None => 0.0,
-            Some(_hit) => {
-                let cp = self.centre - origin;
-                let distance_ratio = self.radius.powi(2) / cp.len_squared();
-                if distance_ratio > 1.0 {
-                    // This means origin is inside the sphere. Any ray will hit the sphere so the
-                    // solid angle is the area of the entire sphere
-                    return 1.0 / (4.0 * PI);
-                }
-
-                let cos_theta_max = f64::sqrt(1.0 - distance_ratio);
-                let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
-
-                1.0 / solid_angle
-            }
-        }
+        sphere_pdf_value(&self.centre, self.radius, origin, direction)
     }
 
     fn random(&self, origin: &Vector) -> Vector {
-        let cp = self.centre - origin;
-        let distance_ratio = self.radius.powi(2) / cp.len_squared();
-        if distance_ratio > 1.0 {
-            // This means origin is inside the sphere. Any ray will hit the sphere
-            return Vector::new(uniform(), uniform(), uniform());
+        sphere_random(&self.centre, self.radius, origin)
+    }
+
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius.powi(2)
+    }
+
+    fn random_point(&self, _time: f64) -> (Vector, Vector) {
+        sphere_random_point(&self.centre, self.radius)
+    }
+
+    fn power(&self, assets: &Assets) -> f64 {
+        self.area() * self.material.emitted_luminance(&self.centre, assets)
+    }
+}
+
+fn sphere_pdf_value(centre: &Vector, radius: f64, origin: &Vector, direction: &Vector) -> f64 {
+    // If origin is on the sphere itself or very close to it then some directions that were
+    // generated by `sphere_random` will result in rays that intersect the sphere in less than the
+    // `tmin` passed to `sphere_hit`. Therefore this function will return a pdf value of 0 which
+    // the calling code needs to handle
+
+    let ray = Ray::new(*origin, *direction, 0.0);
+    match sphere_hit(&ray, centre, radius, 0.001, f64::MAX) {
+        None => 0.0,
+        Some(_distance) => {
+            let cp = centre - origin;
+            let distance_ratio = radius.powi(2) / cp.len_squared();
+            if distance_ratio > 1.0 {
+                // This means origin is inside the sphere. Any ray will hit the sphere so the
+                // solid angle is the area of the entire sphere
+                return 1.0 / (4.0 * PI);
+            }
+
+            let cos_theta_max = f64::sqrt(1.0 - distance_ratio);
+            let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+
+            1.0 / solid_angle
         }
+    }
+}
 
-        let onb = Onb::build_from_w(&cp);
-        onb.local_from_vec(&random_to_sphere(distance_ratio))
+fn sphere_random(centre: &Vector, radius: f64, origin: &Vector) -> Vector {
+    let cp = centre - origin;
+    let distance_ratio = radius.powi(2) / cp.len_squared();
+    if distance_ratio > 1.0 {
+        // This means origin is inside the sphere. Any ray will hit the sphere
+        return random_point_in_unit_sphere().unit_vector();
     }
+
+    let onb = Onb::build_from_w(&cp);
+    onb.local_from_vec(&random_to_sphere(distance_ratio))
+}
+
+fn sphere_random_point(centre: &Vector, radius: f64) -> (Vector, Vector) {
+    // Same as the surface normal, which points inward for a negative radius
+    let normal = random_point_in_unit_sphere().unit_vector();
+    (centre + radius * normal, normal)
 }
 
 pub fn random_to_sphere(distance_ratio: f64) -> Vector {
@@ -206,6 +254,13 @@ impl MovingSphere {
         self.centre_start + time_fraction * (self.centre_end - self.centre_start)
     }
 
+    /// Smallest sphere containing this one between its start and end times
+    fn swept_sphere(&self) -> (Vector, f64) {
+        let centre = (self.centre_start + self.centre_end) / 2.0;
+        let radius = self.radius.abs() + (self.centre_end - self.centre_start).len() / 2.0;
+        (centre, radius)
+    }
+
     fn surface_normal(&self, ray: &Ray, distance: f64) -> Vector {
         // We divide by radius instead of taking the unit vector so that a negative
         // radius sphere will have a surface normal that points inward
@@ -222,14 +277,18 @@ impl Hittable for MovingSphere {
             let surface_normal = self.surface_normal(&ray, distance);
 
             let texture_coords = sphere_texture_coords(&point, &centre, self.radius);
+            let (tangent, bitangent) = sphere_tangent_frame(&point, &centre, self.radius);
 
             HitResult {
                 distance,
                 ray: *ray,
                 point,
                 surface_normal,
+                shading_normal: surface_normal,
                 material: self.material.clone(),
                 texture_coords,
+                tangent,
+                bitangent,
             }
         })
     }
@@ -246,7 +305,32 @@ impl Hittable for MovingSphere {
     }
 
     fn is_attractor(&self) -> bool {
-        false
+        self.material.is_attractor()
+    }
+
+    // Directions are sampled towards a sphere that contains this one throughout its movement,
+    // as neither the pdf nor the sampling knows the time of the ray
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
+        let (centre, radius) = self.swept_sphere();
+        sphere_pdf_value(&centre, radius, origin, direction)
+    }
+
+    fn random(&self, origin: &Vector) -> Vector {
+        let (centre, radius) = self.swept_sphere();
+        sphere_random(&centre, radius, origin)
+    }
+
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius.powi(2)
+    }
+
+    fn random_point(&self, time: f64) -> (Vector, Vector) {
+        sphere_random_point(&self.centre(time), self.radius)
+    }
+
+    fn power(&self, assets: &Assets) -> f64 {
+        self.area() * self.material.emitted_luminance(&self.centre_start, assets)
     }
 }
 
@@ -262,6 +346,8 @@ mod tests {
             radius: 1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(-2.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
@@ -277,6 +363,8 @@ mod tests {
             radius: 1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(-2.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
@@ -292,6 +380,8 @@ mod tests {
             radius: -1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(-2.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
@@ -307,6 +397,8 @@ mod tests {
             radius: 1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(-2.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
@@ -322,6 +414,8 @@ mod tests {
             radius: -1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
         let ray = Ray::new(Vector::new(-2.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
@@ -337,6 +431,8 @@ mod tests {
             radius: 1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
 
@@ -356,6 +452,8 @@ mod tests {
             radius: 1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         };
 
@@ -390,4 +488,77 @@ mod tests {
         assert_approx_eq!(row, 0.0);
         assert_approx_eq!(col, 0.5);
     }
+
+    #[test]
+    fn test_sphere_tangent_frame() {
+        let centre = Vector::new(0.0, 0.0, 0.0);
+        let radius = 1.0;
+
+        let point = Vector::new(0.6, 0.0, 0.8);
+        let (tangent, bitangent) = sphere_tangent_frame(&point, &centre, radius);
+        assert_approx_eq!(tangent.len(), 1.0);
+        assert_approx_eq!(bitangent.len(), 1.0);
+        assert_approx_eq!(Vector::dot(&tangent, &point), 0.0);
+        assert_approx_eq!(Vector::dot(&bitangent, &point), 0.0);
+
+        // Moving along the frame must increase the corresponding texture coordinate
+        let (row, col) = sphere_texture_coords(&point, &centre, radius);
+        let moved = (point + 0.01 * tangent).unit_vector();
+        let (moved_row, moved_col) = sphere_texture_coords(&moved, &centre, radius);
+        assert_approx_eq!(moved_row, row, 1e-4);
+        assert!(moved_col > col);
+
+        let moved = (point + 0.01 * bitangent).unit_vector();
+        let (moved_row, moved_col) = sphere_texture_coords(&moved, &centre, radius);
+        assert!(moved_row > row);
+        assert_approx_eq!(moved_col, col, 1e-4);
+    }
+
+    #[test]
+    fn test_moving_sphere_pdf_value() {
+        let sphere = MovingSphere {
+            centre_start: Vector::new(-1.0, 0.0, 0.0),
+            time_start: 0.0,
+            centre_end: Vector::new(1.0, 0.0, 0.0),
+            time_end: 1.0,
+            radius: 1.0,
+            material: Material::Dielectric {
+                refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
+            },
+        };
+
+        // Seen from a point at a distance of 4 along z the swept sphere of radius 2 covers a cone
+        // with a half angle of 30 degrees
+        let origin = Vector::new(0.0, 0.0, 4.0);
+        let pdf = sphere.pdf_value(&origin, &Vector::new(1.0, 0.0, -4.0));
+        let solid_angle = 2.0 * PI * (1.0 - f64::sqrt(3.0) / 2.0);
+        assert_approx_eq!(pdf, 1.0 / solid_angle);
+
+        for _ in 0..20 {
+            let direction = sphere.random(&origin);
+            assert!(sphere.pdf_value(&origin, &direction) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_sphere_random_from_inside_covers_all_directions() {
+        let sphere = Sphere {
+            centre: Vector::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: Material::Dielectric {
+                refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
+            },
+        };
+
+        let origin = Vector::new(0.0, 0.0, 0.0);
+        let negative = (0..100)
+            .map(|_| sphere.random(&origin))
+            .filter(|direction| direction.x() < 0.0)
+            .count();
+        assert!(negative > 20 && negative < 80);
+    }
 }
@@ -1,5 +1,6 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
+use crate::data::colour::Colour;
 use crate::data::vector::Vector;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::{Geometry, HitResult, Hittable};
@@ -25,6 +26,7 @@ impl Hittable for Translate {
         self.geometry
             .hit(&moved_ray, tmin, tmax)
             .map(|hit| HitResult {
+                ray: *ray,
                 point: hit.point + self.offset,
                 ..hit
             })
@@ -49,11 +51,34 @@ impl Hittable for Translate {
     }
 
     fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
-        self.geometry.pdf_value(origin, direction)
+        self.geometry.pdf_value(&(origin - self.offset), direction)
     }
 
     fn random(&self, origin: &Vector) -> Vector {
-        self.geometry.random(origin)
+        // Directions are the same in both spaces
+        self.geometry.random(&(origin - self.offset))
+    }
+
+    fn area(&self) -> f64 {
+        self.geometry.area()
+    }
+
+    fn random_point(&self, time: f64) -> (Vector, Vector) {
+        let (point, normal) = self.geometry.random_point(time);
+        (point + self.offset, normal)
+    }
+
+    fn power(&self, assets: &Assets) -> f64 {
+        self.geometry.power(assets)
+    }
+
+    fn transmittance(&self, ray: &Ray, tmin: f64, tmax: f64) -> Colour {
+        self.geometry
+            .transmittance(&ray.offset(self.offset), tmin, tmax)
+    }
+
+    fn load_assets(&mut self, assets: &Assets) {
+        self.geometry.load_assets(assets)
     }
 }
 
@@ -61,6 +86,7 @@ impl Hittable for Translate {
 mod tests {
     use super::*;
     use crate::world::geometry::cube::Cube;
+    use crate::world::geometry::sphere::Sphere;
     use crate::world::materials::Material;
     use assert_approx_eq::assert_approx_eq;
 
@@ -71,6 +97,8 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         )
         .translate(Vector::new(1.0, 0.2, 0.0));
@@ -87,6 +115,8 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         )
         .translate(Vector::new(1.0, 0.2, 0.0));
@@ -103,6 +133,8 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         )
         .translate(Vector::new(1.0, 0.2, 0.0));
@@ -120,6 +152,8 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
             },
         )
         .translate(Vector::new(1.0, 0.2, 0.0));
@@ -130,4 +164,28 @@ mod tests {
         assert_approx_eq!(u, 0.3);
         assert_approx_eq!(v, 0.5);
     }
+
+    #[test]
+    fn test_translate_pdf_value() {
+        let sphere = Sphere::build(
+            Vector::new(0.0, 0.0, 0.0),
+            1.0,
+            Material::Dielectric {
+                refractive_index: 1.5,
+                thin_film: None,
+                dispersion: None,
+            },
+        )
+        .translate(Vector::new(5.0, 0.0, 0.0));
+
+        let origin = Vector::new(0.0, 0.0, 0.0);
+        assert!(sphere.pdf_value(&origin, &Vector::new(1.0, 0.0, 0.0)) > 0.0);
+        assert_approx_eq!(sphere.pdf_value(&origin, &Vector::new(-1.0, 0.0, 0.0)), 0.0);
+
+        for _ in 0..20 {
+            let direction = sphere.random(&origin);
+            assert!(direction.x() > 0.0);
+            assert!(sphere.pdf_value(&origin, &direction) > 0.0);
+        }
+    }
 }
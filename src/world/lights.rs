@@ -0,0 +1,157 @@
+//! Lights without any geometry. They cannot be hit by rays, so they are sampled explicitly with
+//! shadow rays at every diffuse bounce
+
+use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::onb::Onb;
+use crate::world::geometry::sphere::random_to_sphere;
+use crate::world::materials::emission::EmissionProfile;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Light {
+    Point {
+        position: Vector,
+        // Radiant intensity, falling off with the square of the distance
+        intensity: Colour,
+    },
+    Spot {
+        position: Vector,
+        direction: Vector,
+        intensity: Colour,
+        // In degrees from the direction, as for `EmissionProfile::Spot`
+        total_angle: f64,
+        falloff_start: f64,
+    },
+    Directional {
+        // Direction the light travels in, e.g. downwards for a sun at its zenith
+        direction: Vector,
+        // Irradiance on a surface facing the light
+        irradiance: Colour,
+        // In degrees, the sun is about 0.53. Zero gives perfectly sharp shadows
+        angular_diameter: f64,
+    },
+}
+
+pub struct LightSample {
+    // Unit vector from the lit point towards the light
+    pub direction: Vector,
+    // Distance to the light, which occluders must be closer than
+    pub distance: f64,
+    // Light arriving at the lit point from the direction
+    pub incident: Colour,
+}
+
+impl Light {
+    pub fn sample(&self, point: &Vector) -> LightSample {
+        match self {
+            Light::Point {
+                position,
+                intensity,
+            } => {
+                let to_light = position - point;
+                let distance = to_light.len();
+                LightSample {
+                    direction: to_light / distance,
+                    distance,
+                    incident: intensity / (distance * distance),
+                }
+            }
+            Light::Spot {
+                position,
+                direction,
+                intensity,
+                total_angle,
+                falloff_start,
+            } => {
+                let to_light = position - point;
+                let distance = to_light.len();
+                let direction_to_light = to_light / distance;
+
+                let profile = EmissionProfile::Spot {
+                    total_angle: *total_angle,
+                    falloff_start: *falloff_start,
+                };
+                let cosine = -Vector::dot(&direction_to_light, &direction.unit_vector());
+
+                LightSample {
+                    direction: direction_to_light,
+                    distance,
+                    incident: profile.scale(cosine) * intensity / (distance * distance),
+                }
+            }
+            Light::Directional {
+                direction,
+                irradiance,
+                angular_diameter,
+            } => {
+                let direction_to_light = -direction.unit_vector();
+                // Pick a direction within the disk of the light to get soft shadows
+                let direction = if *angular_diameter > 0.0 {
+                    let distance_ratio = f64::sin(angular_diameter.to_radians() / 2.0).powi(2);
+                    let onb = Onb::build_from_w(&direction_to_light);
+                    onb.local_from_vec(&random_to_sphere(distance_ratio))
+                        .unit_vector()
+                } else {
+                    direction_to_light
+                };
+
+                LightSample {
+                    direction,
+                    distance: f64::MAX,
+                    incident: *irradiance,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_point_light_falls_off_with_distance() {
+        let light = Light::Point {
+            position: Vector::new(0.0, 2.0, 0.0),
+            intensity: Colour::new(8.0, 8.0, 8.0),
+        };
+        let sample = light.sample(&Vector::new(0.0, 0.0, 0.0));
+
+        assert_approx_eq!(sample.distance, 2.0);
+        assert_approx_eq!(sample.direction.y(), 1.0);
+        assert_approx_eq!(sample.incident.r(), 2.0);
+    }
+
+    #[test]
+    fn test_spot_light_cone() {
+        let light = Light::Spot {
+            position: Vector::new(0.0, 1.0, 0.0),
+            direction: Vector::new(0.0, -1.0, 0.0),
+            intensity: Colour::new(1.0, 1.0, 1.0),
+            total_angle: 30.0,
+            falloff_start: 20.0,
+        };
+
+        let below = light.sample(&Vector::new(0.0, 0.0, 0.0));
+        let outside = light.sample(&Vector::new(1.0, 0.0, 0.0));
+
+        assert_approx_eq!(below.incident.r(), 1.0);
+        assert_approx_eq!(outside.incident.r(), 0.0);
+    }
+
+    #[test]
+    fn test_directional_light_within_disk() {
+        let light = Light::Directional {
+            direction: Vector::new(0.0, -1.0, 0.0),
+            irradiance: Colour::new(3.0, 3.0, 3.0),
+            angular_diameter: 10.0,
+        };
+
+        for _ in 0..100 {
+            let sample = light.sample(&Vector::new(0.0, 0.0, 0.0));
+            assert!(sample.direction.y() >= f64::cos(5_f64.to_radians()) - 1e-9);
+            assert_approx_eq!(sample.incident.g(), 3.0);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! Perturbation of the shading normal by normal maps and height (bump) maps
+
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::world::geometry::HitResult;
+use crate::world::texture::Texture;
+
+// Offset used for the finite differences of the height texture, applied both to the texture
+// coordinates and to the point along the tangent frame
+const HEIGHT_SAMPLE_OFFSET: f64 = 0.001;
+
+pub fn normal_from_map(map: &Texture, hit: &HitResult, assets: &Assets) -> Vector {
+    // Tangent space normals are encoded as colours with every channel mapped from [-1, 1] to
+    // [0, 1], with x along the tangent, y along the bitangent and z along the surface normal
+    let colour = map.value(hit.texture_coords, &hit.point, assets);
+    let x = 2.0 * colour.r() - 1.0;
+    let y = 2.0 * colour.g() - 1.0;
+    let z = 2.0 * colour.b() - 1.0;
+
+    let normal = x * hit.tangent + y * hit.bitangent + z * hit.shading_normal.unit_vector();
+    normal.unit_vector()
+}
+
+pub fn normal_from_height(
+    height: &Texture,
+    scale: f64,
+    hit: &HitResult,
+    assets: &Assets,
+) -> Vector {
+    let (row, col) = hit.texture_coords;
+    let sample = |texture_coords: (f64, f64), point: &Vector| {
+        height.value(texture_coords, point, assets).luminance()
+    };
+
+    let base = sample(hit.texture_coords, &hit.point);
+    let along_tangent = sample(
+        (row, col + HEIGHT_SAMPLE_OFFSET),
+        &(hit.point + HEIGHT_SAMPLE_OFFSET * hit.tangent),
+    );
+    let along_bitangent = sample(
+        (row + HEIGHT_SAMPLE_OFFSET, col),
+        &(hit.point + HEIGHT_SAMPLE_OFFSET * hit.bitangent),
+    );
+
+    let slope_tangent = (along_tangent - base) / HEIGHT_SAMPLE_OFFSET;
+    let slope_bitangent = (along_bitangent - base) / HEIGHT_SAMPLE_OFFSET;
+
+    let normal = hit.shading_normal.unit_vector()
+        - scale * (slope_tangent * hit.tangent + slope_bitangent * hit.bitangent);
+    normal.unit_vector()
+}
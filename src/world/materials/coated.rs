@@ -0,0 +1,134 @@
+use crate::data::assets::Assets;
+use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::pdf::{random_point_in_unit_sphere, uniform};
+use crate::world::geometry::HitResult;
+use crate::world::materials::dielectric::{
+    reflect, reflectivity_schlick_approx, REFRACTIVE_INDEX_OF_AIR,
+};
+use crate::world::materials::{Material, ScatterResult};
+
+const COAT_ATTENUATION: [f64; 3] = [1.0, 1.0, 1.0];
+
+pub fn scatter(
+    refractive_index: f64,
+    roughness: f64,
+    tint: &Colour,
+    thickness: f64,
+    inner: &Material,
+    hit: &HitResult,
+    assets: &Assets,
+) -> Option<ScatterResult> {
+    // Rays leaving the inner material (e.g. a dielectric base) are not affected by the coat
+    if !hit.front_face() {
+        return inner.scatter(hit, assets);
+    }
+
+    let unit_vector = hit.ray.direction().unit_vector();
+    let normal = hit.shading_face_normal();
+    let cosine = -Vector::dot(&unit_vector, &normal);
+
+    // Choose between the coat and the base using the reflectivity of the coat's outer surface
+    let reflect_prob =
+        reflectivity_schlick_approx(cosine, REFRACTIVE_INDEX_OF_AIR, refractive_index);
+    let reflect_rand: f64 = uniform();
+
+    if reflect_rand < reflect_prob {
+        let reflected = reflect(&unit_vector, &normal);
+        let ray = hit.ray.continued(
+            hit.point,
+            reflected + roughness * random_point_in_unit_sphere(),
+        );
+        return Some(ScatterResult::specular(
+            Colour::new(
+                COAT_ATTENUATION[0],
+                COAT_ATTENUATION[1],
+                COAT_ATTENUATION[2],
+            ),
+            ray,
+        ));
+    }
+
+    let absorption = absorption(tint, thickness, refractive_index, cosine);
+    inner
+        .scatter(hit, assets)
+        .map(|scatter| scatter.attenuate(&absorption))
+}
+
+fn absorption(tint: &Colour, thickness: f64, refractive_index: f64, cosine: f64) -> Colour {
+    // The tint is the transmittance of a coat of unit thickness at normal incidence. Light
+    // crosses the coat twice, once on the way to the base and once on the way back out, along
+    // the refracted direction
+    let sin_squared = (1.0 - cosine * cosine) / (refractive_index * refractive_index);
+    let cosine_refracted = f64::sqrt(f64::max(0.0, 1.0 - sin_squared));
+    let exponent = 2.0 * thickness / cosine_refracted;
+
+    Colour::new(
+        tint.r().powf(exponent),
+        tint.g().powf(exponent),
+        tint.b().powf(exponent),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::tests::{floor_hit, white};
+    use assert_approx_eq::assert_approx_eq;
+
+    /// Hit on a white floor at the origin from a ray arriving at the given cosine to its normal
+    fn hit(cosine: f64) -> HitResult {
+        let from = Vector::new(-f64::sqrt(1.0 - cosine * cosine), cosine, 0.0);
+        floor_hit(from, white())
+    }
+
+    /// Share of rays reflected by the coat, and the average light kept over all rays, for a coat
+    /// with a refractive index of 1.5 that lets half the light through at unit thickness
+    fn coat_reflection_and_energy(cosine: f64, thickness: f64) -> (f64, f64) {
+        let assets = Assets::new(&[]).unwrap();
+        let tint = Colour::new(0.5, 0.5, 0.5);
+        let hit = hit(cosine);
+
+        let num_of_samples = 20_000;
+        let mut reflected = 0;
+        let mut energy = 0.0;
+        for _ in 0..num_of_samples {
+            match scatter(1.5, 0.0, &tint, thickness, &white(), &hit, &assets) {
+                Some(ScatterResult::Specular { attenuation, ray }) => {
+                    // Mirrored about the normal
+                    assert_approx_eq!(ray.direction().y(), cosine);
+                    reflected += 1;
+                    energy += attenuation.r();
+                }
+                Some(ScatterResult::Diffuse { attenuation, .. }) => energy += attenuation.r(),
+                None => panic!("a coated white surface always scatters"),
+            }
+        }
+        let num_of_samples = f64::from(num_of_samples);
+        (
+            f64::from(reflected) / num_of_samples,
+            energy / num_of_samples,
+        )
+    }
+
+    #[test]
+    fn test_coat_at_normal_incidence() {
+        // ((1.5 - 1) / (1.5 + 1))² of the light is reflected by the coat, and the rest crosses
+        // the coat twice straight on
+        let (reflected, energy) = coat_reflection_and_energy(1.0, 0.5);
+        assert_approx_eq!(reflected, 0.04, 0.005);
+        assert_approx_eq!(energy, 0.04 + 0.96 * 0.5, 0.01);
+
+        let (_, energy) = coat_reflection_and_energy(1.0, 0.0);
+        assert_approx_eq!(energy, 1.0);
+    }
+
+    #[test]
+    fn test_coat_at_grazing_incidence() {
+        // Schlick's approximation gives 0.04 + 0.96 * 0.99⁵ = 0.953. Light refracted into the
+        // coat travels at a cosine of 0.745, crossing 1.342 units of it on the way in and out
+        let (reflected, energy) = coat_reflection_and_energy(0.01, 0.5);
+        assert_approx_eq!(reflected, 0.953, 0.01);
+        assert_approx_eq!(energy, 0.953 + 0.047 * 0.5_f64.powf(1.342), 0.01);
+    }
+}
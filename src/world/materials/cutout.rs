@@ -0,0 +1,37 @@
+use crate::data::assets::Assets;
+use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::pdf::uniform;
+use crate::world::geometry::HitResult;
+use crate::world::materials::ScatterResult;
+use crate::world::texture::Texture;
+
+const PASS_THROUGH_ATTENUATION: [f64; 3] = [1.0, 1.0, 1.0];
+
+pub fn is_transparent(
+    opacity: &Texture,
+    threshold: Option<f64>,
+    texture_coords: (f64, f64),
+    point: &Vector,
+    assets: &Assets,
+) -> bool {
+    let opacity = opacity.value(texture_coords, point, assets).luminance();
+    match threshold {
+        // Hard edged masks, e.g. leaves or fences
+        Some(threshold) => opacity < threshold,
+        // Partially opaque surfaces let through a fraction of the rays
+        None => uniform::<f64>() >= opacity,
+    }
+}
+
+pub fn pass_through(hit: &HitResult) -> Option<ScatterResult> {
+    let ray = hit.ray.continued(hit.point, *hit.ray.direction());
+    Some(ScatterResult::specular(
+        Colour::new(
+            PASS_THROUGH_ATTENUATION[0],
+            PASS_THROUGH_ATTENUATION[1],
+            PASS_THROUGH_ATTENUATION[2],
+        ),
+        ray,
+    ))
+}
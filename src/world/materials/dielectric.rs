@@ -1,54 +1,106 @@
-use crate::camera::Ray;
 use crate::data::colour::Colour;
+use crate::data::spectrum::{sample_wavelength, wavelength_weight};
 use crate::data::vector::Vector;
 use crate::pdf::uniform;
 use crate::world::geometry::HitResult;
+use crate::world::materials::dispersion::Dispersion;
+use crate::world::materials::thin_film::ThinFilm;
 use crate::world::materials::ScatterResult;
 
-const REFRACTIVE_INDEX_OF_AIR: f64 = 1.0;
+pub const REFRACTIVE_INDEX_OF_AIR: f64 = 1.0;
 const DIELECTRIC_ATTENUATION: [f64; 3] = [1.0, 1.0, 1.0];
 
-pub fn scatter(refractive_index: f64, hit: &HitResult) -> Option<ScatterResult> {
+pub fn scatter(
+    refractive_index: f64,
+    thin_film: &Option<ThinFilm>,
+    hit: &HitResult,
+) -> Option<ScatterResult> {
     let unit_vector = hit.ray.direction().unit_vector();
-    let reflected = reflect(&unit_vector, &hit.surface_normal);
+    let reflected = reflect(&unit_vector, &hit.shading_normal);
 
-    let uvn = Vector::dot(&unit_vector, &hit.surface_normal);
+    let uvn = Vector::dot(&unit_vector, &hit.shading_normal);
 
     // Determine whether we are going from air to the geometry or vv
     // This current does not support refraction from inside one geometry to another
-    let (sign, n_i, n_t) = if uvn > 0.0 {
-        (-1.0, refractive_index, REFRACTIVE_INDEX_OF_AIR)
-    } else {
+    let (sign, n_i, n_t) = if hit.front_face() {
         (1.0, REFRACTIVE_INDEX_OF_AIR, refractive_index)
+    } else {
+        (-1.0, refractive_index, REFRACTIVE_INDEX_OF_AIR)
     };
 
     let cosine = -sign * uvn;
-    let reflect_prob = reflectivity_schlick_approx(cosine, n_i, n_t);
+
+    // The film only coats the outside of the geometry
+    let film_reflectance = match thin_film {
+        Some(thin_film) if hit.front_face() => Some(thin_film.dielectric_reflectance(cosine, n_t)),
+        _ => None,
+    };
+
+    let reflect_prob = match &film_reflectance {
+        Some(reflectance) => (reflectance.r() + reflectance.g() + reflectance.b()) / 3.0,
+        None => reflectivity_schlick_approx(cosine, n_i, n_t),
+    };
     let reflect_rand: f64 = uniform();
     let should_reflect = reflect_rand < reflect_prob;
 
     let maybe_refracted = if should_reflect {
         None
     } else {
-        refract(&unit_vector, &(sign * hit.surface_normal), n_i / n_t)
+        refract(&unit_vector, &(sign * hit.shading_normal), n_i / n_t)
     };
 
+    let attenuation = Colour::new(
+        DIELECTRIC_ATTENUATION[0],
+        DIELECTRIC_ATTENUATION[1],
+        DIELECTRIC_ATTENUATION[2],
+    );
+
     let ray = match maybe_refracted {
-        Some(refracted) => Ray::new(hit.point, refracted, hit.ray.time()),
-        None => Ray::new(hit.point, reflected, hit.ray.time()),
+        Some(refracted) => hit.ray.continued(hit.point, refracted),
+        None => hit.ray.continued(hit.point, reflected),
     };
 
-    Some(ScatterResult::specular(
-        Colour::new(
-            DIELECTRIC_ATTENUATION[0],
-            DIELECTRIC_ATTENUATION[1],
-            DIELECTRIC_ATTENUATION[2],
-        ),
-        ray,
-    ))
+    // With a film the reflectance differs per channel. Weight each channel by its own
+    // reflectance over the probability of the choice made above
+    let attenuation = match film_reflectance {
+        Some(reflectance) if should_reflect => attenuation * reflectance / reflect_prob,
+        Some(reflectance) if maybe_refracted.is_some() => {
+            let transmittance = Colour::new(
+                1.0 - reflectance.r(),
+                1.0 - reflectance.g(),
+                1.0 - reflectance.b(),
+            );
+            attenuation * transmittance / (1.0 - reflect_prob)
+        }
+        _ => attenuation,
+    };
+
+    Some(ScatterResult::specular(attenuation, ray))
+}
+
+pub fn scatter_dispersive(
+    dispersion: &Dispersion,
+    thin_film: &Option<ThinFilm>,
+    hit: &HitResult,
+) -> Option<ScatterResult> {
+    // Rays pick a single wavelength the first time they reach a dispersive material and keep
+    // it for the rest of their path. Weighting by the colour of the wavelength picked at random
+    // averages to white over many rays
+    match hit.ray.wavelength() {
+        Some(wavelength) => scatter(dispersion.refractive_index(wavelength), thin_film, hit),
+        None => {
+            let wavelength = sample_wavelength();
+            let hit = HitResult {
+                ray: hit.ray.with_wavelength(wavelength),
+                ..hit.clone()
+            };
+            scatter(dispersion.refractive_index(wavelength), thin_film, &hit)
+                .map(|scatter| scatter.attenuate(&wavelength_weight(wavelength)))
+        }
+    }
 }
 
-fn reflect(unit_vector: &Vector, surface_normal: &Vector) -> Vector {
+pub fn reflect(unit_vector: &Vector, surface_normal: &Vector) -> Vector {
     let uv = unit_vector;
     let n = surface_normal;
 
@@ -68,7 +120,7 @@ fn refract(
     let dt = Vector::dot(uv, n);
 
     let ni_over_nt = refractive_index_ratio;
-    let discriminant = 2.0 - ni_over_nt * ni_over_nt * (1.0 - dt * dt);
+    let discriminant = 1.0 - ni_over_nt * ni_over_nt * (1.0 - dt * dt);
 
     if discriminant > 0.0 {
         let refracted = ni_over_nt * (uv - n * dt) - n * discriminant.sqrt();
@@ -78,8 +130,33 @@ fn refract(
     None
 }
 
-fn reflectivity_schlick_approx(cosine: f64, n_i: f64, n_t: f64) -> f64 {
+pub fn reflectivity_schlick_approx(cosine: f64, n_i: f64, n_t: f64) -> f64 {
     let r0 = (n_i - n_t) / (n_i + n_t);
     let r0 = r0 * r0;
     r0 + (1.0 - r0) * f64::powi(1.0 - cosine, 5)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_refract_follows_snells_law() {
+        let normal = Vector::new(0.0, 1.0, 0.0);
+        let incoming = Vector::new(1.0, -1.0, 0.0).unit_vector();
+
+        // From air into glass at 45 degrees, sin(t) = sin(45) / 1.5
+        let sin_t = 0.5_f64.sqrt() / 1.5;
+        let refracted = refract(&incoming, &normal, 1.0 / 1.5).unwrap();
+        assert_approx_eq!(refracted.x(), sin_t);
+        assert_approx_eq!(refracted.y(), -(1.0 - sin_t * sin_t).sqrt());
+        assert_approx_eq!(refracted.len(), 1.0);
+
+        // Out of glass at 45 degrees is past the critical angle of about 41.8 degrees
+        assert!(refract(&incoming, &normal, 1.5).is_none());
+        let steep = Vector::new(0.5, -(0.75_f64).sqrt(), 0.0);
+        let refracted = refract(&steep, &normal, 1.5).unwrap();
+        assert_approx_eq!(refracted.x(), 0.75);
+    }
+}
@@ -0,0 +1,50 @@
+//! Wavelength dependent refractive indices
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Dispersion {
+    // n = a + b / λ², with λ in μm. e.g. BK7 glass: a = 1.5046, b = 0.0042
+    Cauchy { a: f64, b: f64 },
+    // n² = 1 + Σ b_i λ² / (λ² - c_i), with λ in μm. e.g. BK7 glass:
+    // b = [1.0396, 0.2318, 1.0105], c = [0.0060, 0.0200, 103.5607]
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub fn refractive_index(&self, wavelength: f64) -> f64 {
+        let micrometres = wavelength / 1000.0;
+        let squared = micrometres * micrometres;
+
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / squared,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * squared / (squared - c[i])).sum();
+                f64::sqrt(1.0 + sum)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_sellmeier_bk7() {
+        let bk7 = Dispersion::Sellmeier {
+            b: [1.039_612_12, 0.231_792_344, 1.010_469_45],
+            c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+        };
+
+        assert_approx_eq!(bk7.refractive_index(587.6), 1.5168, 1e-4);
+        assert!(bk7.refractive_index(450.0) > bk7.refractive_index(650.0));
+    }
+
+    #[test]
+    fn test_cauchy() {
+        let glass = Dispersion::Cauchy { a: 1.5, b: 0.01 };
+
+        assert_approx_eq!(glass.refractive_index(500.0), 1.54);
+        assert!(glass.refractive_index(450.0) > glass.refractive_index(650.0));
+    }
+}
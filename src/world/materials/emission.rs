@@ -0,0 +1,164 @@
+//! Angular distribution of the light emitted by area lights
+
+use crate::float;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum EmissionProfileError {
+    #[error("goniometric profile must have at least one measurement")]
+    EmptyTable,
+    #[error("goniometric profile has <{angles}> angles but <{values}> values")]
+    MismatchedTable { angles: usize, values: usize },
+    #[error("goniometric angle <{0}> must be greater than the one before it")]
+    UnsortedAngles(f64),
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum EmissionProfile {
+    // Same radiance in every direction
+    #[default]
+    Lambertian,
+    // Radiance falls off with the cosine of the angle from the normal raised to the exponent
+    CosinePower {
+        exponent: f64,
+    },
+    // Full radiance within `falloff_start` degrees of the normal, fading out smoothly up to
+    // `total_angle` degrees and black beyond
+    Spot {
+        total_angle: f64,
+        falloff_start: f64,
+    },
+    // Relative radiance measured at increasing angles in degrees from the normal, e.g. taken
+    // from the vertical angles of an IES profile. Values in between are linearly interpolated
+    Goniometric {
+        angles: Vec<f64>,
+        values: Vec<f64>,
+    },
+}
+
+impl EmissionProfile {
+    /// Scale of the emitted radiance in a direction with the given cosine to the normal
+    pub fn scale(&self, cosine: f64) -> f64 {
+        let cosine = float::min(1.0, float::max(0.0, cosine));
+
+        match self {
+            EmissionProfile::Lambertian => 1.0,
+            EmissionProfile::CosinePower { exponent } => cosine.powf(*exponent),
+            EmissionProfile::Spot {
+                total_angle,
+                falloff_start,
+            } => {
+                let cos_total = total_angle.to_radians().cos();
+                let cos_falloff_start = falloff_start.to_radians().cos();
+                if cosine >= cos_falloff_start {
+                    1.0
+                } else if cosine <= cos_total {
+                    0.0
+                } else {
+                    let t = (cosine - cos_total) / (cos_falloff_start - cos_total);
+                    t * t * (3.0 - 2.0 * t)
+                }
+            }
+            EmissionProfile::Goniometric { angles, values } => {
+                goniometric_scale(angles, values, cosine.acos().to_degrees())
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), EmissionProfileError> {
+        match self {
+            EmissionProfile::Goniometric { angles, values } => {
+                if angles.is_empty() {
+                    return Err(EmissionProfileError::EmptyTable);
+                }
+                if angles.len() != values.len() {
+                    return Err(EmissionProfileError::MismatchedTable {
+                        angles: angles.len(),
+                        values: values.len(),
+                    });
+                }
+                match angles.windows(2).find(|pair| pair[1] <= pair[0]) {
+                    Some(pair) => Err(EmissionProfileError::UnsortedAngles(pair[1])),
+                    None => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn goniometric_scale(angles: &[f64], values: &[f64], angle: f64) -> f64 {
+    if angles.is_empty() {
+        return 0.0;
+    }
+
+    match angles.iter().position(|&measured| measured >= angle) {
+        None => *values.last().unwrap(),
+        Some(0) => values[0],
+        Some(index) => {
+            let t = (angle - angles[index - 1]) / (angles[index] - angles[index - 1]);
+            values[index - 1] * (1.0 - t) + values[index] * t
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_cosine_power_profile() {
+        let profile = EmissionProfile::CosinePower { exponent: 2.0 };
+
+        assert_approx_eq!(profile.scale(1.0), 1.0);
+        assert_approx_eq!(profile.scale(0.5), 0.25);
+        assert_approx_eq!(profile.scale(-0.5), 0.0);
+    }
+
+    #[test]
+    fn test_spot_profile() {
+        let profile = EmissionProfile::Spot {
+            total_angle: 60.0,
+            falloff_start: 30.0,
+        };
+
+        assert_approx_eq!(profile.scale(1.0), 1.0);
+        assert_approx_eq!(profile.scale(20_f64.to_radians().cos()), 1.0);
+        assert_approx_eq!(profile.scale(70_f64.to_radians().cos()), 0.0);
+
+        let inside_falloff = profile.scale(45_f64.to_radians().cos());
+        assert!(inside_falloff > 0.0 && inside_falloff < 1.0);
+    }
+
+    #[test]
+    fn test_goniometric_profile() {
+        let profile = EmissionProfile::Goniometric {
+            angles: vec![0.0, 45.0, 90.0],
+            values: vec![1.0, 0.5, 0.0],
+        };
+
+        assert_approx_eq!(profile.scale(1.0), 1.0);
+        assert_approx_eq!(profile.scale(45_f64.to_radians().cos()), 0.5);
+        assert_approx_eq!(profile.scale(60_f64.to_radians().cos()), 1.0 / 3.0);
+        assert_approx_eq!(profile.scale(0.0), 0.0);
+    }
+
+    #[test]
+    fn test_goniometric_profile_is_validated() {
+        let profile =
+            |angles: Vec<f64>, values: Vec<f64>| EmissionProfile::Goniometric { angles, values };
+
+        assert!(profile(vec![0.0, 45.0, 90.0], vec![1.0, 0.5, 0.0])
+            .validate()
+            .is_ok());
+        assert!(profile(vec![], vec![]).validate().is_err());
+        assert!(profile(vec![0.0, 45.0], vec![1.0]).validate().is_err());
+        assert!(profile(vec![0.0, 45.0, 45.0], vec![1.0, 0.5, 0.0])
+            .validate()
+            .is_err());
+        assert!(profile(vec![0.0, 90.0, 45.0], vec![1.0, 0.5, 0.0])
+            .validate()
+            .is_err());
+    }
+}
@@ -1,12 +1,20 @@
-use crate::camera::Ray;
 use crate::data::assets::Assets;
-use crate::pdf::random_point_in_unit_sphere;
+use crate::pdf::Pdf;
 use crate::world::geometry::HitResult;
+use crate::world::materials::phase::PhaseFunction;
 use crate::world::materials::ScatterResult;
 use crate::world::texture::Texture;
 
-pub fn scatter(albedo: &Texture, hit: &HitResult, assets: &Assets) -> Option<ScatterResult> {
-    let scattered = Ray::new(hit.point, random_point_in_unit_sphere(), hit.ray.time());
+pub fn scatter(
+    albedo: &Texture,
+    phase: &PhaseFunction,
+    hit: &HitResult,
+    assets: &Assets,
+) -> Option<ScatterResult> {
     let attenuation = albedo.value(hit.texture_coords, &hit.point, assets);
-    Some(ScatterResult::specular(attenuation, scattered))
+    let pdf = Pdf::Phase {
+        phase: *phase,
+        incoming: *hit.ray.direction(),
+    };
+    Some(ScatterResult::diffuse(attenuation, pdf))
 }
@@ -19,7 +19,7 @@ pub fn scattering_pdf(surface_normal: &Vector, scattered: &Ray) -> f64 {
 
 pub fn scatter(albedo: &Texture, hit: &HitResult, assets: &Assets) -> Option<ScatterResult> {
     let albedo = albedo.value(hit.texture_coords, &hit.point, &assets);
-    let pdf = Pdf::Cosine(Onb::build_from_w(&hit.face_normal()));
+    let pdf = Pdf::Cosine(Onb::build_from_w(&hit.shading_face_normal()));
 
     Some(ScatterResult::diffuse(albedo, pdf))
 }
@@ -0,0 +1,169 @@
+//! Media that absorb, scatter and emit light by different amounts in each channel. Collisions are
+//! sampled against the largest extinction over the channels, so channels that the medium does not
+//! stop as quickly see some collisions as null collisions which let the ray carry on unchanged.
+//! Those are taken with the average probability over the channels and weighted per channel, as in
+//! section 5.1 of Novák et al., "Monte Carlo Methods for Volumetric Light Transport Simulation"
+//! (2018)
+
+use crate::data::colour::Colour;
+use crate::data::spectrum;
+use crate::float;
+use crate::pdf::{uniform, Pdf};
+use crate::world::geometry::HitResult;
+use crate::world::materials::phase::PhaseFunction;
+use crate::world::materials::ScatterResult;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MediumError {
+    #[error("{name} coefficients <{colour:?}> must not be negative")]
+    NegativeCoefficients { name: &'static str, colour: Colour },
+}
+
+pub fn extinction(absorption: &Colour, scattering: &Colour) -> Colour {
+    absorption + scattering
+}
+
+/// Extinction that collisions are sampled against, which no channel goes above
+pub fn majorant(extinction: &Colour) -> f64 {
+    float::max(extinction.r(), float::max(extinction.g(), extinction.b()))
+}
+
+pub fn scatter(
+    absorption: &Colour,
+    scattering: &Colour,
+    phase: &PhaseFunction,
+    hit: &HitResult,
+) -> Option<ScatterResult> {
+    let extinction = extinction(absorption, scattering);
+    let majorant = majorant(&extinction);
+    if majorant <= 0.0 {
+        return None;
+    }
+
+    let null = Colour::new(
+        majorant - extinction.r(),
+        majorant - extinction.g(),
+        majorant - extinction.b(),
+    ) / majorant;
+    let null_probability = average(&null);
+    if uniform::<f64>() < null_probability {
+        let ray = hit.ray.continued(hit.point, *hit.ray.direction());
+        return Some(ScatterResult::specular(null / null_probability, ray));
+    }
+
+    // Light absorbed at real collisions is accounted for by the weight rather than by ending the
+    // path, which for grey media leaves the albedo as the weight
+    if *scattering == Colour::new(0.0, 0.0, 0.0) {
+        return None;
+    }
+    let attenuation = scattering / (majorant * (1.0 - null_probability));
+    let pdf = Pdf::Phase {
+        phase: *phase,
+        incoming: *hit.ray.direction(),
+    };
+    Some(ScatterResult::diffuse(attenuation, pdf))
+}
+
+/// Light emitted at a collision. Emission is spread along the ray in proportion to absorption, and
+/// collisions are found in proportion to the majorant
+pub fn emitted(
+    absorption: &Colour,
+    scattering: &Colour,
+    emission: &Colour,
+    hit: &HitResult,
+) -> Colour {
+    let majorant = majorant(&extinction(absorption, scattering));
+    if majorant <= 0.0 {
+        return Colour::new(0.0, 0.0, 0.0);
+    }
+    spectrum::at_wavelength(&(absorption * emission / majorant), hit.ray.wavelength())
+}
+
+pub fn validate(
+    absorption: &Colour,
+    scattering: &Colour,
+    emission: &Colour,
+) -> Result<(), MediumError> {
+    let coefficients = [
+        ("absorption", absorption),
+        ("scattering", scattering),
+        ("emission", emission),
+    ];
+    for (name, colour) in coefficients.iter() {
+        if colour.r() < 0.0 || colour.g() < 0.0 || colour.b() < 0.0 {
+            return Err(MediumError::NegativeCoefficients {
+                name,
+                colour: **colour,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn average(colour: &Colour) -> f64 {
+    (colour.r() + colour.g() + colour.b()) / 3.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::vector::Vector;
+    use crate::renderer::tests::floor_hit;
+    use crate::world::materials::Material;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn hit() -> HitResult {
+        let material = Material::Medium {
+            absorption: Colour::new(0.0, 0.0, 0.0),
+            scattering: Colour::new(0.0, 0.0, 0.0),
+            emission: Colour::new(0.0, 0.0, 0.0),
+            phase: PhaseFunction::Isotropic,
+        };
+        floor_hit(Vector::new(0.0, 1.0, 0.0), material)
+    }
+
+    #[test]
+    fn test_grey_medium_scatters_with_its_albedo() {
+        let absorption = Colour::new(0.5, 0.5, 0.5);
+        let scattering = Colour::new(1.5, 1.5, 1.5);
+        for _ in 0..100 {
+            match scatter(&absorption, &scattering, &PhaseFunction::Isotropic, &hit()) {
+                Some(ScatterResult::Diffuse { attenuation, .. }) => {
+                    assert_approx_eq!(attenuation.r(), 0.75);
+                    assert_approx_eq!(attenuation.b(), 0.75);
+                }
+                _ => panic!("grey media have no null collisions"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_coloured_medium_weights_average_to_its_coefficients() {
+        // Red is stopped four times as quickly as green and blue
+        let absorption = Colour::new(3.0, 0.5, 0.0);
+        let scattering = Colour::new(1.0, 0.5, 1.0);
+
+        let num_of_samples = 20_000;
+        let mut null = Colour::new(0.0, 0.0, 0.0);
+        let mut scattered = Colour::new(0.0, 0.0, 0.0);
+        for _ in 0..num_of_samples {
+            match scatter(&absorption, &scattering, &PhaseFunction::Isotropic, &hit()) {
+                Some(ScatterResult::Specular { attenuation, .. }) => null = null + attenuation,
+                Some(ScatterResult::Diffuse { attenuation, .. }) => {
+                    scattered = scattered + attenuation
+                }
+                None => panic!("scattering media always carry on"),
+            }
+        }
+
+        let null = null / f64::from(num_of_samples);
+        let scattered = scattered / f64::from(num_of_samples);
+        assert_approx_eq!(null.r(), 0.0);
+        assert_approx_eq!(null.g(), 0.75, 0.02);
+        assert_approx_eq!(null.b(), 0.75, 0.02);
+        assert_approx_eq!(scattered.r(), 0.25, 0.02);
+        assert_approx_eq!(scattered.g(), 0.125, 0.02);
+        assert_approx_eq!(scattered.b(), 0.25, 0.02);
+    }
+}
@@ -1,20 +1,32 @@
-use crate::camera::Ray;
 use crate::data::colour::Colour;
 use crate::data::vector::Vector;
 use crate::pdf::random_point_in_unit_sphere;
 use crate::world::geometry::HitResult;
+use crate::world::materials::thin_film::ThinFilm;
 use crate::world::materials::ScatterResult;
 
-pub fn scatter(albedo: &Colour, fuzz: f64, hit: &HitResult) -> Option<ScatterResult> {
+pub fn scatter(
+    albedo: &Colour,
+    fuzz: f64,
+    thin_film: &Option<ThinFilm>,
+    hit: &HitResult,
+) -> Option<ScatterResult> {
     let unit_vector = hit.ray.direction().unit_vector();
-    let reflected = reflect(&unit_vector, &hit.face_normal());
-    let ray = Ray::new(
-        hit.point,
-        reflected + fuzz * random_point_in_unit_sphere(),
-        hit.ray.time(),
-    );
+    let reflected = reflect(&unit_vector, &hit.shading_face_normal());
 
-    Some(ScatterResult::specular(*albedo, ray))
+    let attenuation = match thin_film {
+        Some(thin_film) => {
+            let cosine = -Vector::dot(&unit_vector, &hit.shading_face_normal());
+            thin_film.conductor_reflectance(cosine, albedo)
+        }
+        None => *albedo,
+    };
+
+    let ray = hit
+        .ray
+        .continued(hit.point, reflected + fuzz * random_point_in_unit_sphere());
+
+    Some(ScatterResult::specular(attenuation, ray))
 }
 
 fn reflect(unit_vector: &Vector, surface_normal: &Vector) -> Vector {
@@ -0,0 +1,104 @@
+use crate::data::assets::Assets;
+use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::float;
+use crate::pdf::uniform;
+use crate::world::materials::Material;
+use crate::world::texture::Texture;
+
+pub fn choose<'a>(
+    a: &'a Material,
+    b: &'a Material,
+    factor: &Texture,
+    texture_coords: (f64, f64),
+    point: &Vector,
+    assets: &Assets,
+) -> &'a Material {
+    // A factor of 0.0 always selects `a` and a factor of 1.0 always selects `b`
+    if uniform::<f64>() < weight(factor, texture_coords, point, assets) {
+        b
+    } else {
+        a
+    }
+}
+
+pub fn emitted(
+    a: &Colour,
+    b: &Colour,
+    factor: &Texture,
+    texture_coords: (f64, f64),
+    point: &Vector,
+    assets: &Assets,
+) -> Colour {
+    // Emission does not depend on the sampled direction, so use its expected value directly
+    // instead of adding noise by picking one of the materials
+    let weight = weight(factor, texture_coords, point, assets);
+    (1.0 - weight) * a + weight * b
+}
+
+pub fn weight(
+    factor: &Texture,
+    texture_coords: (f64, f64),
+    point: &Vector,
+    assets: &Assets,
+) -> f64 {
+    let weight = factor.value(texture_coords, point, assets).luminance();
+    float::min(1.0, float::max(0.0, weight))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::materials::phase::PhaseFunction;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn factor(value: f64) -> Texture {
+        Texture::Constant {
+            colour: Colour::new(value, value, value),
+        }
+    }
+
+    #[test]
+    fn test_choose_picks_b_in_proportion_to_factor() {
+        let a = Material::Isotropic {
+            albedo: factor(1.0),
+            phase: PhaseFunction::Isotropic,
+        };
+        let b = Material::Dielectric {
+            refractive_index: 1.5,
+            thin_film: None,
+            dispersion: None,
+        };
+        let assets = Assets::new(&[]).unwrap();
+        let origin = Vector::new(0.0, 0.0, 0.0);
+        let picks_b = |value: f64, num_of_samples: u32| {
+            (0..num_of_samples)
+                .filter(|_| {
+                    let chosen = choose(&a, &b, &factor(value), (0.5, 0.5), &origin, &assets);
+                    std::ptr::eq(chosen, &b)
+                })
+                .count()
+        };
+
+        assert_eq!(picks_b(0.0, 100), 0);
+        assert_eq!(picks_b(1.0, 100), 100);
+        assert_approx_eq!(picks_b(0.25, 20_000) as f64 / 20_000.0, 0.25, 0.015);
+    }
+
+    #[test]
+    fn test_emitted_blends_by_factor() {
+        let assets = Assets::new(&[]).unwrap();
+        let origin = Vector::new(0.0, 0.0, 0.0);
+        let a = Colour::new(4.0, 0.0, 0.0);
+        let b = Colour::new(0.0, 8.0, 0.0);
+
+        let blended = emitted(&a, &b, &factor(0.25), (0.5, 0.5), &origin, &assets);
+        assert_approx_eq!(blended.r(), 3.0);
+        assert_approx_eq!(blended.g(), 2.0);
+
+        // Factors outside [0, 1] are clamped
+        let blended = emitted(&a, &b, &factor(3.0), (0.5, 0.5), &origin, &assets);
+        assert_approx_eq!(blended.r(), 0.0);
+        assert_approx_eq!(blended.g(), 8.0);
+    }
+}
@@ -1,15 +1,30 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
 use crate::data::colour::Colour;
+use crate::data::spectrum;
 use crate::data::vector::Vector;
 use crate::pdf::Pdf;
 use crate::world::geometry::HitResult;
+use crate::world::materials::dispersion::Dispersion;
+use crate::world::materials::emission::EmissionProfile;
+use crate::world::materials::phase::PhaseFunction;
+use crate::world::materials::thin_film::ThinFilm;
 use crate::world::texture::Texture;
 
+mod bump;
+mod coated;
+mod cutout;
 mod dielectric;
+pub mod dispersion;
+pub mod emission;
 mod isotropic;
 mod lambertian;
+mod medium;
 mod metal;
+mod mix;
+pub mod phase;
+mod subsurface;
+pub mod thin_film;
 
 pub enum ScatterResult {
     Specular {
@@ -30,6 +45,34 @@ impl ScatterResult {
     pub fn diffuse(attenuation: Colour, pdf: Pdf<'static>) -> ScatterResult {
         ScatterResult::Diffuse { attenuation, pdf }
     }
+
+    /// Replaces the attenuation with its value at the given wavelength, for rays carrying light
+    /// of a single wavelength
+    pub fn at_wavelength(self, wavelength: Option<f64>) -> ScatterResult {
+        match self {
+            ScatterResult::Specular { attenuation, ray } => ScatterResult::Specular {
+                attenuation: spectrum::at_wavelength(&attenuation, wavelength),
+                ray,
+            },
+            ScatterResult::Diffuse { attenuation, pdf } => ScatterResult::Diffuse {
+                attenuation: spectrum::at_wavelength(&attenuation, wavelength),
+                pdf,
+            },
+        }
+    }
+
+    pub fn attenuate(self, factor: &Colour) -> ScatterResult {
+        match self {
+            ScatterResult::Specular { attenuation, ray } => ScatterResult::Specular {
+                attenuation: attenuation * factor,
+                ray,
+            },
+            ScatterResult::Diffuse { attenuation, pdf } => ScatterResult::Diffuse {
+                attenuation: attenuation * factor,
+                pdf,
+            },
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -40,23 +83,99 @@ pub enum Material {
     Metal {
         albedo: Colour,
         fuzz: f64,
+        thin_film: Option<ThinFilm>,
     },
     Dielectric {
         // Air: 1.0, Glass: 1.3-1.7, Diamond: 2.4
         refractive_index: f64,
+        thin_film: Option<ThinFilm>,
+        // Replaces the refractive index with one that depends on the wavelength, splitting
+        // white light into its colours
+        dispersion: Option<Dispersion>,
     },
     DiffuseLight {
         emit: Texture,
+        // Single sided lights only emit from the side the surface normal points to
+        #[serde(default)]
+        two_sided: bool,
+        #[serde(default)]
+        profile: EmissionProfile,
     },
+    // Scattering inside media, which is the same in every direction unless another phase function
+    // is given
     Isotropic {
         albedo: Texture,
+        #[serde(default)]
+        phase: PhaseFunction,
+    },
+    // Medium that absorbs, scatters and emits light by different amounts in each channel, e.g.
+    // fire, glowing gas and coloured liquids. Coefficients are per unit length at unit density,
+    // and the emission is the light a medium too thick to see through would glow with
+    Medium {
+        absorption: Colour,
+        scattering: Colour,
+        emission: Colour,
+        #[serde(default)]
+        phase: PhaseFunction,
+    },
+    Coated {
+        // Clear coat, e.g. varnish: 1.5, car paint clear coat: 1.5-1.6
+        refractive_index: f64,
+        // 0.0 is a perfect mirror finish, higher values give a satin finish
+        roughness: f64,
+        // Transmittance of a coat of unit thickness at normal incidence
+        tint: Colour,
+        thickness: f64,
+        inner: Box<Material>,
+    },
+    Mix {
+        a: Box<Material>,
+        b: Box<Material>,
+        // Probability of picking `b` at each hit, taken from the texture luminance
+        factor: Texture,
+    },
+    NormalMap {
+        inner: Box<Material>,
+        // Tangent space normals, usually an image asset
+        map: Texture,
+    },
+    BumpMap {
+        inner: Box<Material>,
+        // Height taken from the texture luminance
+        height: Texture,
+        scale: f64,
+    },
+    Cutout {
+        inner: Box<Material>,
+        // Taken from the texture luminance, usually the alpha channel of an image asset
+        opacity: Texture,
+        // Opacity below which the surface is fully transparent. Without one the opacity is
+        // used as the probability of a ray hitting the surface
+        threshold: Option<f64>,
+    },
+    Subsurface {
+        // Fraction of light kept at each scattering event inside the object
+        albedo: Colour,
+        // Average distance travelled between scattering events, e.g. small for marble and
+        // larger in the red channel for skin
+        mean_free_path: Colour,
+        refractive_index: f64,
     },
 }
 
 impl Material {
-    pub fn scattering_pdf(&self, surface_normal: &Vector, scattered: &Ray) -> f64 {
+    pub fn scattering_pdf(&self, hit: &HitResult, scattered: &Ray) -> f64 {
         match self {
-            Material::Lambertian { .. } => lambertian::scattering_pdf(surface_normal, scattered),
+            Material::Lambertian { .. } => {
+                lambertian::scattering_pdf(&hit.shading_face_normal(), scattered)
+            }
+            Material::Isotropic { phase, .. } | Material::Medium { phase, .. } => {
+                phase.value(hit.ray.direction(), scattered.direction())
+            }
+            Material::Coated { inner, .. } => inner.scattering_pdf(hit, scattered),
+            Material::NormalMap { inner, .. } => inner.scattering_pdf(hit, scattered),
+            Material::BumpMap { inner, .. } => inner.scattering_pdf(hit, scattered),
+            Material::Cutout { inner, .. } => inner.scattering_pdf(hit, scattered),
             _ => 1.0,
         }
     }
@@ -64,29 +183,178 @@ impl Material {
     pub fn scatter(&self, hit: &HitResult, assets: &Assets) -> Option<ScatterResult> {
         match self {
             Material::Lambertian { albedo } => lambertian::scatter(&albedo, hit, assets),
-            Material::Metal { albedo, fuzz } => metal::scatter(&albedo, *fuzz, hit),
-            Material::Dielectric { refractive_index } => {
-                dielectric::scatter(*refractive_index, hit)
-            }
+            Material::Metal {
+                albedo,
+                fuzz,
+                thin_film,
+            } => metal::scatter(&albedo, *fuzz, thin_film, hit),
+            Material::Dielectric {
+                refractive_index,
+                thin_film,
+                dispersion,
+            } => match dispersion {
+                Some(dispersion) => dielectric::scatter_dispersive(dispersion, thin_film, hit),
+                None => dielectric::scatter(*refractive_index, thin_film, hit),
+            },
             Material::DiffuseLight { .. } => None,
-            Material::Isotropic { albedo } => isotropic::scatter(&albedo, hit, assets),
+            Material::Isotropic { albedo, phase } => {
+                isotropic::scatter(&albedo, phase, hit, assets)
+            }
+            Material::Medium {
+                absorption,
+                scattering,
+                phase,
+                ..
+            } => medium::scatter(absorption, scattering, phase, hit),
+            Material::Coated {
+                refractive_index,
+                roughness,
+                tint,
+                thickness,
+                inner,
+            } => coated::scatter(
+                *refractive_index,
+                *roughness,
+                tint,
+                *thickness,
+                inner,
+                hit,
+                assets,
+            ),
+            Material::Mix { a, b, factor } => {
+                mix::choose(a, b, factor, hit.texture_coords, &hit.point, assets)
+                    .scatter(hit, assets)
+            }
+            Material::NormalMap { inner, map } => {
+                let shading_normal = bump::normal_from_map(map, hit, assets);
+                inner.scatter(
+                    &HitResult {
+                        shading_normal,
+                        ..hit.clone()
+                    },
+                    assets,
+                )
+            }
+            Material::BumpMap {
+                inner,
+                height,
+                scale,
+            } => {
+                let shading_normal = bump::normal_from_height(height, *scale, hit, assets);
+                inner.scatter(
+                    &HitResult {
+                        shading_normal,
+                        ..hit.clone()
+                    },
+                    assets,
+                )
+            }
+            Material::Cutout {
+                inner,
+                opacity,
+                threshold,
+            } => {
+                if cutout::is_transparent(
+                    opacity,
+                    *threshold,
+                    hit.texture_coords,
+                    &hit.point,
+                    assets,
+                ) {
+                    cutout::pass_through(hit)
+                } else {
+                    inner.scatter(hit, assets)
+                }
+            }
+            Material::Subsurface {
+                albedo,
+                mean_free_path,
+                refractive_index,
+            } => subsurface::scatter(albedo, mean_free_path, *refractive_index, hit),
         }
     }
 
-    pub fn emitted(
-        &self,
-        front_face: bool,
-        texture_coords: (f64, f64),
-        point: &Vector,
-        assets: &Assets,
-    ) -> Colour {
-        if !front_face {
-            return Colour::new(0.0, 0.0, 0.0);
+    pub fn emitted(&self, hit: &HitResult, assets: &Assets) -> Colour {
+        match self {
+            Material::DiffuseLight {
+                emit,
+                two_sided,
+                profile,
+            } => {
+                if !hit.front_face() && !two_sided {
+                    return Colour::new(0.0, 0.0, 0.0);
+                }
+
+                let cosine = -Vector::dot(&hit.ray.direction().unit_vector(), &hit.face_normal());
+                let scale = profile.scale(cosine);
+
+                match hit.ray.wavelength() {
+                    Some(wavelength) => {
+                        let value =
+                            emit.spectral_value(hit.texture_coords, &hit.point, wavelength, assets);
+                        Colour::new(scale * value, scale * value, scale * value)
+                    }
+                    None => scale * emit.value(hit.texture_coords, &hit.point, assets),
+                }
+            }
+            Material::Medium {
+                absorption,
+                scattering,
+                emission,
+                ..
+            } => medium::emitted(absorption, scattering, emission, hit),
+            Material::Coated { inner, .. } => inner.emitted(hit, assets),
+            Material::Mix { a, b, factor } => mix::emitted(
+                &a.emitted(hit, assets),
+                &b.emitted(hit, assets),
+                factor,
+                hit.texture_coords,
+                &hit.point,
+                assets,
+            ),
+            Material::NormalMap { inner, .. } => inner.emitted(hit, assets),
+            Material::BumpMap { inner, .. } => inner.emitted(hit, assets),
+            Material::Cutout { inner, .. } => inner.emitted(hit, assets),
+            _ => Colour::new(0.0, 0.0, 0.0),
         }
+    }
 
+    /// Rough luminance of the light emitted by a surface, taken at the middle of its texture
+    pub fn emitted_luminance(&self, point: &Vector, assets: &Assets) -> f64 {
         match self {
-            Material::DiffuseLight { emit } => emit.value(texture_coords, point, assets),
-            _ => Colour::new(0.0, 0.0, 0.0),
+            Material::DiffuseLight {
+                emit, two_sided, ..
+            } => {
+                let luminance = emit.value((0.5, 0.5), point, assets).luminance();
+                if *two_sided {
+                    2.0 * luminance
+                } else {
+                    luminance
+                }
+            }
+            Material::Coated { inner, .. } => inner.emitted_luminance(point, assets),
+            Material::Mix { a, b, factor } => {
+                let weight = mix::weight(factor, (0.5, 0.5), point, assets);
+                (1.0 - weight) * a.emitted_luminance(point, assets)
+                    + weight * b.emitted_luminance(point, assets)
+            }
+            Material::NormalMap { inner, .. } => inner.emitted_luminance(point, assets),
+            Material::BumpMap { inner, .. } => inner.emitted_luminance(point, assets),
+            Material::Cutout { inner, .. } => inner.emitted_luminance(point, assets),
+            _ => 0.0,
+        }
+    }
+
+    /// Whether light is emitted from both sides of the surface
+    pub fn is_two_sided(&self) -> bool {
+        match self {
+            Material::DiffuseLight { two_sided, .. } => *two_sided,
+            Material::Coated { inner, .. } => inner.is_two_sided(),
+            Material::Mix { a, b, .. } => a.is_two_sided() || b.is_two_sided(),
+            Material::NormalMap { inner, .. } => inner.is_two_sided(),
+            Material::BumpMap { inner, .. } => inner.is_two_sided(),
+            Material::Cutout { inner, .. } => inner.is_two_sided(),
+            _ => false,
         }
     }
 
@@ -96,6 +364,53 @@ impl Material {
                 albedo.validate(assets)?;
                 Ok(())
             }
+            Material::DiffuseLight { emit, profile, .. } => {
+                emit.validate(assets)?;
+                profile.validate()?;
+                Ok(())
+            }
+            Material::Coated { inner, .. } => inner.validate(assets),
+            Material::Mix { a, b, factor } => {
+                a.validate(assets)?;
+                b.validate(assets)?;
+                factor.validate(assets)?;
+                Ok(())
+            }
+            Material::NormalMap { inner, map } => {
+                inner.validate(assets)?;
+                map.validate(assets)?;
+                Ok(())
+            }
+            Material::BumpMap { inner, height, .. } => {
+                inner.validate(assets)?;
+                height.validate(assets)?;
+                Ok(())
+            }
+            Material::Cutout { inner, opacity, .. } => {
+                inner.validate(assets)?;
+                opacity.validate(assets)?;
+                Ok(())
+            }
+            Material::Isotropic { albedo, phase } => {
+                albedo.validate(assets)?;
+                phase.validate()?;
+                Ok(())
+            }
+            Material::Medium {
+                absorption,
+                scattering,
+                emission,
+                phase,
+            } => {
+                medium::validate(absorption, scattering, emission)?;
+                phase.validate()?;
+                Ok(())
+            }
+            Material::Subsurface {
+                albedo,
+                mean_free_path,
+                ..
+            } => Ok(subsurface::validate(albedo, mean_free_path)?),
             _ => Ok(()),
         }
     }
@@ -104,7 +419,230 @@ impl Material {
         match self {
             Material::DiffuseLight { .. } => true,
             Material::Dielectric { .. } => true,
+            Material::Coated { inner, .. } => inner.is_attractor(),
+            Material::Mix { a, b, .. } => a.is_attractor() || b.is_attractor(),
+            Material::NormalMap { inner, .. } => inner.is_attractor(),
+            Material::BumpMap { inner, .. } => inner.is_attractor(),
+            Material::Cutout { inner, .. } => inner.is_attractor(),
             _ => false,
         }
     }
+
+    /// Whether scattering happens inside a participating medium rather than off a surface
+    pub fn is_volumetric(&self) -> bool {
+        matches!(self, Material::Isotropic { .. } | Material::Medium { .. })
+    }
+
+    /// Extinction in each channel of a medium of unit density made of this material. Media that
+    /// scatter with an albedo stop every channel alike
+    pub fn extinction(&self) -> Colour {
+        match self {
+            Material::Medium {
+                absorption,
+                scattering,
+                ..
+            } => medium::extinction(absorption, scattering),
+            _ => Colour::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Extinction of a medium of unit density made of this material that collisions are sampled
+    /// against
+    pub fn majorant(&self) -> f64 {
+        medium::majorant(&self.extinction())
+    }
+
+    pub fn needs_resolving(&self) -> bool {
+        match self {
+            Material::Mix { .. } => true,
+            Material::NormalMap { .. } => true,
+            Material::BumpMap { .. } => true,
+            Material::Cutout { .. } => true,
+            Material::Coated { inner, .. } => inner.needs_resolving(),
+            _ => false,
+        }
+    }
+
+    /// Replaces any stochastic choice of material with the material picked for this hit and
+    /// applies any perturbation of the shading normal, so that `scatter`, `scattering_pdf` and
+    /// `emitted` all agree on which material was hit and how it is oriented. Returns `None` if
+    /// the hit was on a transparent part of the surface and the ray should carry on through it
+    pub fn resolve(self, hit: HitResult, assets: &Assets) -> Option<HitResult> {
+        match self {
+            Material::Mix { a, b, factor } => {
+                mix::choose(&a, &b, &factor, hit.texture_coords, &hit.point, assets)
+                    .clone()
+                    .resolve(hit, assets)
+            }
+            Material::Coated {
+                refractive_index,
+                roughness,
+                tint,
+                thickness,
+                inner,
+            } => {
+                let resolved = inner.resolve(hit, assets)?;
+                Some(HitResult {
+                    material: Material::Coated {
+                        refractive_index,
+                        roughness,
+                        tint,
+                        thickness,
+                        inner: Box::from(resolved.material),
+                    },
+                    ..resolved
+                })
+            }
+            Material::NormalMap { inner, map } => {
+                let shading_normal = bump::normal_from_map(&map, &hit, assets);
+                inner.resolve(
+                    HitResult {
+                        shading_normal,
+                        ..hit
+                    },
+                    assets,
+                )
+            }
+            Material::BumpMap {
+                inner,
+                height,
+                scale,
+            } => {
+                let shading_normal = bump::normal_from_height(&height, scale, &hit, assets);
+                inner.resolve(
+                    HitResult {
+                        shading_normal,
+                        ..hit
+                    },
+                    assets,
+                )
+            }
+            Material::Cutout {
+                inner,
+                opacity,
+                threshold,
+            } => {
+                if cutout::is_transparent(
+                    &opacity,
+                    threshold,
+                    hit.texture_coords,
+                    &hit.point,
+                    assets,
+                ) {
+                    None
+                } else {
+                    inner.resolve(hit, assets)
+                }
+            }
+            material => Some(HitResult { material, ..hit }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::tests::{floor_hit, white};
+    use assert_approx_eq::assert_approx_eq;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_normal_map_only_tilts_shading_normal() {
+        // Tangent space normal halfway between the tangent and the surface normal
+        let tilt = 0.5 * (1.0 + 0.5_f64.sqrt());
+        let normal_map = Material::NormalMap {
+            inner: Box::new(white()),
+            map: Texture::Constant {
+                colour: Colour::new(tilt, 0.5, tilt),
+            },
+        };
+        let assets = Assets::new(&[]).unwrap();
+        let hit = floor_hit(Vector::new(0.0, 1.0, 0.0), normal_map)
+            .resolve_material(&assets)
+            .unwrap();
+
+        assert!(hit.front_face());
+        assert_approx_eq!(hit.surface_normal.y(), 1.0);
+        assert_approx_eq!(
+            Vector::dot(&hit.shading_normal, &hit.tangent),
+            0.5_f64.sqrt()
+        );
+        assert_approx_eq!(hit.shading_normal.y(), 0.5_f64.sqrt());
+
+        // Diffuse scattering follows the shading normal
+        let along_shading_normal = hit.ray.continued(hit.point, hit.shading_normal);
+        assert_approx_eq!(
+            hit.material.scattering_pdf(&hit, &along_shading_normal),
+            1.0 / PI
+        );
+    }
+
+    #[test]
+    fn test_mix_resolves_to_either_material() {
+        let metal = Material::Metal {
+            albedo: Colour::new(0.5, 0.5, 0.5),
+            fuzz: 0.0,
+            thin_film: None,
+        };
+        let mix = Material::Mix {
+            a: Box::new(white()),
+            b: Box::new(metal.clone()),
+            factor: Texture::Constant {
+                colour: Colour::new(0.25, 0.25, 0.25),
+            },
+        };
+        let assets = Assets::new(&[]).unwrap();
+
+        // Once resolved, whichever material was picked scatters on its own
+        let num_of_samples = 20_000;
+        let mut num_of_reflections = 0;
+        for _ in 0..num_of_samples {
+            let hit = floor_hit(Vector::new(0.0, 1.0, 0.0), mix.clone())
+                .resolve_material(&assets)
+                .unwrap();
+            match hit.material.scatter(&hit, &assets) {
+                Some(ScatterResult::Specular { attenuation, .. }) => {
+                    assert_eq!(hit.material, metal);
+                    assert_approx_eq!(attenuation.r(), 0.5);
+                    num_of_reflections += 1;
+                }
+                Some(ScatterResult::Diffuse { attenuation, .. }) => {
+                    assert_eq!(hit.material, white());
+                    assert_approx_eq!(attenuation.r(), 1.0);
+                    let up = hit.ray.continued(hit.point, Vector::new(0.0, 1.0, 0.0));
+                    assert_approx_eq!(hit.material.scattering_pdf(&hit, &up), 1.0 / PI);
+                }
+                None => panic!("both materials scatter"),
+            }
+        }
+        assert_approx_eq!(
+            f64::from(num_of_reflections) / f64::from(num_of_samples),
+            0.25,
+            0.015
+        );
+    }
+
+    #[test]
+    fn test_mix_blends_emitted_luminance() {
+        let light = Material::DiffuseLight {
+            emit: Texture::Constant {
+                colour: Colour::new(4.0, 4.0, 4.0),
+            },
+            two_sided: false,
+            profile: EmissionProfile::Lambertian,
+        };
+        let mix = Material::Mix {
+            a: Box::new(white()),
+            b: Box::new(light),
+            factor: Texture::Constant {
+                colour: Colour::new(0.25, 0.25, 0.25),
+            },
+        };
+        let assets = Assets::new(&[]).unwrap();
+
+        // Lights are picked by power before the material is resolved, so the mix counts for as
+        // much light as it gives on average
+        let point = Vector::new(0.0, 0.0, 0.0);
+        assert_approx_eq!(mix.emitted_luminance(&point, &assets), 0.25 * 4.0);
+    }
 }
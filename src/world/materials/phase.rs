@@ -0,0 +1,192 @@
+//! Phase functions, which spread the light scattered inside a medium over directions depending on
+//! the angle between the direction the light was travelling in and the direction it leaves in.
+//! They are symmetric in the two directions, so light can be traced through media either way
+
+use crate::data::vector::Vector;
+use crate::onb::Onb;
+use crate::pdf::uniform;
+use std::f64::consts::PI;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PhaseFunctionError {
+    #[error("asymmetry <{0}> must be strictly between -1 and 1")]
+    InvalidAsymmetry(f64),
+    #[error("weight <{0}> of the forward lobe must be between 0 and 1")]
+    InvalidWeight(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum PhaseFunction {
+    // Light leaves in every direction alike, e.g. thin smoke
+    #[default]
+    Isotropic,
+    // Henyey and Greenstein (1941). Positive asymmetry scatters light forwards, e.g. 0.85 for
+    // clouds and fog, and negative asymmetry scatters it back towards where it came from
+    HenyeyGreenstein {
+        asymmetry: f64,
+    },
+    // Blend of a forward and a backward lobe, e.g. for clouds which both glow around the sun
+    // and shine back towards it
+    DoubleHenyeyGreenstein {
+        forward: f64,
+        backward: f64,
+        // Share of the light scattered by the forward lobe
+        forward_weight: f64,
+    },
+}
+
+impl PhaseFunction {
+    /// Density with respect to solid angle of light travelling in the incoming direction leaving
+    /// in the scattered direction, which is also the density of sampling it
+    pub fn value(&self, incoming: &Vector, scattered: &Vector) -> f64 {
+        let cosine = Vector::dot(&incoming.unit_vector(), &scattered.unit_vector());
+        match self {
+            PhaseFunction::Isotropic => 1.0 / (4.0 * PI),
+            PhaseFunction::HenyeyGreenstein { asymmetry } => henyey_greenstein(*asymmetry, cosine),
+            PhaseFunction::DoubleHenyeyGreenstein {
+                forward,
+                backward,
+                forward_weight,
+            } => {
+                forward_weight * henyey_greenstein(*forward, cosine)
+                    + (1.0 - forward_weight) * henyey_greenstein(*backward, cosine)
+            }
+        }
+    }
+
+    /// Scattered direction for light travelling in the incoming direction
+    pub fn generate(&self, incoming: &Vector) -> Vector {
+        let asymmetry = match self {
+            PhaseFunction::Isotropic => 0.0,
+            PhaseFunction::HenyeyGreenstein { asymmetry } => *asymmetry,
+            PhaseFunction::DoubleHenyeyGreenstein {
+                forward,
+                backward,
+                forward_weight,
+            } => {
+                if uniform::<f64>() < *forward_weight {
+                    *forward
+                } else {
+                    *backward
+                }
+            }
+        };
+
+        let cosine = sample_cosine(asymmetry);
+        let sine = f64::max(0.0, 1.0 - cosine * cosine).sqrt();
+        let phi = 2.0 * PI * uniform::<f64>();
+        Onb::build_from_w(incoming).local_from_vec(&Vector::new(
+            sine * phi.cos(),
+            sine * phi.sin(),
+            cosine,
+        ))
+    }
+
+    pub fn validate(&self) -> Result<(), PhaseFunctionError> {
+        let asymmetries = match self {
+            PhaseFunction::Isotropic => vec![],
+            PhaseFunction::HenyeyGreenstein { asymmetry } => vec![*asymmetry],
+            PhaseFunction::DoubleHenyeyGreenstein {
+                forward,
+                backward,
+                forward_weight,
+            } => {
+                if !(0.0..=1.0).contains(forward_weight) {
+                    return Err(PhaseFunctionError::InvalidWeight(*forward_weight));
+                }
+                vec![*forward, *backward]
+            }
+        };
+        match asymmetries.iter().find(|asymmetry| asymmetry.abs() >= 1.0) {
+            Some(asymmetry) => Err(PhaseFunctionError::InvalidAsymmetry(*asymmetry)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn henyey_greenstein(asymmetry: f64, cosine: f64) -> f64 {
+    let g = asymmetry;
+    let denominator = 1.0 + g * g - 2.0 * g * cosine;
+    (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+}
+
+/// Cosine of the angle to the incoming direction, inverting the cumulative distribution of the
+/// Henyey-Greenstein phase function
+fn sample_cosine(asymmetry: f64) -> f64 {
+    let g = asymmetry;
+    let random = uniform::<f64>();
+    if g.abs() < 0.001 {
+        return 1.0 - 2.0 * random;
+    }
+    let root = (1.0 - g * g) / (1.0 + g - 2.0 * g * random);
+    (1.0 + g * g - root * root) / (2.0 * g)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_phase_functions_integrate_to_one() {
+        let incoming = Vector::new(0.0, 0.0, 1.0);
+        let phase_functions = [
+            PhaseFunction::Isotropic,
+            PhaseFunction::HenyeyGreenstein { asymmetry: 0.7 },
+            PhaseFunction::DoubleHenyeyGreenstein {
+                forward: 0.8,
+                backward: -0.4,
+                forward_weight: 0.6,
+            },
+        ];
+
+        let num_of_steps = 10_000;
+        for phase in phase_functions.iter() {
+            // Integrating over the angle to the incoming direction, with dω = 2π sin(θ) dθ
+            let step = PI / f64::from(num_of_steps);
+            let integral: f64 = (0..num_of_steps)
+                .map(|i| {
+                    let theta = (f64::from(i) + 0.5) * step;
+                    let scattered = Vector::new(theta.sin(), 0.0, theta.cos());
+                    phase.value(&incoming, &scattered) * 2.0 * PI * theta.sin() * step
+                })
+                .sum();
+            assert_approx_eq!(integral, 1.0, 0.001);
+        }
+    }
+
+    #[test]
+    fn test_henyey_greenstein_samples_have_mean_cosine_of_asymmetry() {
+        let incoming = Vector::new(1.0, 2.0, -1.0);
+        let phase = PhaseFunction::HenyeyGreenstein { asymmetry: -0.6 };
+
+        let num_of_samples = 20_000;
+        let mean: f64 = (0..num_of_samples)
+            .map(|_| {
+                let scattered = phase.generate(&incoming);
+                assert_approx_eq!(scattered.len(), 1.0);
+                Vector::dot(&scattered, &incoming.unit_vector())
+            })
+            .sum::<f64>()
+            / f64::from(num_of_samples);
+        assert_approx_eq!(mean, -0.6, 0.02);
+    }
+
+    #[test]
+    fn test_phase_function_validation() {
+        assert!(PhaseFunction::HenyeyGreenstein { asymmetry: 1.0 }
+            .validate()
+            .is_err());
+        assert!(PhaseFunction::DoubleHenyeyGreenstein {
+            forward: 0.5,
+            backward: -0.5,
+            forward_weight: 1.5,
+        }
+        .validate()
+        .is_err());
+        assert!(PhaseFunction::HenyeyGreenstein { asymmetry: -0.9 }
+            .validate()
+            .is_ok());
+    }
+}
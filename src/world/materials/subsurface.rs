@@ -0,0 +1,152 @@
+//! Random walk subsurface scattering. Light refracts into the object as with a dielectric and
+//! then scatters through its volume until it finds its way back out through the surface.
+//! Every hit on the inside of the surface is the end of one step of the walk, so the walk is
+//! bounded by the object's own geometry
+
+use crate::data::colour::Colour;
+use crate::pdf::{random_point_in_unit_sphere, uniform};
+use crate::world::geometry::medium::sample_free_flight;
+use crate::world::geometry::HitResult;
+use crate::world::materials::{dielectric, ScatterResult};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SubsurfaceError {
+    #[error("albedo <{0:?}> must be between 0 and 1 in every channel")]
+    InvalidAlbedo(Colour),
+    #[error("mean free path <{0:?}> must be positive in every channel")]
+    InvalidMeanFreePath(Colour),
+}
+
+pub fn scatter(
+    albedo: &Colour,
+    mean_free_path: &Colour,
+    refractive_index: f64,
+    hit: &HitResult,
+) -> Option<ScatterResult> {
+    if hit.front_face() {
+        return dielectric::scatter(refractive_index, &None, hit);
+    }
+
+    // The ray started inside the object, either after refracting in or at a previous
+    // scattering event, and travelled to this point on the surface
+    let density = density(mean_free_path);
+    let distance_to_surface = hit.distance * hit.ray.direction().len();
+    let distance = sample_free_flight(hero_channel(&density));
+
+    if distance >= distance_to_surface {
+        return dielectric::scatter(refractive_index, &None, hit).map(|scatter| {
+            scatter.attenuate(&transmittance_weight(&density, distance_to_surface))
+        });
+    }
+
+    let point = hit.ray.point(distance / hit.ray.direction().len());
+    let ray = hit.ray.continued(point, random_point_in_unit_sphere());
+    Some(ScatterResult::specular(
+        scattering_weight(albedo, &density, distance),
+        ray,
+    ))
+}
+
+pub fn validate(albedo: &Colour, mean_free_path: &Colour) -> Result<(), SubsurfaceError> {
+    let channels = |colour: &Colour| [colour.r(), colour.g(), colour.b()];
+    if channels(albedo).iter().any(|c| !(0.0..=1.0).contains(c)) {
+        return Err(SubsurfaceError::InvalidAlbedo(*albedo));
+    }
+    if channels(mean_free_path).iter().any(|&c| c <= 0.0) {
+        return Err(SubsurfaceError::InvalidMeanFreePath(*mean_free_path));
+    }
+    Ok(())
+}
+
+fn density(mean_free_path: &Colour) -> Colour {
+    Colour::new(
+        1.0 / mean_free_path.r(),
+        1.0 / mean_free_path.g(),
+        1.0 / mean_free_path.b(),
+    )
+}
+
+fn hero_channel(density: &Colour) -> f64 {
+    // The distance is sampled using the density of a single channel picked at random. The
+    // weights below account for all channels that could have been picked
+    let channel: f64 = uniform();
+    if channel < 1.0 / 3.0 {
+        density.r()
+    } else if channel < 2.0 / 3.0 {
+        density.g()
+    } else {
+        density.b()
+    }
+}
+
+fn transmittance(density: &Colour, distance: f64) -> Colour {
+    Colour::new(
+        f64::exp(-density.r() * distance),
+        f64::exp(-density.g() * distance),
+        f64::exp(-density.b() * distance),
+    )
+}
+
+fn average(colour: &Colour) -> f64 {
+    (colour.r() + colour.g() + colour.b()) / 3.0
+}
+
+fn transmittance_weight(density: &Colour, distance: f64) -> Colour {
+    // Probability of reaching the surface averaged over the channels
+    let transmittance = transmittance(density, distance);
+    let pdf = average(&transmittance);
+    transmittance / pdf
+}
+
+fn scattering_weight(albedo: &Colour, density: &Colour, distance: f64) -> Colour {
+    // Probability density of scattering at this distance averaged over the channels
+    let scattering = density * transmittance(density, distance);
+    let pdf = average(&scattering);
+    albedo * scattering / pdf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn assert_colour_eq(actual: &Colour, expected: &Colour) {
+        assert_approx_eq!(actual.r(), expected.r());
+        assert_approx_eq!(actual.g(), expected.g());
+        assert_approx_eq!(actual.b(), expected.b());
+    }
+
+    #[test]
+    fn test_grey_medium_weights() {
+        let albedo = Colour::new(0.9, 0.5, 0.1);
+        let density = Colour::new(2.0, 2.0, 2.0);
+
+        assert_colour_eq(
+            &scattering_weight(&albedo, &density, 0.3),
+            &Colour::new(0.9, 0.5, 0.1),
+        );
+        assert_colour_eq(
+            &transmittance_weight(&density, 0.3),
+            &Colour::new(1.0, 1.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn test_coloured_medium_favours_sparse_channels() {
+        let density = Colour::new(0.5, 1.0, 4.0);
+        let weight = transmittance_weight(&density, 1.0);
+
+        assert!(weight.r() > weight.g());
+        assert!(weight.g() > weight.b());
+    }
+
+    #[test]
+    fn test_validate() {
+        let albedo = Colour::new(0.9, 0.5, 0.1);
+        assert!(validate(&albedo, &Colour::new(1.0, 0.5, 0.1)).is_ok());
+        assert!(validate(&albedo, &Colour::new(1.0, 0.0, 0.1)).is_err());
+        assert!(validate(&albedo, &Colour::new(1.0, 0.5, -0.1)).is_err());
+        assert!(validate(&Colour::new(1.2, 0.5, 0.1), &Colour::new(1.0, 1.0, 1.0)).is_err());
+    }
+}
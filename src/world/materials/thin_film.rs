@@ -0,0 +1,143 @@
+//! Reflectance of surfaces covered by a thin transparent film, e.g. soap bubbles, oil slicks
+//! and anodised metals. Light reflected off the top and the bottom of the film interferes,
+//! so the reflectance depends on the wavelength. It is evaluated at one representative
+//! wavelength per colour channel
+
+use crate::data::colour::Colour;
+use std::f64::consts::PI;
+
+// Wavelengths in nm used for the red, green and blue channels
+const CHANNEL_WAVELENGTHS: [f64; 3] = [650.0, 510.0, 475.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ThinFilm {
+    // In nm. Interference colours are strongest between 100 and 1000 nm
+    thickness: f64,
+    // Soap film: 1.33, oil: 1.45-1.5, anodised titanium oxide: 2.2-2.5
+    refractive_index: f64,
+}
+
+impl ThinFilm {
+    /// Reflectance of a film over a dielectric with the given refractive index for light
+    /// arriving from air
+    pub fn dielectric_reflectance(&self, cosine: f64, refractive_index: f64) -> Colour {
+        self.reflectance(cosine, |cosine_film, _| {
+            let n_f = self.refractive_index;
+            let n_t = refractive_index;
+
+            let sin_squared = (1.0 - cosine * cosine) / (n_t * n_t);
+            if sin_squared >= 1.0 {
+                // Total internal reflection at the bottom of the film
+                return (1.0, 1.0);
+            }
+            let cosine_t = f64::sqrt(1.0 - sin_squared);
+
+            let r_s = (n_f * cosine_film - n_t * cosine_t) / (n_f * cosine_film + n_t * cosine_t);
+            let r_p = (n_t * cosine_film - n_f * cosine_t) / (n_t * cosine_film + n_f * cosine_t);
+            (r_s, r_p)
+        })
+    }
+
+    /// Reflectance of a film over a conductor. The conductor is approximated as reflecting the
+    /// given albedo with the phase shift of a perfect conductor
+    pub fn conductor_reflectance(&self, cosine: f64, albedo: &Colour) -> Colour {
+        let amplitudes = [
+            -f64::sqrt(albedo.r()),
+            -f64::sqrt(albedo.g()),
+            -f64::sqrt(albedo.b()),
+        ];
+        self.reflectance(cosine, |_, channel| {
+            (amplitudes[channel], amplitudes[channel])
+        })
+    }
+
+    fn reflectance<F>(&self, cosine: f64, substrate_amplitudes: F) -> Colour
+    where
+        F: Fn(f64, usize) -> (f64, f64),
+    {
+        let n_f = self.refractive_index;
+        let sin_squared = (1.0 - cosine * cosine) / (n_f * n_f);
+        let cosine_film = f64::sqrt(f64::max(0.0, 1.0 - sin_squared));
+
+        // Amplitudes for light reflected off the top of the film for s and p polarisations
+        let top_s = (cosine - n_f * cosine_film) / (cosine + n_f * cosine_film);
+        let top_p = (n_f * cosine - cosine_film) / (n_f * cosine + cosine_film);
+
+        let channel = |channel: usize| {
+            let (bottom_s, bottom_p) = substrate_amplitudes(cosine_film, channel);
+            let phase =
+                4.0 * PI * n_f * self.thickness * cosine_film / CHANNEL_WAVELENGTHS[channel];
+
+            let s = airy_reflectance(top_s, bottom_s, phase);
+            let p = airy_reflectance(top_p, bottom_p, phase);
+            (s + p) / 2.0
+        };
+
+        Colour::new(channel(0), channel(1), channel(2))
+    }
+}
+
+fn airy_reflectance(top: f64, bottom: f64, phase: f64) -> f64 {
+    // Sum over all the reflections inside the film
+    let cross = 2.0 * top * bottom * f64::cos(phase);
+    let numerator = top * top + bottom * bottom + cross;
+    let denominator = 1.0 + top * top * bottom * bottom + cross;
+    numerator / denominator
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn fresnel_reflectance(cosine: f64, refractive_index: f64) -> f64 {
+        let n = refractive_index;
+        let cosine_t = f64::sqrt(1.0 - (1.0 - cosine * cosine) / (n * n));
+        let r_s = (cosine - n * cosine_t) / (cosine + n * cosine_t);
+        let r_p = (n * cosine - cosine_t) / (n * cosine + cosine_t);
+        (r_s * r_s + r_p * r_p) / 2.0
+    }
+
+    #[test]
+    fn test_film_without_thickness_has_no_effect() {
+        let film = ThinFilm {
+            thickness: 0.0,
+            refractive_index: 1.33,
+        };
+
+        for &cosine in [1.0, 0.7, 0.2].iter() {
+            let reflectance = film.dielectric_reflectance(cosine, 1.5);
+            let expected = fresnel_reflectance(cosine, 1.5);
+
+            assert_approx_eq!(reflectance.r(), expected);
+            assert_approx_eq!(reflectance.g(), expected);
+            assert_approx_eq!(reflectance.b(), expected);
+        }
+    }
+
+    #[test]
+    fn test_soap_film_reflectance_depends_on_wavelength() {
+        let film = ThinFilm {
+            thickness: 300.0,
+            refractive_index: 1.33,
+        };
+        let reflectance = film.dielectric_reflectance(1.0, 1.0);
+
+        assert!((reflectance.r() - reflectance.g()).abs() > 0.01);
+        assert!((reflectance.g() - reflectance.b()).abs() > 0.01);
+    }
+
+    #[test]
+    fn test_conductor_film_keeps_reflectance_in_range() {
+        let film = ThinFilm {
+            thickness: 250.0,
+            refractive_index: 2.4,
+        };
+        let reflectance = film.conductor_reflectance(0.5, &Colour::new(0.9, 0.8, 0.7));
+
+        for &channel in [reflectance.r(), reflectance.g(), reflectance.b()].iter() {
+            assert!(channel >= 0.0);
+            assert!(channel <= 1.0);
+        }
+    }
+}
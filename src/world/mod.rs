@@ -1,23 +1,42 @@
 use crate::data::assets::Assets;
 use crate::world::background::Background;
+use crate::world::fog::Fog;
 use crate::world::geometry::{Geometry, Hittable};
+use crate::world::lights::Light;
 
 pub mod background;
+pub mod fog;
 pub mod geometry;
+pub mod lights;
 pub mod materials;
+pub mod sky;
 pub mod texture;
 
 #[derive(Serialize, Deserialize)]
 pub struct WorldSave {
+    #[serde(deserialize_with = "Background::deserialize_any_format")]
     background: Background,
     geometries: Vec<Geometry>,
+    #[serde(default)]
+    lights: Vec<Light>,
+    #[serde(default)]
+    fog: Option<Fog>,
 }
 
 impl WorldSave {
-    pub fn new(background: Background, geometries: Vec<Geometry>) -> WorldSave {
+    pub fn new(background: Background, geometries: Vec<Geometry>, lights: Vec<Light>) -> WorldSave {
         WorldSave {
             background,
             geometries,
+            lights,
+            fog: None,
+        }
+    }
+
+    pub fn with_fog(self, fog: Fog) -> WorldSave {
+        WorldSave {
+            fog: Some(fog),
+            ..self
         }
     }
 
@@ -25,6 +44,14 @@ impl WorldSave {
         &self.background
     }
 
+    pub fn lights(&self) -> &Vec<Light> {
+        &self.lights
+    }
+
+    pub fn fog(&self) -> Option<&Fog> {
+        self.fog.as_ref()
+    }
+
     pub fn drain_geometries(&mut self) -> Vec<Geometry> {
         if self.geometries.is_empty() {
             panic!("Geometries have already been drained")
@@ -34,6 +61,10 @@ impl WorldSave {
     }
 
     pub fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.background.validate(assets)?;
+        if let Some(fog) = &self.fog {
+            fog.validate()?;
+        }
         for geometry in &self.geometries {
             geometry.validate(assets)?
         }
@@ -0,0 +1,258 @@
+//! Clear daylight sky using the analytic model of Preetham, Shirley and Smits, "A Practical
+//! Analytic Model for Daylight" (1999), together with a sun disk attenuated by the same
+//! atmosphere. Luminances follow the scale used for colour temperatures, i.e. a scene luminance
+//! of 1.0 is 683 cd / m²
+
+use crate::data::colour::Colour;
+use crate::data::spectrum::{
+    planck, wavelength_weight, xyz_to_rgb, WAVELENGTH_MAX, WAVELENGTH_MIN,
+};
+use crate::data::vector::Vector;
+use crate::float;
+use std::f64::consts::PI;
+
+// Luminance in cd / m² of a scene luminance of 1.0
+const CANDELA_PER_UNIT: f64 = 683.0;
+// Luminance of the sun outside of the atmosphere in cd / m²
+const SUN_LUMINANCE: f64 = 1.96e9;
+const SUN_TEMPERATURE: f64 = 5778.0;
+pub const SUN_ANGULAR_DIAMETER: f64 = 0.53;
+// Number of wavelengths used to integrate the colour of the sun
+const SUN_COLOUR_STEPS: usize = 40;
+// The model breaks down for rays below the horizon, which see the horizon instead
+const HORIZON_COSINE: f64 = 0.01;
+
+/// Parameters of a sky as given in configs, see `Sky::new`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SkySave {
+    sun_elevation: f64,
+    sun_azimuth: f64,
+    turbidity: f64,
+}
+
+/// Sky worked out from its parameters once, when it is read from a config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "SkySave", into = "SkySave")]
+pub struct Sky {
+    save: SkySave,
+    sun_direction: Vector,
+    // Coefficients A to E of the Perez distribution for Y, x and y
+    perez: [[f64; 5]; 3],
+    // Y, x and y at the zenith divided by the Perez distribution there
+    zenith: [f64; 3],
+    // Radiance of the sun disk
+    sun: Colour,
+}
+
+impl From<SkySave> for Sky {
+    fn from(save: SkySave) -> Sky {
+        Sky::new(save.sun_elevation, save.sun_azimuth, save.turbidity)
+    }
+}
+
+impl From<Sky> for SkySave {
+    fn from(sky: Sky) -> SkySave {
+        sky.save
+    }
+}
+
+impl Sky {
+    /// Angles are in degrees. The azimuth is measured about the y axis from +z towards +x and
+    /// the turbidity goes from about 2 for a very clear sky to 10 for a hazy one
+    pub fn new(sun_elevation: f64, sun_azimuth: f64, turbidity: f64) -> Sky {
+        let sun_direction = from_angles(sun_elevation.to_radians(), sun_azimuth.to_radians());
+        // Keep the sun just above the horizon, where the model is still valid
+        let theta_sun = float::min(
+            f64::acos(HORIZON_COSINE),
+            PI / 2.0 - sun_elevation.to_radians(),
+        );
+        let theta_sun = float::max(0.0, theta_sun);
+        let t = turbidity;
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+        // In kcd / m²
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let zenith_x = zenith_chromaticity(
+            t,
+            theta_sun,
+            [
+                [0.00166, -0.00375, 0.00209, 0.0],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886],
+            ],
+        );
+        let zenith_y = zenith_chromaticity(
+            t,
+            theta_sun,
+            [
+                [0.00275, -0.00610, 0.00317, 0.0],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688],
+            ],
+        );
+
+        let zenith = [
+            zenith_luminance * 1000.0 / perez_function(&perez[0], 1.0, theta_sun),
+            zenith_x / perez_function(&perez[1], 1.0, theta_sun),
+            zenith_y / perez_function(&perez[2], 1.0, theta_sun),
+        ];
+
+        Sky {
+            save: SkySave {
+                sun_elevation,
+                sun_azimuth,
+                turbidity,
+            },
+            sun_direction,
+            perez,
+            zenith,
+            sun: sun_colour(theta_sun, turbidity),
+        }
+    }
+
+    pub fn sun_direction(&self) -> &Vector {
+        &self.sun_direction
+    }
+
+    /// Radiance of the sky, including the sun disk, seen along a direction
+    pub fn value(&self, direction: &Vector) -> Colour {
+        let direction = direction.unit_vector();
+        let cos_gamma = Vector::dot(&direction, &self.sun_direction);
+
+        let sun = if cos_gamma >= sun_cos_max() && direction.y() > 0.0 {
+            self.sun
+        } else {
+            Colour::new(0.0, 0.0, 0.0)
+        };
+
+        self.sky_value(&direction, cos_gamma) + sun
+    }
+
+    /// Radiance of the sky without the sun disk
+    pub fn sky_value(&self, direction: &Vector, cos_gamma: f64) -> Colour {
+        let cos_theta = float::max(HORIZON_COSINE, direction.y());
+        let gamma = float::min(1.0, float::max(-1.0, cos_gamma)).acos();
+
+        let luminance = self.zenith[0] * perez_function(&self.perez[0], cos_theta, gamma);
+        let x = self.zenith[1] * perez_function(&self.perez[1], cos_theta, gamma);
+        let y = self.zenith[2] * perez_function(&self.perez[2], cos_theta, gamma);
+
+        let luminance = float::max(0.0, luminance) / CANDELA_PER_UNIT;
+        xyz_to_rgb(x / y * luminance, luminance, (1.0 - x - y) / y * luminance)
+    }
+}
+
+pub fn sun_cos_max() -> f64 {
+    f64::cos(SUN_ANGULAR_DIAMETER.to_radians() / 2.0)
+}
+
+fn from_angles(elevation: f64, azimuth: f64) -> Vector {
+    Vector::new(
+        elevation.cos() * azimuth.sin(),
+        elevation.sin(),
+        elevation.cos() * azimuth.cos(),
+    )
+}
+
+fn zenith_chromaticity(turbidity: f64, theta_sun: f64, coefficients: [[f64; 4]; 3]) -> f64 {
+    let thetas = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.0];
+    let polynomial = |row: &[f64; 4]| -> f64 { row.iter().zip(&thetas).map(|(c, t)| c * t).sum() };
+
+    turbidity * turbidity * polynomial(&coefficients[0])
+        + turbidity * polynomial(&coefficients[1])
+        + polynomial(&coefficients[2])
+}
+
+/// Perez distribution for a direction with the given cosine to the zenith and angle to the sun
+fn perez_function(coefficients: &[f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+    let [a, b, c, d, e] = *coefficients;
+
+    (1.0 + a * f64::exp(b / cos_theta)) * (1.0 + c * f64::exp(d * gamma) + e * gamma.cos().powi(2))
+}
+
+/// Radiance of the sun disk after Rayleigh and aerosol extinction, from the appendix of the paper
+fn sun_colour(theta_sun: f64, turbidity: f64) -> Colour {
+    // Relative optical mass of the atmosphere along the path of the sunlight
+    let optical_mass =
+        1.0 / (theta_sun.cos() + 0.15 * f64::powf(93.885 - theta_sun.to_degrees(), -1.253));
+    let angstrom_beta = 0.046_08 * turbidity - 0.045_86;
+    let angstrom_alpha = 1.3;
+
+    let range = WAVELENGTH_MAX - WAVELENGTH_MIN;
+    let mut colour = Colour::new(0.0, 0.0, 0.0);
+    let mut unattenuated = 0.0;
+    for step in 0..SUN_COLOUR_STEPS {
+        let t = (step as f64 + 0.5) / SUN_COLOUR_STEPS as f64;
+        let wavelength = WAVELENGTH_MIN + t * range;
+        let micrometres = wavelength / 1000.0;
+
+        let rayleigh = f64::exp(-0.008_735 * micrometres.powf(-4.08) * optical_mass);
+        let aerosol = f64::exp(-angstrom_beta * micrometres.powf(-angstrom_alpha) * optical_mass);
+
+        let weighted = planck(wavelength, SUN_TEMPERATURE) * wavelength_weight(wavelength);
+        colour = colour + rayleigh * aerosol * weighted;
+        unattenuated += weighted.luminance();
+    }
+
+    // Scale so that the sun has its full luminance without any atmosphere
+    SUN_LUMINANCE / CANDELA_PER_UNIT * colour / unattenuated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sky_is_brighter_towards_the_sun() {
+        let sky = Sky::new(30.0, 90.0, 3.0);
+
+        let towards_sun = sky.sky_value(&from_angles(0.6, 90_f64.to_radians()), 0.9);
+        let away_from_sun = sky.sky_value(&from_angles(0.6, -90_f64.to_radians()), -0.5);
+
+        assert!(towards_sun.luminance() > away_from_sun.luminance());
+        assert!(away_from_sun.b() > away_from_sun.r());
+    }
+
+    #[test]
+    fn test_zenith_luminance() {
+        // Around 8 kcd / m² for the sun at 60° with a clear sky
+        let sky = Sky::new(60.0, 0.0, 2.0);
+        let zenith = sky.sky_value(&Vector::new(0.0, 1.0, 0.0), (30_f64.to_radians()).cos());
+
+        let candela = zenith.luminance() * CANDELA_PER_UNIT;
+        assert!(candela > 4000.0 && candela < 12000.0);
+    }
+
+    #[test]
+    fn test_low_sun_is_redder() {
+        let high = sun_colour(20_f64.to_radians(), 3.0);
+        let low = sun_colour(85_f64.to_radians(), 3.0);
+
+        assert!(low.r() / low.b() > high.r() / high.b());
+        assert!(low.luminance() < high.luminance());
+    }
+}
@@ -1,10 +1,13 @@
 pub mod perlin;
+pub mod temperature;
 
 use crate::data::assets::Assets;
 use crate::data::colour::Colour;
 use crate::data::image::Image;
+use crate::data::spectrum::{rgb_to_spectrum, Spectrum};
 use crate::data::vector::Vector;
 use crate::world::texture::perlin::{perlin_turbulence, NoiseConfig};
+use crate::world::texture::temperature::Temperature;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Texture {
@@ -24,6 +27,20 @@ pub enum Texture {
     Image {
         asset_name: String,
     },
+    // Grey colour taken from the alpha channel of an image asset, e.g. for cutout masks
+    Alpha {
+        asset_name: String,
+    },
+    // Measured emission spectrum, used in place of a colour for lights
+    Spectrum {
+        spectrum: Spectrum,
+        scale: f64,
+    },
+    // Blackbody emission, e.g. candles: 1900, incandescent bulbs: 2700, daylight: 6500
+    Temperature {
+        #[serde(flatten)]
+        temperature: Temperature,
+    },
 }
 
 impl Texture {
@@ -43,15 +60,38 @@ impl Texture {
                 let image = assets.get_asset(asset_name);
                 image_texture(image, texture_coords)
             }
+            Texture::Alpha { asset_name } => {
+                let image = assets.get_asset(asset_name);
+                alpha_texture(image, texture_coords)
+            }
+            Texture::Spectrum { spectrum, scale } => *scale * spectrum.to_rgb(),
+            Texture::Temperature { temperature } => temperature.colour(),
+        }
+    }
+
+    /// Value of the spectrum of the texture at the given wavelength
+    pub fn spectral_value(
+        &self,
+        texture_coords: (f64, f64),
+        point: &Vector,
+        wavelength: f64,
+        assets: &Assets,
+    ) -> f64 {
+        match self {
+            Texture::Spectrum { spectrum, scale } => *scale * spectrum.value(wavelength),
+            Texture::Temperature { temperature } => temperature.spectral_value(wavelength),
+            _ => rgb_to_spectrum(&self.value(texture_coords, point, assets), wavelength),
         }
     }
 
     pub fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
         match self {
-            Texture::Image { asset_name } => {
+            Texture::Image { asset_name } | Texture::Alpha { asset_name } => {
                 assets.validate(&asset_name)?;
                 Ok(())
             }
+            Texture::Spectrum { spectrum, .. } => Ok(spectrum.validate()?),
+            Texture::Temperature { temperature } => Ok(temperature.validate()?),
             _ => Ok(()),
         }
     }
@@ -87,6 +127,17 @@ fn noise_texture(
 }
 
 fn image_texture(image: &Image, texture_coords: (f64, f64)) -> Colour {
+    let (row, col) = image_coords(image, texture_coords);
+    *image.get_pixel(row, col)
+}
+
+fn alpha_texture(image: &Image, texture_coords: (f64, f64)) -> Colour {
+    let (row, col) = image_coords(image, texture_coords);
+    let alpha = image.get_alpha(row, col);
+    Colour::new(alpha, alpha, alpha)
+}
+
+fn image_coords(image: &Image, texture_coords: (f64, f64)) -> (u32, u32) {
     let height = image.height() as f64;
     let width = image.width() as f64;
 
@@ -99,5 +150,5 @@ fn image_texture(image: &Image, texture_coords: (f64, f64)) -> Colour {
     let row = f64::min(height - 1.0, row);
     let col = f64::min(width - 1.0, col);
 
-    *image.get_pixel(row as u32, col as u32)
+    (row as u32, col as u32)
 }
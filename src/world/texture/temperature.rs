@@ -0,0 +1,231 @@
+//! Emission of lights specified by a colour temperature and an intensity, so that they can be
+//! matched to real bulbs and daylight. A scene colour of luminance 1.0 corresponds to the
+//! luminance of a radiance of 1 W / (sr m²) at 555 nm, i.e. 683 cd / m², with scene units in m
+
+use crate::data::colour::Colour;
+use crate::data::spectrum::{planck, xyz, Spectrum, WAVELENGTH_MAX, WAVELENGTH_MIN};
+use std::f64::consts::PI;
+use thiserror::Error;
+
+// Luminous efficacy of light at 555 nm in lm / W
+const MAXIMUM_LUMINOUS_EFFICACY: f64 = 683.0;
+// In W / (m² K⁴)
+const STEFAN_BOLTZMANN: f64 = 5.670_374_419e-8;
+// Number of wavelengths used to integrate over the visible range
+const LUMINANCE_STEPS: usize = 40;
+
+#[derive(Debug, Error)]
+pub enum TemperatureError {
+    #[error("colour temperature <{0}> must be positive")]
+    InvalidTemperature(f64),
+    #[error("emitting area <{0}> must be positive")]
+    InvalidArea(f64),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Intensity {
+    // Luminance in scene units, i.e. that of an RGB light of the same brightness
+    Luminance { luminance: f64 },
+    // Radiant flux of a light with the given emitting area in m². Most of the power of colder
+    // blackbodies is infrared, so they need far more watts for the same brightness
+    Watts { watts: f64, area: f64 },
+    // Luminous flux of a light with the given emitting area in m², as listed for bulbs
+    Lumens { lumens: f64, area: f64 },
+}
+
+/// Parameters of a colour temperature as given in configs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemperatureSave {
+    kelvin: f64,
+    intensity: Intensity,
+}
+
+/// Emission of a colour temperature worked out once, when it is read from a config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "TemperatureSave", into = "TemperatureSave")]
+pub struct Temperature {
+    save: TemperatureSave,
+    colour: Colour,
+    // Scale of the blackbody spectrum, relative to its peak, by the same factor as the colour so
+    // that both modes agree on the brightness
+    spectral_scale: f64,
+}
+
+impl From<TemperatureSave> for Temperature {
+    fn from(save: TemperatureSave) -> Temperature {
+        Temperature::new(save.kelvin, save.intensity)
+    }
+}
+
+impl From<Temperature> for TemperatureSave {
+    fn from(temperature: Temperature) -> TemperatureSave {
+        temperature.save
+    }
+}
+
+impl Temperature {
+    pub fn new(kelvin: f64, intensity: Intensity) -> Temperature {
+        let colour = Spectrum::Blackbody {
+            temperature: kelvin,
+        }
+        .to_rgb();
+        let spectral_scale = luminance(kelvin, &intensity) / colour.luminance();
+        Temperature {
+            save: TemperatureSave { kelvin, intensity },
+            colour: spectral_scale * colour,
+            spectral_scale,
+        }
+    }
+
+    pub fn colour(&self) -> Colour {
+        self.colour
+    }
+
+    pub fn spectral_value(&self, wavelength: f64) -> f64 {
+        let spectrum = Spectrum::Blackbody {
+            temperature: self.save.kelvin,
+        };
+        self.spectral_scale * spectrum.value(wavelength)
+    }
+
+    pub fn validate(&self) -> Result<(), TemperatureError> {
+        let kelvin = self.save.kelvin;
+        if kelvin <= 0.0 {
+            return Err(TemperatureError::InvalidTemperature(kelvin));
+        }
+        match self.save.intensity {
+            Intensity::Watts { area, .. } | Intensity::Lumens { area, .. } if area <= 0.0 => {
+                Err(TemperatureError::InvalidArea(area))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn luminance(kelvin: f64, intensity: &Intensity) -> f64 {
+    // Radiance of a Lambertian emitter is its flux over π times its area
+    match intensity {
+        Intensity::Luminance { luminance } => *luminance,
+        Intensity::Watts { watts, area } => watts / (PI * area) * luminous_fraction(kelvin),
+        Intensity::Lumens { lumens, area } => lumens / (PI * area) / MAXIMUM_LUMINOUS_EFFICACY,
+    }
+}
+
+/// Fraction of the radiance of a blackbody that counts towards its luminance, i.e. its luminous
+/// efficacy relative to the maximum
+fn luminous_fraction(kelvin: f64) -> f64 {
+    let range = WAVELENGTH_MAX - WAVELENGTH_MIN;
+    let visible: f64 = (0..LUMINANCE_STEPS)
+        .map(|step| {
+            let t = (step as f64 + 0.5) / LUMINANCE_STEPS as f64;
+            let wavelength = WAVELENGTH_MIN + t * range;
+            let (_, y, _) = xyz(wavelength);
+            planck(wavelength, kelvin) * y
+        })
+        .sum();
+    let visible = visible * range * 1e-9 / LUMINANCE_STEPS as f64;
+
+    let total = STEFAN_BOLTZMANN * kelvin.powi(4) / PI;
+    visible / total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_luminance_intensity() {
+        let intensity = Intensity::Luminance { luminance: 4.0 };
+
+        assert_approx_eq!(
+            Temperature::new(2700.0, intensity.clone())
+                .colour()
+                .luminance(),
+            4.0
+        );
+        assert_approx_eq!(
+            Temperature::new(6500.0, intensity).colour().luminance(),
+            4.0
+        );
+    }
+
+    #[test]
+    fn test_lumens_intensity() {
+        let intensity = Intensity::Lumens {
+            lumens: 683.0 * PI,
+            area: 1.0,
+        };
+
+        assert_approx_eq!(
+            Temperature::new(4000.0, intensity).colour().luminance(),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_luminous_efficacy_of_blackbodies() {
+        // Close to the radiant efficacy of an incandescent filament and of sunlight
+        let incandescent = MAXIMUM_LUMINOUS_EFFICACY * luminous_fraction(2856.0);
+        let daylight = MAXIMUM_LUMINOUS_EFFICACY * luminous_fraction(5800.0);
+
+        assert_approx_eq!(incandescent, 16.5, 0.5);
+        assert_approx_eq!(daylight, 92.3, 1.0);
+    }
+
+    #[test]
+    fn test_temperature_colours() {
+        let intensity = Intensity::Luminance { luminance: 1.0 };
+        let warm = Temperature::new(2700.0, intensity.clone()).colour();
+        let cool = Temperature::new(10000.0, intensity).colour();
+
+        assert!(warm.r() > warm.b());
+        assert!(cool.b() > cool.r());
+    }
+
+    #[test]
+    fn test_temperature_is_read_from_its_parameters() {
+        let yaml = "
+kelvin: 2700.0
+intensity:
+  Watts:
+    watts: 60.0
+    area: 0.01
+";
+        let temperature: Temperature = serde_yaml::from_str(yaml).unwrap();
+        let intensity = Intensity::Watts {
+            watts: 60.0,
+            area: 0.01,
+        };
+        let expected = Temperature::new(2700.0, intensity);
+        assert_approx_eq!(temperature.colour().r(), expected.colour().r());
+        assert_approx_eq!(temperature.colour().b(), expected.colour().b());
+        assert_approx_eq!(
+            temperature.spectral_value(550.0),
+            expected.spectral_value(550.0)
+        );
+
+        let serialised = serde_yaml::to_string(&temperature).unwrap();
+        assert!(serialised.contains("kelvin: 2700.0"));
+    }
+
+    #[test]
+    fn test_temperature_is_validated() {
+        let luminance = Intensity::Luminance { luminance: 1.0 };
+        assert!(Temperature::new(2700.0, luminance.clone())
+            .validate()
+            .is_ok());
+        assert!(Temperature::new(0.0, luminance).validate().is_err());
+
+        let lumens = Intensity::Lumens {
+            lumens: 800.0,
+            area: 0.0,
+        };
+        assert!(Temperature::new(2700.0, lumens).validate().is_err());
+        let watts = Intensity::Watts {
+            watts: 60.0,
+            area: -1.0,
+        };
+        assert!(Temperature::new(2700.0, watts).validate().is_err());
+    }
+}